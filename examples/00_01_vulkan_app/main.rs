@@ -6,7 +6,8 @@ use {
         app::{app_main, App, AppState},
         graphics::vulkan::{
             FrameStatus, FramesInFlight, PresentImageStatus,
-            RequiredDeviceFeatures, Swapchain, VulkanContext,
+            RequiredDeviceFeatures, Swapchain, SwapchainSettings,
+            VulkanContext,
         },
         unwrap_here,
     },
@@ -49,7 +50,12 @@ impl App for VulkanApp {
         let PhysicalSize { width, height } = window.inner_size();
         let swapchain = unwrap_here!(
             "Create the application swapchain",
-            Swapchain::new(ctx.clone(), (width, height), None)
+            Swapchain::new(
+                ctx.clone(),
+                (width, height),
+                None,
+                &SwapchainSettings::default(),
+            )
         );
 
         let frames_in_flight = unwrap_here!(
@@ -103,7 +109,12 @@ impl App for VulkanApp {
             let PhysicalSize { width, height } = window.inner_size();
             self.swapchain = unwrap_here!(
                 "rebuild the swapchain",
-                Swapchain::new(self.ctx.clone(), (width, height), None,)
+                Swapchain::new(
+                    self.ctx.clone(),
+                    (width, height),
+                    None,
+                    &SwapchainSettings::default(),
+                )
             );
         }
 
@@ -31,7 +31,7 @@ impl Demo for ExampleDemo {
 
     /// Initialize the demo
     fn new(
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         _gfx: &mut Graphics,
         _args: &Args,
     ) -> Result<Self> {
@@ -41,7 +41,7 @@ impl Demo for ExampleDemo {
     /// Draw a frame
     fn draw(
         &mut self,
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         gfx: &mut Graphics,
         frame: &Frame,
     ) -> Result<AppState> {
@@ -36,7 +36,7 @@ impl Demo for FirstTriangle {
 
     /// Initialize the demo
     fn new(
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         gfx: &mut Graphics,
         _args: &Args,
     ) -> Result<Self> {
@@ -48,7 +48,7 @@ impl Demo for FirstTriangle {
     /// Draw a frame
     fn draw(
         &mut self,
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         gfx: &mut Graphics,
         frame: &Frame,
     ) -> Result<AppState> {
@@ -0,0 +1,321 @@
+use {
+    anyhow::{Context, Result},
+    ash::vk,
+    clap::Parser,
+    demo_vk::{
+        app::{multi_window_app_main, AppState, MultiWindowApp},
+        graphics::vulkan::{
+            raii, FrameStatus, FramesInFlight, PresentImageStatus,
+            RequiredDeviceFeatures, Swapchain, SwapchainSettings,
+            VulkanContext,
+        },
+        unwrap_here,
+    },
+    std::sync::Arc,
+    winit::{
+        dpi::{LogicalSize, PhysicalSize},
+        event::WindowEvent,
+        event_loop::ActiveEventLoop,
+        keyboard::{KeyCode, PhysicalKey},
+        window::{Fullscreen, Window, WindowAttributes, WindowId},
+    },
+};
+
+#[derive(Debug, Parser)]
+struct Args {}
+
+/// The surface, swapchain, and frames in flight for one window.
+///
+/// Targets don't own their windows. A window must outlive its surface, so the
+/// windows are kept in [MultiWindowExample] and dropped after every target and
+/// the context.
+struct WindowTarget {
+    frames_in_flight: FramesInFlight,
+    swapchain: Swapchain,
+    swapchain_needs_rebuild: bool,
+    surface: Arc<raii::Surface>,
+    clear_color: [f32; 4],
+    window_id: WindowId,
+}
+
+impl WindowTarget {
+    fn new(
+        ctx: &Arc<VulkanContext>,
+        surface: Arc<raii::Surface>,
+        window: &Window,
+        clear_color: [f32; 4],
+    ) -> Result<Self> {
+        let PhysicalSize { width, height } = window.inner_size();
+        let swapchain = unwrap_here!(
+            "Create the window's swapchain",
+            Swapchain::for_surface(
+                ctx.clone(),
+                surface.clone(),
+                (width, height),
+                None,
+                &SwapchainSettings::default(),
+            )
+        );
+        let frames_in_flight = unwrap_here!(
+            "Create the window's frames-in-flight synchronization",
+            FramesInFlight::new(ctx.clone(), swapchain.images().len(), 2)
+        );
+        window.set_visible(true);
+        Ok(Self {
+            frames_in_flight,
+            swapchain,
+            swapchain_needs_rebuild: false,
+            surface,
+            clear_color,
+            window_id: window.id(),
+        })
+    }
+
+    /// Clears the window's next swapchain image and presents it.
+    fn draw(
+        &mut self,
+        ctx: &Arc<VulkanContext>,
+        window: &Window,
+    ) -> Result<()> {
+        if self.swapchain_needs_rebuild {
+            self.swapchain_needs_rebuild = false;
+            unwrap_here!(
+                "Wait for all frames to finish before rebuilding the swapchain",
+                self.frames_in_flight.wait_for_all_frames_to_complete()
+            );
+            let PhysicalSize { width, height } = window.inner_size();
+            self.swapchain = unwrap_here!(
+                "Rebuild the window's swapchain",
+                Swapchain::for_surface(
+                    ctx.clone(),
+                    self.surface.clone(),
+                    (width, height),
+                    Some(self.swapchain.raw()),
+                    &SwapchainSettings::default(),
+                )
+            );
+            unwrap_here!(
+                "Rebuild the window's swapchain semaphores",
+                self.frames_in_flight.rebuild_swapchain_semaphores(
+                    ctx,
+                    self.swapchain.images().len(),
+                )
+            );
+        }
+
+        let frame = match self.frames_in_flight.start_frame(&self.swapchain)? {
+            FrameStatus::FrameStarted(frame) => frame,
+            FrameStatus::SwapchainNeedsRebuild => {
+                self.swapchain_needs_rebuild = true;
+                return Ok(());
+            }
+        };
+
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        unsafe {
+            ctx.cmd_pipeline_barrier(
+                frame.command_buffer(),
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[vk::ImageMemoryBarrier {
+                    dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+                    old_layout: vk::ImageLayout::UNDEFINED,
+                    new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    image: frame.swapchain_image(),
+                    subresource_range,
+                    ..Default::default()
+                }],
+            );
+            ctx.cmd_clear_color_image(
+                frame.command_buffer(),
+                frame.swapchain_image(),
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &vk::ClearColorValue {
+                    float32: self.clear_color,
+                },
+                &[subresource_range],
+            );
+            ctx.cmd_pipeline_barrier(
+                frame.command_buffer(),
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[vk::ImageMemoryBarrier {
+                    src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+                    old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    new_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+                    image: frame.swapchain_image(),
+                    subresource_range,
+                    ..Default::default()
+                }],
+            );
+        }
+
+        let status = self
+            .frames_in_flight
+            .present_frame(&self.swapchain, frame)?;
+        if status == PresentImageStatus::SwapchainNeedsRebuild {
+            self.swapchain_needs_rebuild = true;
+        }
+        Ok(())
+    }
+}
+
+/// A small control panel window next to a large output window.
+///
+/// Press Space in either window to change the output color, F11 to toggle
+/// fullscreen for the output window, and Escape to quit.
+///
+/// Fields are dropped in order. The context holds the control panel's surface,
+/// so the windows are declared last to outlive every surface.
+struct MultiWindowExample {
+    control_panel: WindowTarget,
+    output: WindowTarget,
+    ctx: Arc<VulkanContext>,
+    control_panel_window: Window,
+    output_window: Window,
+}
+
+impl MultiWindowExample {
+    fn target_mut(&mut self, window_id: WindowId) -> Option<&mut WindowTarget> {
+        [&mut self.control_panel, &mut self.output]
+            .into_iter()
+            .find(|target| target.window_id == window_id)
+    }
+}
+
+impl MultiWindowApp for MultiWindowExample {
+    type Args = Args;
+
+    fn new(event_loop: &ActiveEventLoop, _args: &Self::Args) -> Result<Self> {
+        let control_panel_window = unwrap_here!(
+            "Create the control panel window",
+            event_loop.create_window(
+                WindowAttributes::default()
+                    .with_title("Control Panel")
+                    .with_inner_size(LogicalSize::new(320, 240))
+                    .with_visible(false)
+            )
+        );
+        let output_window = unwrap_here!(
+            "Create the output window",
+            event_loop.create_window(
+                WindowAttributes::default()
+                    .with_title("Output")
+                    .with_inner_size(LogicalSize::new(1024, 768))
+                    .with_visible(false)
+            )
+        );
+
+        // The context is created with the first window's surface, additional
+        // windows create their own surfaces on the same device.
+        let ctx = unwrap_here!(
+            "Create the Vulkan Context",
+            VulkanContext::new(
+                &control_panel_window,
+                RequiredDeviceFeatures::default()
+            )
+        );
+        let output_surface = unwrap_here!(
+            "Create the output window's surface",
+            ctx.create_surface(&output_window)
+        );
+
+        let control_panel = WindowTarget::new(
+            &ctx,
+            ctx.surface_khr.clone().context("Context has no surface")?,
+            &control_panel_window,
+            [0.2, 0.2, 0.2, 1.0],
+        )?;
+        let output = WindowTarget::new(
+            &ctx,
+            output_surface,
+            &output_window,
+            [0.1, 0.3, 0.8, 1.0],
+        )?;
+
+        Ok(Self {
+            control_panel,
+            output,
+            ctx,
+            control_panel_window,
+            output_window,
+        })
+    }
+
+    fn window(&self, window_id: WindowId) -> Option<&Window> {
+        [&self.control_panel_window, &self.output_window]
+            .into_iter()
+            .find(|window| window.id() == window_id)
+    }
+
+    fn handle_window_event(
+        &mut self,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) -> Result<AppState> {
+        match event {
+            WindowEvent::CloseRequested => return Ok(AppState::Exit),
+            WindowEvent::Resized(_) => {
+                if let Some(target) = self.target_mut(window_id) {
+                    target.swapchain_needs_rebuild = true;
+                }
+            }
+            WindowEvent::KeyboardInput { event, .. }
+                if event.state.is_pressed() =>
+            {
+                match event.physical_key {
+                    PhysicalKey::Code(KeyCode::Escape) => {
+                        return Ok(AppState::Exit);
+                    }
+                    PhysicalKey::Code(KeyCode::Space) => {
+                        let [r, g, b, a] = self.output.clear_color;
+                        self.output.clear_color = [b, r, g, a];
+                    }
+                    PhysicalKey::Code(KeyCode::F11) => {
+                        let window = &self.output_window;
+                        window.set_fullscreen(match window.fullscreen() {
+                            Some(_) => None,
+                            None => Some(Fullscreen::Borderless(
+                                window.current_monitor(),
+                            )),
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        Ok(AppState::Continue)
+    }
+
+    fn update(&mut self, window_id: WindowId) -> Result<AppState> {
+        let (target, window) = if window_id == self.control_panel_window.id() {
+            (&mut self.control_panel, &self.control_panel_window)
+        } else if window_id == self.output_window.id() {
+            (&mut self.output, &self.output_window)
+        } else {
+            return Ok(AppState::Continue);
+        };
+        let PhysicalSize { width, height } = window.inner_size();
+        if width > 0 && height > 0 {
+            target.draw(&self.ctx, window)?;
+        }
+        Ok(AppState::Continue)
+    }
+}
+
+pub fn main() {
+    let _ = multi_window_app_main::<MultiWindowExample>();
+}
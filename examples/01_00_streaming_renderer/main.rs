@@ -8,13 +8,17 @@ use {
         graphics::{
             image_memory_barrier,
             streaming_renderer::{
-                StreamingRenderer, TextureAtlas, TextureLoader, TrianglesMesh,
+                Bounds, DrawOrder, Instance, InstancedMesh, Mesh, RenderTarget,
+                StaticMesh, StreamingRenderer, TextureAtlas, TextureLoader,
+                TrianglesMesh,
+            },
+            vulkan::{
+                Frame, RequiredDeviceFeatures, ShaderInvocations, SyncCommands,
             },
-            vulkan::{Frame, RequiredDeviceFeatures},
         },
     },
-    nalgebra::Matrix4,
-    std::{f32, sync::Arc},
+    nalgebra::{Matrix4, Rotation3, Vector3},
+    std::{collections::BTreeMap, f32, sync::Arc},
     winit::{
         dpi::PhysicalSize,
         event::WindowEvent,
@@ -24,7 +28,28 @@ use {
 };
 
 #[derive(Debug, Parser)]
-struct Args {}
+struct Args {
+    /// The number of samples per pixel: 1, 2, 4, 8, 16, 32, or 64.
+    #[arg(long, default_value = "4", value_parser = parse_samples)]
+    samples: vk::SampleCountFlags,
+}
+
+fn parse_samples(value: &str) -> Result<vk::SampleCountFlags, String> {
+    match value.trim() {
+        "1" => Ok(vk::SampleCountFlags::TYPE_1),
+        "2" => Ok(vk::SampleCountFlags::TYPE_2),
+        "4" => Ok(vk::SampleCountFlags::TYPE_4),
+        "8" => Ok(vk::SampleCountFlags::TYPE_8),
+        "16" => Ok(vk::SampleCountFlags::TYPE_16),
+        "32" => Ok(vk::SampleCountFlags::TYPE_32),
+        "64" => Ok(vk::SampleCountFlags::TYPE_64),
+        _ => Err(format!(
+            "expected 1, 2, 4, 8, 16, 32, or 64 samples, got {value}"
+        )),
+    }
+}
+
+const PARTICLE_COUNT: usize = 64;
 
 pub fn ortho_projection(aspect: f32, height: f32) -> Matrix4<f32> {
     let w = height * aspect;
@@ -42,7 +67,10 @@ pub fn ortho_projection(aspect: f32, height: f32) -> Matrix4<f32> {
 struct Example {
     texture_atlas: TextureAtlas,
     mesh: TrianglesMesh,
+    particles: InstancedMesh,
+    backdrop: StaticMesh,
     g2: StreamingRenderer,
+    render_target: RenderTarget,
 }
 
 impl Demo for Example {
@@ -51,6 +79,11 @@ impl Demo for Example {
 
     fn required_device_features() -> RequiredDeviceFeatures {
         RequiredDeviceFeatures {
+            physical_device_features: vk::PhysicalDeviceFeatures {
+                // required for per-material pipeline statistics
+                pipeline_statistics_query: vk::TRUE,
+                ..Default::default()
+            },
             physical_device_dynamic_rendering_features:
                 vk::PhysicalDeviceDynamicRenderingFeatures {
                     dynamic_rendering: vk::TRUE,
@@ -69,6 +102,9 @@ impl Demo for Example {
 
                     // required for mesh buffers (vertex and transforms)
                     buffer_device_address: vk::TRUE,
+
+                    // required for GPU culling
+                    draw_indirect_count: vk::TRUE,
                     ..Default::default()
                 },
             ..Default::default()
@@ -77,9 +113,9 @@ impl Demo for Example {
 
     /// Initialize the demo
     fn new(
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         gfx: &mut Graphics,
-        _args: &Self::Args,
+        args: &Self::Args,
     ) -> Result<Self> {
         let texture_atlas = {
             let mut texture_atlas = TextureAtlas::new(&gfx.vulkan)
@@ -96,18 +132,40 @@ impl Demo for Example {
             texture_atlas
         };
 
-        let g2 = StreamingRenderer::new(
+        let samples = args.samples;
+        let mut render_target = RenderTarget::new_multisampled(
             &gfx.vulkan,
+            &gfx.frames_in_flight,
             gfx.swapchain.format(),
+            samples,
+        )
+        .context("Unable to create render target")?;
+        render_target.set_clear_color([0.7, 0.7, 0.7, 1.0]);
+
+        let mut g2 = StreamingRenderer::new_with_samples(
+            &gfx.vulkan,
+            gfx.swapchain.format(),
+            samples,
             &gfx.frames_in_flight,
             &texture_atlas,
         )
         .context("Unable to create g2 subsystem")?;
+        g2.set_draw_order(DrawOrder::Sorted);
+        g2.enable_pipeline_statistics(
+            gfx.vulkan.clone(),
+            &gfx.frames_in_flight,
+            16,
+        )
+        .context("Unable to enable pipeline statistics")?;
 
         let mesh = {
             let mut mesh =
                 TrianglesMesh::new(10, g2.default_material().clone());
             mesh.set_transform(ortho_projection(4.0 / 3.0, 20.0));
+            mesh.set_bounds(Some(Bounds::new(
+                [-0.5, -0.5, 0.0],
+                [0.5, 0.5, 0.0],
+            )));
             mesh.set_scissor(vk::Rect2D {
                 extent: gfx.swapchain.extent(),
                 ..Default::default()
@@ -115,20 +173,93 @@ impl Demo for Example {
             mesh
         };
 
+        let particles = {
+            let mut geometry =
+                TrianglesMesh::new(4, g2.default_material().clone());
+            geometry.set_transform(ortho_projection(4.0 / 3.0, 20.0));
+            geometry.set_scissor(vk::Rect2D {
+                extent: gfx.swapchain.extent(),
+                ..Default::default()
+            });
+            geometry.quad(
+                [1.0, 1.0, 1.0, 1.0],
+                -1,
+                nalgebra::vector![-0.1, 0.1, 0.0],
+                nalgebra::vector![0.1, 0.1, 0.0],
+                nalgebra::vector![0.1, -0.1, 0.0],
+                nalgebra::vector![-0.1, -0.1, 0.0],
+            );
+            // bounds must contain every instance
+            geometry.set_bounds(Some(Bounds::new(
+                [-2.1, -2.1, 0.0],
+                [2.1, 2.1, 0.0],
+            )));
+            InstancedMesh::new(geometry, PARTICLE_COUNT)
+        };
+
+        // The backdrop never changes, so it's uploaded once. Its layer draws it
+        // before the streamed meshes.
+        let backdrop = {
+            let mut geometry =
+                TrianglesMesh::new(4, g2.default_material().clone());
+            geometry.set_transform(ortho_projection(4.0 / 3.0, 20.0));
+            geometry.set_scissor(vk::Rect2D {
+                extent: gfx.swapchain.extent(),
+                ..Default::default()
+            });
+            let z = 0.5;
+            geometry.quad(
+                [0.2, 0.2, 0.3, 1.0],
+                -1,
+                nalgebra::vector![-3.0, 3.0, z],
+                nalgebra::vector![3.0, 3.0, z],
+                nalgebra::vector![3.0, -3.0, z],
+                nalgebra::vector![-3.0, -3.0, z],
+            );
+            geometry.set_bounds(Bounds::from_vertices(geometry.vertices()));
+            let sync_commands = SyncCommands::new(gfx.vulkan.clone())?;
+            let mut backdrop =
+                StaticMesh::from_mesh(&gfx.vulkan, &sync_commands, &geometry)
+                    .context("Unable to upload backdrop")?;
+            backdrop.set_layer(-1);
+            backdrop
+        };
+
         Ok(Self {
             texture_atlas,
             mesh,
+            particles,
+            backdrop,
             g2,
+            render_target,
         })
     }
 
     fn update(
         &mut self,
-        _window: &mut Window,
-        _gfx: &mut Graphics,
+        _window: Option<&mut Window>,
+        gfx: &mut Graphics,
     ) -> Result<AppState> {
         self.mesh.clear();
 
+        // a ring of small quads orbiting the penguin, drawn with one
+        // instanced draw call
+        self.particles.clear_instances();
+        let t = gfx.elapsed_seconds() as f32;
+        for i in 0..PARTICLE_COUNT {
+            let angle = t + i as f32 * f32::consts::TAU / PARTICLE_COUNT as f32;
+            let rotation =
+                Rotation3::from_axis_angle(&Vector3::z_axis(), angle);
+            let transform = rotation.to_homogeneous()
+                * Matrix4::new_translation(&Vector3::new(2.0, 0.0, 0.0));
+            let hue = i as f32 / PARTICLE_COUNT as f32;
+            self.particles.push_instance(Instance::new(
+                transform,
+                [hue, 1.0 - hue, 1.0, 1.0],
+                -1,
+            ));
+        }
+
         let z = 0.0;
         self.mesh.quad(
             [1.0, 1.0, 1.0, 1.0],
@@ -145,7 +276,7 @@ impl Demo for Example {
     /// Draw a frame
     fn draw(
         &mut self,
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         gfx: &mut Graphics,
         frame: &Frame,
     ) -> Result<AppState> {
@@ -159,52 +290,43 @@ impl Demo for Example {
             .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
             .call();
 
-        unsafe {
-            let color_attachments = [vk::RenderingAttachmentInfo {
-                image_view: frame.swapchain_image_view(),
-                image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-                resolve_mode: vk::ResolveModeFlags::NONE,
-                load_op: vk::AttachmentLoadOp::CLEAR,
-                store_op: vk::AttachmentStoreOp::STORE,
-                clear_value: vk::ClearValue {
-                    color: vk::ClearColorValue {
-                        float32: [0.7, 0.7, 0.7, 1.0],
-                    },
-                },
-                ..Default::default()
-            }];
-            gfx.vulkan.cmd_begin_rendering(
-                frame.command_buffer(),
-                &vk::RenderingInfo {
-                    render_area: vk::Rect2D {
-                        offset: vk::Offset2D { x: 0, y: 0 },
-                        extent: gfx.swapchain.extent(),
-                    },
-                    layer_count: 1,
-                    color_attachment_count: 1,
-                    p_color_attachments: color_attachments.as_ptr(),
-                    ..Default::default()
-                },
+        // a material's pipeline can be drawn in several runs, so sum them
+        let mut invocations: BTreeMap<String, ShaderInvocations> =
+            BTreeMap::new();
+        for (name, run) in self.g2.read_pipeline_statistics(frame)? {
+            *invocations.entry(name).or_default() += run;
+        }
+        for (name, invocations) in invocations {
+            gfx.metrics.record_metric(
+                format!("{name} vertex invocations"),
+                invocations.vertex as f32,
             );
-            gfx.vulkan.cmd_set_viewport(
-                frame.command_buffer(),
-                0,
-                &[vk::Viewport {
-                    x: 0.0,
-                    y: 0.0,
-                    width: gfx.swapchain.extent().width as f32,
-                    height: gfx.swapchain.extent().height as f32,
-                    min_depth: 0.0,
-                    max_depth: 1.0,
-                }],
+            gfx.metrics.record_metric(
+                format!("{name} fragment invocations"),
+                invocations.fragment as f32,
             );
-            self.g2
-                .bind_texture_atlas(&gfx.vulkan, frame, &self.texture_atlas);
-            self.g2.prepare_meshes(&gfx.vulkan, frame, &[&self.mesh])?;
-            self.g2.write_draw_commands(&gfx.vulkan, frame)?;
-            gfx.vulkan.cmd_end_rendering(frame.command_buffer());
         }
 
+        // meshes are culled on the GPU before rendering begins
+        let batch = self.g2.prepare_meshes(
+            &gfx.vulkan,
+            frame,
+            &[&self.mesh, &self.particles, &self.backdrop],
+        )?;
+        self.g2.cull_batch(&gfx.vulkan, frame, batch)?;
+
+        self.render_target.begin_rendering(
+            &gfx.vulkan,
+            frame,
+            frame.swapchain_image_view(),
+            gfx.swapchain.extent(),
+        )?;
+        self.g2
+            .bind_texture_atlas(&gfx.vulkan, frame, &self.texture_atlas);
+        self.g2
+            .write_batch_draw_commands(&gfx.vulkan, frame, batch)?;
+        self.render_target.end_rendering(&gfx.vulkan, frame);
+
         image_memory_barrier()
             .ctx(&gfx.vulkan)
             .command_buffer(frame.command_buffer())
@@ -221,7 +343,7 @@ impl Demo for Example {
     fn handle_window_event(
         &mut self,
         _window: &mut Window,
-        _gfx: &mut Graphics,
+        gfx: &mut Graphics,
         event: WindowEvent,
     ) -> Result<AppState> {
         match event {
@@ -229,16 +351,27 @@ impl Demo for Example {
                 if event.physical_key == PhysicalKey::Code(KeyCode::Escape) {
                     return Ok(AppState::Exit);
                 }
+                // log the frame metrics, including per-material statistics
+                if event.physical_key == PhysicalKey::Code(KeyCode::KeyM)
+                    && event.state.is_pressed()
+                {
+                    log::info!("{}", gfx.metrics);
+                }
             }
             WindowEvent::Resized(PhysicalSize { width, height }) => {
-                self.mesh.set_transform(ortho_projection(
-                    width as f32 / height as f32,
-                    20.0,
-                ));
-                self.mesh.set_scissor(vk::Rect2D {
+                let transform =
+                    ortho_projection(width as f32 / height as f32, 20.0);
+                let scissor = vk::Rect2D {
                     offset: vk::Offset2D { x: 0, y: 0 },
                     extent: vk::Extent2D { width, height },
-                });
+                };
+                self.mesh.set_transform(transform);
+                self.mesh.set_scissor(scissor);
+                let geometry = self.particles.geometry_mut();
+                geometry.set_transform(transform);
+                geometry.set_scissor(scissor);
+                self.backdrop.set_transform(transform);
+                self.backdrop.set_scissor(scissor);
             }
             _ => {}
         };
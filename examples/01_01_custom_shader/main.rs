@@ -8,14 +8,15 @@ use {
         graphics::{
             image_memory_barrier,
             streaming_renderer::{
-                StreamingRenderer, TextureAtlas, TrianglesMesh,
+                MaterialState, RenderTarget, StreamingRenderer, TextureAtlas,
+                TrianglesMesh,
             },
             vulkan::{raii, spirv_words, Frame, RequiredDeviceFeatures},
         },
         unwrap_here,
     },
     nalgebra::Matrix4,
-    std::time::Instant,
+    std::sync::Arc,
     winit::{
         dpi::PhysicalSize,
         event::WindowEvent,
@@ -47,12 +48,28 @@ struct FrameData {
     current_time: f32,
 }
 
+/// Per-material parameters, read by ./triangle.frag.glsl.
+#[derive(Debug, Copy, Clone)]
+#[repr(C, align(16))]
+struct WaveParameters {
+    color: [f32; 4],
+    frequency: f32,
+    speed: f32,
+}
+
+/// A vertex without color or texture data, read by ./triangle.vert.glsl.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+struct WaveVertex {
+    pos: [f32; 2],
+    uv: [f32; 2],
+}
+
 struct Example {
+    render_target: RenderTarget,
     texture_atlas: TextureAtlas,
-    mesh: TrianglesMesh,
-    g2: StreamingRenderer<FrameData>,
-    app_start: Instant,
-    last_frame: Instant,
+    mesh: TrianglesMesh<WaveVertex>,
+    g2: StreamingRenderer<FrameData, WaveVertex>,
 }
 
 impl Demo for Example {
@@ -86,7 +103,7 @@ impl Demo for Example {
     }
 
     fn new(
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         gfx: &mut Graphics,
         _args: &Self::Args,
     ) -> Result<Self> {
@@ -96,8 +113,8 @@ impl Demo for Example {
         );
 
         let g2 = unwrap_here!(
-            "Create streaming renderer with custom frame data",
-            StreamingRenderer::<FrameData>::new(
+            "Create streaming renderer with custom frame and vertex data",
+            StreamingRenderer::<FrameData, WaveVertex>::new(
                 &gfx.vulkan,
                 gfx.swapchain.format(),
                 &gfx.frames_in_flight,
@@ -106,9 +123,20 @@ impl Demo for Example {
         );
 
         let mesh = {
+            let vertex_shader_spirv =
+                spirv_words(include_bytes!("./triangle.vert.spv"))?;
+            let vertex_module = Arc::new(raii::ShaderModule::new(
+                "Custom Vertex Module",
+                gfx.vulkan.device.clone(),
+                &vk::ShaderModuleCreateInfo {
+                    code_size: vertex_shader_spirv.len() * 4,
+                    p_code: vertex_shader_spirv.as_ptr(),
+                    ..Default::default()
+                },
+            )?);
             let fragment_shader_spirv =
                 spirv_words(include_bytes!("./triangle.frag.spv"))?;
-            let fragment_module = raii::ShaderModule::new(
+            let fragment_module = Arc::new(raii::ShaderModule::new(
                 "Custom Fragment Module",
                 gfx.vulkan.device.clone(),
                 &vk::ShaderModuleCreateInfo {
@@ -116,10 +144,20 @@ impl Demo for Example {
                     p_code: fragment_shader_spirv.as_ptr(),
                     ..Default::default()
                 },
-            )?;
+            )?);
             let mut mesh = TrianglesMesh::new(
                 10,
-                g2.new_material(&gfx.vulkan, None, Some(&fragment_module))?,
+                g2.new_material_with_parameters(
+                    &gfx.vulkan,
+                    Some(&vertex_module),
+                    Some(&fragment_module),
+                    MaterialState::default(),
+                    WaveParameters {
+                        color: [0.2, 0.8, 1.0, 1.0],
+                        frequency: 1.5,
+                        speed: 1.0,
+                    },
+                )?,
             );
             mesh.set_transform(ortho_projection(1.0, 1.0));
             mesh.set_scissor(vk::Rect2D {
@@ -129,30 +167,34 @@ impl Demo for Example {
             mesh
         };
 
+        let mut render_target = RenderTarget::new(&gfx.frames_in_flight);
+        render_target.set_clear_color([0.7, 0.7, 0.7, 1.0]);
+
         Ok(Self {
+            render_target,
             texture_atlas,
             mesh,
             g2,
-            app_start: Instant::now(),
-            last_frame: Instant::now(),
         })
     }
 
     fn update(
         &mut self,
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         _gfx: &mut Graphics,
     ) -> Result<AppState> {
         self.mesh.clear();
 
-        let z = 0.0;
-        self.mesh.quad(
-            [1.0, 1.0, 1.0, 1.0],
-            -1,
-            nalgebra::vector![-0.5, 0.5, z],
-            nalgebra::vector![0.5, 0.5, z],
-            nalgebra::vector![0.5, -0.5, z],
-            nalgebra::vector![-0.5, -0.5, z],
+        let vertex = |pos, uv| WaveVertex { pos, uv };
+        self.mesh.indexed_triangles(
+            [
+                vertex([-0.5, 0.5], [0.0, 0.0]),
+                vertex([0.5, 0.5], [1.0, 0.0]),
+                vertex([0.5, -0.5], [1.0, 1.0]),
+                vertex([-0.5, -0.5], [0.0, 1.0]),
+            ]
+            .into_iter(),
+            [0, 1, 2, 0, 2, 3].into_iter(),
         );
 
         Ok(AppState::Continue)
@@ -161,22 +203,17 @@ impl Demo for Example {
     /// Draw a frame
     fn draw(
         &mut self,
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         gfx: &mut Graphics,
         frame: &Frame,
     ) -> Result<AppState> {
         self.g2.set_frame_constants(
             frame,
             FrameData {
-                delta_time: Instant::now()
-                    .duration_since(self.last_frame)
-                    .as_secs_f32(),
-                current_time: Instant::now()
-                    .duration_since(self.app_start)
-                    .as_secs_f32(),
+                delta_time: gfx.frame_delta_seconds(),
+                current_time: gfx.elapsed_seconds() as f32,
             },
         )?;
-        self.last_frame = Instant::now();
 
         image_memory_barrier()
             .ctx(&gfx.vulkan)
@@ -188,51 +225,19 @@ impl Demo for Example {
             .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
             .call();
 
-        unsafe {
-            let color_attachments = [vk::RenderingAttachmentInfo {
-                image_view: frame.swapchain_image_view(),
-                image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-                resolve_mode: vk::ResolveModeFlags::NONE,
-                load_op: vk::AttachmentLoadOp::CLEAR,
-                store_op: vk::AttachmentStoreOp::STORE,
-                clear_value: vk::ClearValue {
-                    color: vk::ClearColorValue {
-                        float32: [0.7, 0.7, 0.7, 1.0],
-                    },
-                },
-                ..Default::default()
-            }];
-            gfx.vulkan.cmd_begin_rendering(
-                frame.command_buffer(),
-                &vk::RenderingInfo {
-                    render_area: vk::Rect2D {
-                        offset: vk::Offset2D { x: 0, y: 0 },
-                        extent: gfx.swapchain.extent(),
-                    },
-                    layer_count: 1,
-                    color_attachment_count: 1,
-                    p_color_attachments: color_attachments.as_ptr(),
-                    ..Default::default()
-                },
-            );
-            gfx.vulkan.cmd_set_viewport(
-                frame.command_buffer(),
-                0,
-                &[vk::Viewport {
-                    x: 0.0,
-                    y: 0.0,
-                    width: gfx.swapchain.extent().width as f32,
-                    height: gfx.swapchain.extent().height as f32,
-                    min_depth: 0.0,
-                    max_depth: 1.0,
-                }],
-            );
-            self.g2
-                .bind_texture_atlas(&gfx.vulkan, frame, &self.texture_atlas);
+        let batch =
             self.g2.prepare_meshes(&gfx.vulkan, frame, &[&self.mesh])?;
-            self.g2.write_draw_commands(&gfx.vulkan, frame)?;
-            gfx.vulkan.cmd_end_rendering(frame.command_buffer());
-        }
+        self.render_target.begin_rendering(
+            &gfx.vulkan,
+            frame,
+            frame.swapchain_image_view(),
+            gfx.swapchain.extent(),
+        )?;
+        self.g2
+            .bind_texture_atlas(&gfx.vulkan, frame, &self.texture_atlas);
+        self.g2
+            .write_batch_draw_commands(&gfx.vulkan, frame, batch)?;
+        self.render_target.end_rendering(&gfx.vulkan, frame);
 
         image_memory_barrier()
             .ctx(&gfx.vulkan)
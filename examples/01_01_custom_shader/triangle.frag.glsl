@@ -1,6 +1,9 @@
 #version 460
 #pragma shader_stage(fragment)
 
+// Adds support for buffer references, used for material parameters
+#extension GL_EXT_buffer_reference : enable
+
 // Adds support for non-uniform indexing and variable sized descriptor arrays
 #extension GL_EXT_nonuniform_qualifier : require
 
@@ -14,6 +17,20 @@ layout(set = 1, binding = 0) uniform ubo {
     float current_time;
 } u_FrameConstants;
 
+// Matches WaveParameters in main.rs
+struct Parameters {
+    vec4 color;
+    float frequency;
+    float speed;
+};
+
+layout(buffer_reference, std430) readonly buffer ParametersBuffer {
+    Parameters data;
+};
+layout(push_constant) uniform constants {
+    layout(offset = 40) ParametersBuffer parameters;
+} pc_Constants;
+
 // Inputs
 layout(location = 0) in vec4 in_VertexColor;
 layout(location = 1) in vec2 in_UV;
@@ -23,7 +40,8 @@ layout(location = 2) flat in int in_TextureIndex;
 layout(location = 0) out vec4 out_FragColor;
 
 void main() {
-    float t = u_FrameConstants.current_time;
+    Parameters parameters = pc_Constants.parameters.data;
+    float t = u_FrameConstants.current_time * parameters.speed;
     float pi = 3.1415;
     float tau = pi * 2;
 
@@ -32,12 +50,12 @@ void main() {
     {
         float line_h =
             0.5
-                + 0.125 * cos(t * pi + in_UV.x * tau * 1.5)
+                + 0.125 * cos(t * pi + in_UV.x * tau * parameters.frequency)
                 + 0.0125 * cos(0.5 * pi + t * pi + in_UV.x * tau * 3.2)
                 + 0.006 * cos(0.31 * pi + t * pi * 1.2 + in_UV.x * tau * 7.8);
         float d_line = 1.0 - (abs(in_UV.y - line_h) / 0.5);
         frag_color += pow(d_line, 32.0);
     }
 
-    out_FragColor = vec4(vec3(frag_color), 1.0);
+    out_FragColor = vec4(frag_color * parameters.color.rgb, 1.0);
 }
@@ -0,0 +1,64 @@
+#version 460
+#pragma shader_stage(vertex)
+
+// Adds support for buffer references, used for vertex data
+#extension GL_EXT_buffer_reference : enable
+
+#extension GL_EXT_shader_explicit_arithmetic_types_int32: enable
+
+// Matches WaveVertex in main.rs
+struct Vertex {
+    vec2 pos;
+    vec2 uv;
+};
+
+struct MeshTransform {
+    mat4 transform;
+};
+
+struct Instance {
+    mat4 transform;
+    vec4 color;
+    int texture_index;
+};
+
+// Push Constants
+layout(buffer_reference, std430) readonly buffer VertexBuffer {
+    Vertex data[];
+};
+layout(buffer_reference, std430) readonly buffer TransformBuffer {
+    MeshTransform data[];
+};
+layout(buffer_reference, std430) readonly buffer InstanceBuffer {
+    Instance data[];
+};
+layout(buffer_reference, std430) readonly buffer TransformIndexBuffer {
+    uint32_t data[];
+};
+layout(push_constant) uniform constants {
+    VertexBuffer vertices;
+    TransformBuffer mesh_transforms;
+    uint32_t transform_index;
+    InstanceBuffer instances;
+    TransformIndexBuffer instance_transform_indices;
+} pc_Constants;
+
+// Per-Vertex outputs
+layout(location = 0) out vec4 out_VertexColor;
+layout(location = 1) out vec2 out_UV;
+layout(location = 2) flat out int out_TextureIndex;
+
+void main() {
+    Vertex vert = pc_Constants.vertices.data[gl_VertexIndex];
+    out_VertexColor = vec4(1.0);
+    out_TextureIndex = -1;
+    out_UV = vert.uv;
+
+    uint32_t transform_index =
+        pc_Constants.instance_transform_indices.data[gl_InstanceIndex];
+    mat4 transform =
+        pc_Constants.mesh_transforms.data[transform_index].transform
+        * pc_Constants.instances.data[gl_InstanceIndex].transform;
+
+    gl_Position = transform * vec4(vert.pos, 0.0, 1.0);
+}
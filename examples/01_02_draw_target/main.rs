@@ -6,16 +6,16 @@ use {
         app::AppState,
         demo::{demo_main, Demo, Graphics},
         graphics::{
-            image_memory_barrier,
+            render_graph::{RenderGraph, TransientImagePool},
             streaming_renderer::{
-                StreamingRenderer, Texture, TextureAtlas, TrianglesMesh,
+                RenderTarget, StreamingRenderer, TextureAtlas, TrianglesMesh,
             },
             vulkan::{Frame, RequiredDeviceFeatures},
+            ImageUsage,
         },
         unwrap_here,
     },
     nalgebra::Matrix4,
-    std::time::Instant,
     winit::{
         dpi::PhysicalSize,
         event::WindowEvent,
@@ -41,11 +41,11 @@ pub fn ortho_projection(aspect: f32, height: f32) -> Matrix4<f32> {
 }
 
 struct Example {
+    render_target: RenderTarget,
     texture_atlas: TextureAtlas,
     mesh: TrianglesMesh,
     g2: StreamingRenderer,
-    draw_target: Texture,
-    start: Instant,
+    transient_images: TransientImagePool,
 }
 
 impl Demo for Example {
@@ -74,12 +74,18 @@ impl Demo for Example {
                     buffer_device_address: vk::TRUE,
                     ..Default::default()
                 },
+            // required for render graph barriers
+            physical_device_synchronization2_features:
+                vk::PhysicalDeviceSynchronization2Features {
+                    synchronization2: vk::TRUE,
+                    ..Default::default()
+                },
             ..Default::default()
         }
     }
 
     fn new(
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         gfx: &mut Graphics,
         _args: &Self::Args,
     ) -> Result<Self> {
@@ -113,36 +119,22 @@ impl Demo for Example {
             mesh
         };
 
-        let draw_target = unwrap_here!(
-            "Create high precision offscreen render target",
-            Texture::builder()
-                .ctx(&gfx.vulkan)
-                .image_usage_flags(
-                    vk::ImageUsageFlags::COLOR_ATTACHMENT
-                        | vk::ImageUsageFlags::TRANSFER_SRC,
-                )
-                .memory_property_flags(vk::MemoryPropertyFlags::DEVICE_LOCAL)
-                .dimensions((
-                    gfx.swapchain.extent().width,
-                    gfx.swapchain.extent().height,
-                ))
-                .format(vk::Format::R16G16B16A16_SFLOAT)
-                .build()
-        );
+        let mut render_target = RenderTarget::new(&gfx.frames_in_flight);
+        render_target.set_clear_color([0.7, 0.7, 0.7, 1.0]);
 
         Ok(Self {
+            render_target,
             texture_atlas,
             mesh,
             g2,
-            draw_target,
-            start: Instant::now(),
+            transient_images: TransientImagePool::new(&gfx.frames_in_flight),
         })
     }
 
     fn update(
         &mut self,
-        _window: &mut Window,
-        _gfx: &mut Graphics,
+        _window: Option<&mut Window>,
+        gfx: &mut Graphics,
     ) -> Result<AppState> {
         self.mesh.clear();
 
@@ -156,7 +148,7 @@ impl Demo for Example {
             nalgebra::vector![-0.5, -0.5, z],
         );
 
-        let dt = Instant::now().duration_since(self.start).as_secs_f32();
+        let dt = gfx.elapsed_seconds() as f32;
         let point = nalgebra::vector![0.45 * dt.cos(), 0.45 * dt.sin(), z];
         self.mesh.quad(
             [
@@ -178,139 +170,100 @@ impl Demo for Example {
     /// Draw a frame
     fn draw(
         &mut self,
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         gfx: &mut Graphics,
         frame: &Frame,
     ) -> Result<AppState> {
-        // render to the draw target
-
-        image_memory_barrier()
-            .ctx(&gfx.vulkan)
-            .command_buffer(frame.command_buffer())
-            .image(self.draw_target.image().raw)
-            .old_layout(vk::ImageLayout::UNDEFINED)
-            .new_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .src_access_mask(vk::AccessFlags::empty())
-            .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
-            .call();
-
-        unsafe {
-            let color_attachments = [vk::RenderingAttachmentInfo {
-                image_view: self.draw_target.view().raw,
-                image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-                resolve_mode: vk::ResolveModeFlags::NONE,
-                load_op: vk::AttachmentLoadOp::CLEAR,
-                store_op: vk::AttachmentStoreOp::STORE,
-                clear_value: vk::ClearValue {
-                    color: vk::ClearColorValue {
-                        float32: [0.7, 0.7, 0.7, 1.0],
-                    },
-                },
-                ..Default::default()
-            }];
-            gfx.vulkan.cmd_begin_rendering(
-                frame.command_buffer(),
-                &vk::RenderingInfo {
-                    render_area: vk::Rect2D {
-                        offset: vk::Offset2D { x: 0, y: 0 },
-                        extent: self.draw_target.extent(),
-                    },
-                    layer_count: 1,
-                    color_attachment_count: 1,
-                    p_color_attachments: color_attachments.as_ptr(),
-                    ..Default::default()
-                },
-            );
-            gfx.vulkan.cmd_set_viewport(
-                frame.command_buffer(),
-                0,
-                &[vk::Viewport {
-                    x: 0.0,
-                    y: 0.0,
-                    width: self.draw_target.width() as f32,
-                    height: self.draw_target.height() as f32,
-                    min_depth: 0.0,
-                    max_depth: 1.0,
-                }],
-            );
-            self.g2
-                .bind_texture_atlas(&gfx.vulkan, frame, &self.texture_atlas);
-            self.g2.prepare_meshes(&gfx.vulkan, frame, &[&self.mesh])?;
-            self.g2.write_draw_commands(&gfx.vulkan, frame)?;
-            gfx.vulkan.cmd_end_rendering(frame.command_buffer());
-        }
+        let mut graph = RenderGraph::new();
+        let draw_target = graph.transient_image(
+            gfx.swapchain.extent(),
+            vk::Format::R16G16B16A16_SFLOAT,
+        );
+        let swapchain_image =
+            graph.import_swapchain_image(frame, &gfx.swapchain);
 
-        image_memory_barrier()
-            .ctx(&gfx.vulkan)
-            .command_buffer(frame.command_buffer())
-            .image(self.draw_target.image().raw)
-            .old_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
-            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
-            .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
-            .call();
+        // render to the draw target
+        graph
+            .add_pass("draw")
+            .write_image(draw_target, ImageUsage::ColorAttachment)
+            .execute(|pass| {
+                let target = pass.image(draw_target)?;
+                let batch = self.g2.prepare_meshes(
+                    pass.vulkan(),
+                    pass.frame(),
+                    &[&self.mesh],
+                )?;
+                self.render_target.begin_rendering(
+                    pass.vulkan(),
+                    pass.frame(),
+                    target.view,
+                    target.extent,
+                )?;
+                self.g2.bind_texture_atlas(
+                    pass.vulkan(),
+                    pass.frame(),
+                    &self.texture_atlas,
+                );
+                self.g2.write_batch_draw_commands(
+                    pass.vulkan(),
+                    pass.frame(),
+                    batch,
+                )?;
+                self.render_target
+                    .end_rendering(pass.vulkan(), pass.frame());
+                Ok(())
+            });
 
         // blit the draw target onto the swapchain image
-        image_memory_barrier()
-            .ctx(&gfx.vulkan)
-            .command_buffer(frame.command_buffer())
-            .image(frame.swapchain_image())
-            .old_layout(vk::ImageLayout::UNDEFINED)
-            .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
-            .src_access_mask(vk::AccessFlags::empty())
-            .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
-            .call();
-
-        unsafe {
-            gfx.vulkan.cmd_blit_image(
-                frame.command_buffer(),
-                self.draw_target.image().raw,
-                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-                frame.swapchain_image(),
-                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                &[vk::ImageBlit {
-                    src_subresource: vk::ImageSubresourceLayers {
-                        aspect_mask: vk::ImageAspectFlags::COLOR,
-                        mip_level: 0,
-                        base_array_layer: 0,
-                        layer_count: 1,
-                    },
-                    src_offsets: [
-                        vk::Offset3D::default(),
-                        vk::Offset3D {
-                            x: self.draw_target.width() as i32,
-                            y: self.draw_target.height() as i32,
-                            z: 1,
+        graph
+            .add_pass("blit to swapchain")
+            .read_image(draw_target, ImageUsage::TransferSrc)
+            .write_image(swapchain_image, ImageUsage::TransferDst)
+            .execute(|pass| unsafe {
+                let src = pass.image(draw_target)?;
+                let dst = pass.image(swapchain_image)?;
+                pass.vulkan().cmd_blit_image(
+                    pass.command_buffer(),
+                    src.image,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    dst.image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[vk::ImageBlit {
+                        src_subresource: vk::ImageSubresourceLayers {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            mip_level: 0,
+                            base_array_layer: 0,
+                            layer_count: 1,
                         },
-                    ],
-                    dst_subresource: vk::ImageSubresourceLayers {
-                        aspect_mask: vk::ImageAspectFlags::COLOR,
-                        mip_level: 0,
-                        base_array_layer: 0,
-                        layer_count: 1,
-                    },
-                    dst_offsets: [
-                        vk::Offset3D::default(),
-                        vk::Offset3D {
-                            x: gfx.swapchain.extent().width as i32,
-                            y: gfx.swapchain.extent().height as i32,
-                            z: 1,
+                        src_offsets: [
+                            vk::Offset3D::default(),
+                            vk::Offset3D {
+                                x: src.extent.width as i32,
+                                y: src.extent.height as i32,
+                                z: 1,
+                            },
+                        ],
+                        dst_subresource: vk::ImageSubresourceLayers {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            mip_level: 0,
+                            base_array_layer: 0,
+                            layer_count: 1,
                         },
-                    ],
-                }],
-                vk::Filter::LINEAR,
-            );
-        }
+                        dst_offsets: [
+                            vk::Offset3D::default(),
+                            vk::Offset3D {
+                                x: dst.extent.width as i32,
+                                y: dst.extent.height as i32,
+                                z: 1,
+                            },
+                        ],
+                    }],
+                    vk::Filter::LINEAR,
+                );
+                Ok(())
+            });
 
-        image_memory_barrier()
-            .ctx(&gfx.vulkan)
-            .command_buffer(frame.command_buffer())
-            .image(frame.swapchain_image())
-            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
-            .new_layout(vk::ImageLayout::PRESENT_SRC_KHR)
-            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
-            .dst_access_mask(vk::AccessFlags::empty())
-            .call();
+        graph.execute(&gfx.vulkan, frame, &mut self.transient_images)?;
 
         Ok(AppState::Continue)
     }
@@ -341,31 +294,6 @@ impl Demo for Example {
         };
         Ok(AppState::Continue)
     }
-
-    fn rebuild_swapchain_resources(
-        &mut self,
-        #[allow(unused_variables)] window: &mut Window,
-        #[allow(unused_variables)] gfx: &mut Graphics,
-    ) -> Result<()> {
-        // safe because no frame are in flight
-        self.draw_target = unwrap_here!(
-            "Recreate the high precision offscreen render target",
-            Texture::builder()
-                .ctx(&gfx.vulkan)
-                .image_usage_flags(
-                    vk::ImageUsageFlags::COLOR_ATTACHMENT
-                        | vk::ImageUsageFlags::TRANSFER_SRC,
-                )
-                .memory_property_flags(vk::MemoryPropertyFlags::DEVICE_LOCAL)
-                .dimensions((
-                    gfx.swapchain.extent().width,
-                    gfx.swapchain.extent().height,
-                ))
-                .format(vk::Format::R16G16B16A16_SFLOAT)
-                .build()
-        );
-        Ok(())
-    }
 }
 
 fn main() {
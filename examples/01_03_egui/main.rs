@@ -1,5 +1,5 @@
 use {
-    anyhow::Result,
+    anyhow::{Context, Result},
     ash::vk::{self},
     clap::Parser,
     demo_vk::{
@@ -7,6 +7,7 @@ use {
         demo::{demo_main, Demo, EguiPainter, Graphics},
         graphics::{
             image_memory_barrier,
+            streaming_renderer::RenderTarget,
             vulkan::{Frame, RequiredDeviceFeatures},
         },
         unwrap_here,
@@ -25,6 +26,7 @@ use {
 struct Args {}
 
 struct Example {
+    render_target: RenderTarget,
     egui_painter: EguiPainter,
     show_fps: bool,
 }
@@ -61,16 +63,20 @@ impl Demo for Example {
 
     /// Initialize the demo
     fn new(
-        window: &mut Window,
+        window: Option<&mut Window>,
         gfx: &mut Graphics,
         _args: &Self::Args,
     ) -> Result<Self> {
         log::info!("hello world");
+        let window = window.context("The egui demo requires a window")?;
         let egui_painter = unwrap_here!(
             "Create the egui painter",
             EguiPainter::new(gfx, window)
         );
+        let mut render_target = RenderTarget::new(&gfx.frames_in_flight);
+        render_target.set_clear_color([0.0, 0.0, 0.0, 0.0]);
         Ok(Self {
+            render_target,
             egui_painter,
             show_fps: false,
         })
@@ -78,10 +84,12 @@ impl Demo for Example {
 
     fn update(
         &mut self,
-        window: &mut Window,
+        window: Option<&mut Window>,
         gfx: &mut Graphics,
     ) -> Result<AppState> {
+        let window = window.context("The egui demo requires a window")?;
         let before_egui = Instant::now();
+        let mut vsync = gfx.vsync();
         unwrap_here!(
             "Update EGUI UI",
             self.egui_painter.run(gfx, window, |ctx| {
@@ -98,6 +106,11 @@ impl Demo for Example {
                                     RichText::new("Frame Metrics")
                                         .font(FontId::proportional(12.0)),
                                 );
+                                ui.checkbox(
+                                    &mut vsync,
+                                    RichText::new("VSync")
+                                        .font(FontId::proportional(12.0)),
+                                );
                                 if self.show_fps {
                                     ui.add(
                                         egui::Label::new(
@@ -122,6 +135,9 @@ impl Demo for Example {
             })
         );
         gfx.metrics.ms_since("EGUI", before_egui);
+        if vsync != gfx.vsync() {
+            gfx.set_vsync(vsync);
+        }
 
         Ok(AppState::Continue)
     }
@@ -129,7 +145,7 @@ impl Demo for Example {
     /// Draw a frame
     fn draw(
         &mut self,
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         gfx: &mut Graphics,
         frame: &Frame,
     ) -> Result<AppState> {
@@ -143,48 +159,16 @@ impl Demo for Example {
             .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
             .call();
 
+        self.render_target.begin_rendering(
+            &gfx.vulkan,
+            frame,
+            frame.swapchain_image_view(),
+            gfx.swapchain.extent(),
+        )?;
         unsafe {
-            let color_attachments = [vk::RenderingAttachmentInfo {
-                image_view: frame.swapchain_image_view(),
-                image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-                resolve_mode: vk::ResolveModeFlags::NONE,
-                load_op: vk::AttachmentLoadOp::CLEAR,
-                store_op: vk::AttachmentStoreOp::STORE,
-                clear_value: vk::ClearValue {
-                    color: vk::ClearColorValue {
-                        float32: [0.0, 0.0, 0.0, 0.0],
-                    },
-                },
-                ..Default::default()
-            }];
-            gfx.vulkan.cmd_begin_rendering(
-                frame.command_buffer(),
-                &vk::RenderingInfo {
-                    render_area: vk::Rect2D {
-                        offset: vk::Offset2D { x: 0, y: 0 },
-                        extent: gfx.swapchain.extent(),
-                    },
-                    layer_count: 1,
-                    color_attachment_count: 1,
-                    p_color_attachments: color_attachments.as_ptr(),
-                    ..Default::default()
-                },
-            );
-            gfx.vulkan.cmd_set_viewport(
-                frame.command_buffer(),
-                0,
-                &[vk::Viewport {
-                    x: 0.0,
-                    y: 0.0,
-                    width: gfx.swapchain.extent().width as f32,
-                    height: gfx.swapchain.extent().height as f32,
-                    min_depth: 0.0,
-                    max_depth: 1.0,
-                }],
-            );
             unwrap_here!("Render EGUI ui", self.egui_painter.draw(gfx, frame));
-            gfx.vulkan.cmd_end_rendering(frame.command_buffer());
         }
+        self.render_target.end_rendering(&gfx.vulkan, frame);
 
         image_memory_barrier()
             .ctx(&gfx.vulkan)
@@ -201,7 +185,7 @@ impl Demo for Example {
 
     fn rebuild_swapchain_resources(
         &mut self,
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         gfx: &mut Graphics,
     ) -> Result<()> {
         self.egui_painter.rebuild_swapchain_resources(gfx)
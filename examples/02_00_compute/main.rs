@@ -2,18 +2,19 @@ mod compute_pipeline;
 
 use {
     crate::compute_pipeline::Compute,
-    anyhow::Result,
+    anyhow::{Context, Result},
     ash::vk::{self},
     clap::Parser,
     demo_vk::{
         app::AppState,
         demo::{demo_main, Demo, EguiPainter, Graphics},
         graphics::{
-            image_memory_barrier,
-            streaming_renderer::Texture,
+            render_graph::{RenderGraph, TransientImagePool},
+            streaming_renderer::{RenderTarget, Texture},
             vulkan::{
                 raii, spirv_words, Frame, RequiredDeviceFeatures, SyncCommands,
             },
+            Barriers, ImageUsage,
         },
         unwrap_here,
     },
@@ -29,10 +30,28 @@ use {
 #[derive(Debug, Parser)]
 struct Args {}
 
+const COLOR_LAYER: vk::ImageSubresourceLayers = vk::ImageSubresourceLayers {
+    aspect_mask: vk::ImageAspectFlags::COLOR,
+    mip_level: 0,
+    base_array_layer: 0,
+    layer_count: 1,
+};
+
+/// The far corner of an image with the given extent.
+fn extent_offset(extent: vk::Extent2D) -> vk::Offset3D {
+    vk::Offset3D {
+        x: extent.width as i32,
+        y: extent.height as i32,
+        z: 1,
+    }
+}
+
 struct Example {
     gui: EguiPainter,
+    gui_target: RenderTarget,
     kernel: Compute,
     image: Arc<Texture>,
+    transient_pool: TransientImagePool,
 }
 
 impl Demo for Example {
@@ -73,10 +92,11 @@ impl Demo for Example {
 
     /// Initialize the demo
     fn new(
-        window: &mut Window,
+        window: Option<&mut Window>,
         gfx: &mut Graphics,
         _args: &Self::Args,
     ) -> Result<Self> {
+        let window = window.context("The compute demo requires a window")?;
         let gui =
             unwrap_here!("Create EGUI painter", EguiPainter::new(gfx, window));
 
@@ -130,15 +150,9 @@ impl Demo for Example {
         unwrap_here!(
             "Initialize compute image",
             one_time_commands.submit_and_wait(|command_buffer| {
-                image_memory_barrier()
-                    .ctx(&gfx.vulkan)
-                    .command_buffer(command_buffer)
-                    .image(image.image().raw)
-                    .old_layout(vk::ImageLayout::UNDEFINED)
-                    .new_layout(vk::ImageLayout::GENERAL)
-                    .src_access_mask(vk::AccessFlags::empty())
-                    .dst_access_mask(vk::AccessFlags::SHADER_WRITE)
-                    .call();
+                Barriers::new()
+                    .texture_discarding(&image, ImageUsage::StorageWrite)
+                    .record(&gfx.vulkan, command_buffer)?;
                 init.dispatch(&gfx.vulkan, command_buffer, &image);
                 Ok(())
             })
@@ -168,18 +182,25 @@ impl Demo for Example {
         };
         compute.write_descriptor_set(&gfx.vulkan, &image);
 
+        // The GUI is drawn over the blitted compute image.
+        let mut gui_target = RenderTarget::new(&gfx.frames_in_flight);
+        gui_target.set_preserve_color(true);
+
         Ok(Self {
             gui,
+            gui_target,
             image,
             kernel: compute,
+            transient_pool: TransientImagePool::new(&gfx.frames_in_flight),
         })
     }
 
     fn update(
         &mut self,
-        #[allow(unused_variables)] window: &mut Window,
+        #[allow(unused_variables)] window: Option<&mut Window>,
         #[allow(unused_variables)] gfx: &mut Graphics,
     ) -> Result<AppState> {
+        let window = window.context("The compute demo requires a window")?;
         let ui_result = self.gui.run(gfx, window, |ctx| {
             egui::Window::new("Frame Metrics")
                 .anchor(Align2::LEFT_TOP, [0.0, 0.0])
@@ -193,163 +214,98 @@ impl Demo for Example {
 
     fn draw(
         &mut self,
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         gfx: &mut Graphics,
         frame: &Frame,
     ) -> Result<AppState> {
-        image_memory_barrier()
-            .ctx(&gfx.vulkan)
-            .command_buffer(frame.command_buffer())
-            .image(self.image.image().raw)
-            .old_layout(vk::ImageLayout::UNDEFINED)
-            .new_layout(vk::ImageLayout::GENERAL)
-            .src_access_mask(vk::AccessFlags::empty())
-            .dst_access_mask(vk::AccessFlags::SHADER_WRITE)
-            .call();
-
-        self.kernel
-            .dispatch(&gfx.vulkan, frame.command_buffer(), &self.image);
-
-        image_memory_barrier()
-            .ctx(&gfx.vulkan)
-            .command_buffer(frame.command_buffer())
-            .image(self.image.image().raw)
-            .old_layout(vk::ImageLayout::GENERAL)
-            .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
-            .src_access_mask(vk::AccessFlags::SHADER_WRITE)
-            .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
-            .call();
+        let Self {
+            gui,
+            gui_target,
+            kernel,
+            image,
+            transient_pool,
+        } = self;
+        let gfx = &*gfx;
 
-        image_memory_barrier()
-            .ctx(&gfx.vulkan)
-            .command_buffer(frame.command_buffer())
-            .image(frame.swapchain_image())
-            .old_layout(vk::ImageLayout::UNDEFINED)
-            .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
-            .src_access_mask(vk::AccessFlags::empty())
-            .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
-            .call();
+        let mut graph = RenderGraph::new();
+        let compute_image = graph.import_texture(image);
+        let swapchain_image =
+            graph.import_swapchain_image(frame, &gfx.swapchain);
 
-        unsafe {
-            gfx.vulkan.cmd_blit_image(
-                frame.command_buffer(),
-                self.image.image().raw,
-                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-                frame.swapchain_image(),
-                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                &[vk::ImageBlit {
-                    src_subresource: vk::ImageSubresourceLayers {
-                        aspect_mask: vk::ImageAspectFlags::COLOR,
-                        mip_level: 0,
-                        base_array_layer: 0,
-                        layer_count: 1,
-                    },
-                    src_offsets: [
-                        vk::Offset3D::default(),
-                        vk::Offset3D {
-                            x: self.image.width() as i32,
-                            y: self.image.height() as i32,
-                            z: 1,
-                        },
-                    ],
-                    dst_subresource: vk::ImageSubresourceLayers {
-                        aspect_mask: vk::ImageAspectFlags::COLOR,
-                        mip_level: 0,
-                        base_array_layer: 0,
-                        layer_count: 1,
-                    },
-                    dst_offsets: [
-                        vk::Offset3D::default(),
-                        vk::Offset3D {
-                            x: gfx.swapchain.extent().width as i32,
-                            y: gfx.swapchain.extent().height as i32,
-                            z: 1,
-                        },
-                    ],
-                }],
-                vk::Filter::LINEAR,
-            );
-        }
+        graph
+            .add_pass("compute")
+            .write_image(compute_image, ImageUsage::StorageWrite)
+            .execute(|pass| {
+                kernel.dispatch(pass.vulkan(), pass.command_buffer(), image);
+                Ok(())
+            });
 
-        image_memory_barrier()
-            .ctx(&gfx.vulkan)
-            .command_buffer(frame.command_buffer())
-            .image(frame.swapchain_image())
-            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
-            .new_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
-            .dst_access_mask(
-                vk::AccessFlags::COLOR_ATTACHMENT_READ
-                    | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
-            )
-            .call();
+        graph
+            .add_pass("blit to swapchain")
+            .read_image(compute_image, ImageUsage::TransferSrc)
+            .write_image(swapchain_image, ImageUsage::TransferDst)
+            .execute(|pass| {
+                let src = pass.image(compute_image)?;
+                let dst = pass.image(swapchain_image)?;
+                unsafe {
+                    pass.vulkan().cmd_blit_image(
+                        pass.command_buffer(),
+                        src.image,
+                        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                        dst.image,
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                        &[vk::ImageBlit {
+                            src_subresource: COLOR_LAYER,
+                            src_offsets: [
+                                vk::Offset3D::default(),
+                                extent_offset(src.extent),
+                            ],
+                            dst_subresource: COLOR_LAYER,
+                            dst_offsets: [
+                                vk::Offset3D::default(),
+                                extent_offset(dst.extent),
+                            ],
+                        }],
+                        vk::Filter::LINEAR,
+                    );
+                }
+                Ok(())
+            });
 
-        unsafe {
-            let color_attachments = [vk::RenderingAttachmentInfo {
-                image_view: frame.swapchain_image_view(),
-                image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-                resolve_mode: vk::ResolveModeFlags::NONE,
-                load_op: vk::AttachmentLoadOp::LOAD,
-                store_op: vk::AttachmentStoreOp::STORE,
-                clear_value: vk::ClearValue {
-                    color: vk::ClearColorValue {
-                        float32: [0.0, 0.0, 0.0, 0.0],
-                    },
-                },
-                ..Default::default()
-            }];
-            gfx.vulkan.cmd_begin_rendering(
-                frame.command_buffer(),
-                &vk::RenderingInfo {
-                    render_area: vk::Rect2D {
-                        offset: vk::Offset2D { x: 0, y: 0 },
-                        extent: gfx.swapchain.extent(),
-                    },
-                    layer_count: 1,
-                    color_attachment_count: 1,
-                    p_color_attachments: color_attachments.as_ptr(),
-                    ..Default::default()
-                },
-            );
-            gfx.vulkan.cmd_set_viewport(
-                frame.command_buffer(),
-                0,
-                &[vk::Viewport {
-                    x: 0.0,
-                    y: 0.0,
-                    width: gfx.swapchain.extent().width as f32,
-                    height: gfx.swapchain.extent().height as f32,
-                    min_depth: 0.0,
-                    max_depth: 1.0,
-                }],
-            );
-            // draw
-            unwrap_here!(
-                "Draw GUI to current frame",
-                self.gui.draw(gfx, frame)
-            );
-            gfx.vulkan.cmd_end_rendering(frame.command_buffer());
-        }
+        // The GUI is drawn over the blitted image, so the pass loads the
+        // swapchain image's previous contents.
+        graph
+            .add_pass("gui")
+            .read_image(swapchain_image, ImageUsage::ColorAttachment)
+            .write_image(swapchain_image, ImageUsage::ColorAttachment)
+            .execute(|pass| {
+                let target = pass.image(swapchain_image)?;
+                gui_target.begin_rendering(
+                    pass.vulkan(),
+                    pass.frame(),
+                    target.view,
+                    target.extent,
+                )?;
+                unwrap_here!("Draw GUI to current frame", unsafe {
+                    gui.draw(gfx, pass.frame())
+                });
+                gui_target.end_rendering(pass.vulkan(), pass.frame());
+                Ok(())
+            });
 
-        image_memory_barrier()
-            .ctx(&gfx.vulkan)
-            .command_buffer(frame.command_buffer())
-            .image(frame.swapchain_image())
-            .old_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .new_layout(vk::ImageLayout::PRESENT_SRC_KHR)
-            .src_access_mask(
-                vk::AccessFlags::COLOR_ATTACHMENT_READ
-                    | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
-            )
-            .dst_access_mask(vk::AccessFlags::empty())
-            .call();
+        // The graph records every barrier and transitions the swapchain image
+        // for presentation.
+        unwrap_here!(
+            "Execute frame render graph",
+            graph.execute(&gfx.vulkan, frame, transient_pool)
+        );
 
         Ok(AppState::Continue)
     }
 
     fn rebuild_swapchain_resources(
         &mut self,
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         gfx: &mut Graphics,
     ) -> Result<()> {
         unwrap_here!(
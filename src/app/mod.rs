@@ -1,7 +1,8 @@
 //! The Winit ApplicationHandler implementation.
 //!
 //! This module defines the traits and functions required for managing the
-//! lifecycle of a Winit application with a single Vulkan-enabled window.
+//! lifecycle of a Winit application with a single Vulkan-enabled window, or
+//! with several windows via [MultiWindowApp].
 
 mod logging;
 
@@ -13,7 +14,7 @@ use {
         application::ApplicationHandler,
         event::{DeviceEvent, WindowEvent},
         event_loop::{ActiveEventLoop, EventLoop},
-        window::{Window, WindowAttributes},
+        window::{Window, WindowAttributes, WindowId},
     },
 };
 
@@ -72,6 +73,105 @@ pub trait App {
     }
 }
 
+/// Implementations of this trait can be run with multi_window_app_main to
+/// manage any number of Winit windows.
+///
+/// The application creates and owns its windows, e.g. a control panel next to
+/// a fullscreen output. Window events are routed to the application by
+/// WindowId. Windows typically share one VulkanContext and each create their
+/// own surface, Swapchain, and FramesInFlight. See
+/// [VulkanContext::create_surface](crate::graphics::vulkan::VulkanContext::create_surface).
+pub trait MultiWindowApp {
+    type Args: Sized + Parser;
+
+    /// Creates a new instance of the application along with its windows.
+    ///
+    /// Windows are created with [ActiveEventLoop::create_window]. The app
+    /// must create at least one window to receive updates.
+    fn new(event_loop: &ActiveEventLoop, args: &Self::Args) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Returns the application's window with the given id.
+    fn window(&self, window_id: WindowId) -> Option<&Window>;
+
+    /// Handles a single WindowEvent for one of the application's windows.
+    ///
+    /// By default, the application exits when any window is closed.
+    fn handle_window_event(
+        &mut self,
+        #[allow(unused_variables)] window_id: WindowId,
+        event: WindowEvent,
+    ) -> Result<AppState> {
+        if event == WindowEvent::CloseRequested {
+            return Ok(AppState::Exit);
+        }
+        Ok(AppState::Continue)
+    }
+
+    /// Handles a single DeviceEvent.
+    fn handle_device_event(
+        &mut self,
+        #[allow(unused_variables)] event: DeviceEvent,
+    ) -> Result<AppState> {
+        Ok(AppState::Continue)
+    }
+
+    /// Called in a loop when the OS requests a new frame for the window.
+    ///
+    /// Another frame is requested for the window after every update. This
+    /// method blocks event processing for every window, so it should be kept
+    /// as responsive as possible.
+    fn update(
+        &mut self,
+        #[allow(unused_variables)] window_id: WindowId,
+    ) -> Result<AppState> {
+        Ok(AppState::Continue)
+    }
+}
+
+/// Runs a single-window App as a MultiWindowApp.
+struct SingleWindowApp<A: App> {
+    // the app is dropped before the window so surfaces are destroyed first
+    app: A,
+    window: Window,
+}
+
+impl<A: App> MultiWindowApp for SingleWindowApp<A> {
+    type Args = A::Args;
+
+    fn new(event_loop: &ActiveEventLoop, args: &Self::Args) -> Result<Self> {
+        let mut window = event_loop
+            .create_window(WindowAttributes::default().with_visible(false))
+            .context("Unable to create window")?;
+        let app = A::new(&mut window, args)?;
+        Ok(Self { app, window })
+    }
+
+    fn window(&self, window_id: WindowId) -> Option<&Window> {
+        (self.window.id() == window_id).then_some(&self.window)
+    }
+
+    fn handle_window_event(
+        &mut self,
+        _window_id: WindowId,
+        event: WindowEvent,
+    ) -> Result<AppState> {
+        if event == WindowEvent::CloseRequested {
+            return Ok(AppState::Exit);
+        }
+        self.app.handle_window_event(&mut self.window, event)
+    }
+
+    fn handle_device_event(&mut self, event: DeviceEvent) -> Result<AppState> {
+        self.app.handle_device_event(&mut self.window, event)
+    }
+
+    fn update(&mut self, _window_id: WindowId) -> Result<AppState> {
+        self.app.update(&mut self.window)
+    }
+}
+
 /// The entrypoint for implementations of the App trait.
 ///
 /// Initializes logging and the application event loop. Any errors that cause
@@ -80,7 +180,49 @@ pub fn app_main<A>() -> Result<()>
 where
     A: App + 'static,
 {
-    let exit_result = try_app_main::<A>();
+    multi_window_app_main::<SingleWindowApp<A>>()
+}
+
+/// The entrypoint for implementations of the MultiWindowApp trait.
+///
+/// Behaves like [app_main], but the application manages its own windows.
+pub fn multi_window_app_main<A>() -> Result<()>
+where
+    A: MultiWindowApp + 'static,
+{
+    report_exit_error(try_app_main::<A>())
+}
+
+/// Runs a single-window App with already-parsed args.
+///
+/// Logging must already be set up, see [setup_and_parse_args].
+pub(crate) fn run_app<A>(args: A::Args) -> Result<()>
+where
+    A: App + 'static,
+{
+    run_event_loop::<SingleWindowApp<A>>(args)
+}
+
+/// Initializes logging for the application.
+pub(crate) fn setup_logging() {
+    logging::setup();
+}
+
+/// Initializes logging and parses the application's CLI args, including any
+/// args files.
+pub(crate) fn setup_and_parse_args<ArgsT: Parser>() -> Result<ArgsT> {
+    setup_logging();
+
+    let args = unwrap_here!(
+        "Parse CLI args",
+        argfile::expand_args(argfile::parse_fromfile, argfile::PREFIX)
+    );
+    Ok(ArgsT::parse_from(args))
+}
+
+/// Logs the error which caused the application to exit, if any, and returns
+/// the result unchanged.
+pub(crate) fn report_exit_error(exit_result: Result<()>) -> Result<()> {
     if let Some(err) = exit_result.as_ref().err() {
         let result: String = err
             .chain()
@@ -100,14 +242,13 @@ where
     exit_result
 }
 
-struct WinitAppHandler<A: App + 'static> {
+struct WinitAppHandler<A: MultiWindowApp + 'static> {
     args: Option<A::Args>,
     app: Option<A>,
-    window: Option<Window>,
     exit_result: Result<()>,
 }
 
-impl<A: App + 'static> WinitAppHandler<A> {
+impl<A: MultiWindowApp + 'static> WinitAppHandler<A> {
     fn process_app_state(
         &mut self,
         app_state: Result<AppState>,
@@ -124,30 +265,15 @@ impl<A: App + 'static> WinitAppHandler<A> {
     }
 }
 
-impl<A: App + 'static> ApplicationHandler for WinitAppHandler<A> {
+impl<A: MultiWindowApp + 'static> ApplicationHandler for WinitAppHandler<A> {
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
-        if self.window.is_some() {
-            // All setup logic is one-time only, so if the window already
-            // exists then do nothing.
+        let Some(args) = self.args.take() else {
+            // All setup logic is one-time only, so if the app was already
+            // created then do nothing.
             return;
-        }
-
-        self.window = match event_loop
-            .create_window(WindowAttributes::default().with_visible(false))
-        {
-            Ok(window) => Some(window),
-            Err(error) => {
-                self.exit_result =
-                    Err(error).context("Unable to create window");
-                event_loop.exit();
-                return;
-            }
         };
 
-        self.app = match A::new(
-            self.window.as_mut().unwrap(),
-            &self.args.take().unwrap(),
-        ) {
+        self.app = match A::new(event_loop, &args) {
             Ok(app) => Some(app),
             Err(error) => {
                 self.exit_result =
@@ -161,34 +287,30 @@ impl<A: App + 'static> ApplicationHandler for WinitAppHandler<A> {
     fn window_event(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
-        _window_id: winit::window::WindowId,
+        window_id: WindowId,
         event: WindowEvent,
     ) {
-        if self.app.is_none() {
+        let Some(app) = self.app.as_mut() else {
             // Quit if the app doesn't exist, this can happen when initial
             // setup failed but an event is still queued.
             self.process_app_state(Ok(AppState::Exit), event_loop);
             return;
-        }
+        };
 
         let app_state = match event {
-            WindowEvent::CloseRequested => Ok(AppState::Exit),
             WindowEvent::RedrawRequested => {
-                self.window.as_mut().unwrap().pre_present_notify();
-                let state = self
-                    .app
-                    .as_mut()
-                    .unwrap()
-                    .update(self.window.as_mut().unwrap())
+                if let Some(window) = app.window(window_id) {
+                    window.pre_present_notify();
+                }
+                let state = app
+                    .update(window_id)
                     .context("Unexpected error in App::update()!");
-                self.window.as_mut().unwrap().request_redraw();
+                if let Some(window) = app.window(window_id) {
+                    window.request_redraw();
+                }
                 state
             }
-            _ => self
-                .app
-                .as_mut()
-                .unwrap()
-                .handle_window_event(self.window.as_mut().unwrap(), event),
+            _ => app.handle_window_event(window_id, event),
         };
         self.process_app_state(app_state, event_loop);
     }
@@ -199,30 +321,29 @@ impl<A: App + 'static> ApplicationHandler for WinitAppHandler<A> {
         _device_id: winit::event::DeviceId,
         event: DeviceEvent,
     ) {
-        let app_state = self
-            .app
-            .as_mut()
-            .unwrap()
-            .handle_device_event(self.window.as_mut().unwrap(), event);
+        let Some(app) = self.app.as_mut() else {
+            return;
+        };
+        let app_state = app.handle_device_event(event);
         self.process_app_state(app_state, event_loop);
     }
 }
 
 fn try_app_main<A>() -> Result<()>
 where
-    A: App + 'static,
+    A: MultiWindowApp + 'static,
 {
-    logging::setup();
-
-    let args = unwrap_here!(
-        "Parse CLI args",
-        argfile::expand_args(argfile::parse_fromfile, argfile::PREFIX)
-    );
+    let args = setup_and_parse_args::<A::Args>()?;
+    run_event_loop::<A>(args)
+}
 
+fn run_event_loop<A>(args: A::Args) -> Result<()>
+where
+    A: MultiWindowApp + 'static,
+{
     let mut winit_app = WinitAppHandler::<A> {
-        args: Some(A::Args::parse_from(args)),
+        args: Some(args),
         app: None,
-        window: None,
         exit_result: Ok(()),
     };
 
@@ -167,13 +167,21 @@ impl EguiPainter {
 
     /// Draws the EGUI UI to the currently bound color attachment.
     ///
+    /// The painter's pipelines use [RenderTarget::DEPTH_FORMAT], so rendering
+    /// must be started with [RenderTarget::begin_rendering]. Use
+    /// [RenderTarget::set_preserve_color] to draw over an existing image.
+    ///
     /// # Safety
     ///
-    /// - This function assumes that a render pass is already started (dynamic
-    ///   or not)
+    /// - This function assumes that rendering was started by a
+    ///   [RenderTarget] with a single sample.
     /// - This function assumes that the color attachment 0 is either a
     ///   swapchain image or an image with the same dimensions and format.
-    /// - This function assumes that the viewport has already been set.
+    ///
+    /// [RenderTarget]: crate::graphics::streaming_renderer::RenderTarget
+    /// [RenderTarget::DEPTH_FORMAT]: crate::graphics::streaming_renderer::RenderTarget::DEPTH_FORMAT
+    /// [RenderTarget::begin_rendering]: crate::graphics::streaming_renderer::RenderTarget::begin_rendering
+    /// [RenderTarget::set_preserve_color]: crate::graphics::streaming_renderer::RenderTarget::set_preserve_color
     pub unsafe fn draw(&mut self, gfx: &Graphics, frame: &Frame) -> Result<()> {
         self.renderer
             .bind_texture_atlas(&gfx.vulkan, frame, &self.atlas);
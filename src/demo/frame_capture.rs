@@ -0,0 +1,119 @@
+use {
+    crate::graphics::{
+        vulkan::{Frame, Swapchain, VulkanContext},
+        ImageAccess, ImageReadback,
+    },
+    ash::vk,
+    std::path::PathBuf,
+};
+
+/// The state of a swapchain image after [super::Demo::draw].
+///
+/// The demo may have used any commands to render and transition the image, so
+/// copies wait for all of them.
+pub(super) const RENDERED_SWAPCHAIN_IMAGE: ImageAccess = ImageAccess {
+    stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+    access_mask: vk::AccessFlags2::MEMORY_WRITE,
+    layout: vk::ImageLayout::PRESENT_SRC_KHR,
+};
+
+/// A swapchain image copy which is saved once its frame completes.
+#[derive(Debug)]
+struct PendingCapture {
+    frame_value: u64,
+    readback: ImageReadback,
+    path: PathBuf,
+}
+
+/// Copies swapchain images into readback buffers and saves them as PNG files
+/// once the frames that copied them complete.
+#[derive(Debug, Default)]
+pub(super) struct FrameCapture {
+    requested: Option<PathBuf>,
+    pending: Vec<PendingCapture>,
+}
+
+impl FrameCapture {
+    /// Requests a capture of the next frame's swapchain image.
+    pub fn request(&mut self, path: PathBuf) {
+        self.requested = Some(path);
+    }
+
+    /// Records a copy of the frame's swapchain image if a capture was
+    /// requested.
+    ///
+    /// The swapchain image must be ready for presentation. Captures are
+    /// skipped with a warning if the image can't be read back, e.g. when the
+    /// swapchain was rebuilt with an unsupported format after the request.
+    pub fn record(
+        &mut self,
+        ctx: &VulkanContext,
+        frame: &Frame,
+        swapchain: &Swapchain,
+    ) {
+        let Some(path) = self.requested.take() else {
+            return;
+        };
+        let readback = match ImageReadback::new(
+            ctx,
+            swapchain.extent(),
+            swapchain.format(),
+        ) {
+            Ok(readback) => readback,
+            Err(error) => {
+                log::warn!(
+                    "Unable to capture frame to {}: {error:?}",
+                    path.display()
+                );
+                return;
+            }
+        };
+        if let Err(error) = readback.record_copy(
+            ctx,
+            frame.command_buffer(),
+            frame.swapchain_image(),
+            RENDERED_SWAPCHAIN_IMAGE,
+        ) {
+            log::warn!(
+                "Unable to capture frame to {}: {error:?}",
+                path.display()
+            );
+            return;
+        }
+        self.pending.push(PendingCapture {
+            frame_value: frame.frame_value(),
+            readback,
+            path,
+        });
+    }
+
+    /// Returns true when there are captures waiting for their frames to
+    /// complete.
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Saves every capture whose frame has completed.
+    ///
+    /// Captures which can't be saved are logged and discarded.
+    pub fn save_completed(&mut self, completed_frame_value: u64) {
+        let (completed, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|capture| capture.frame_value <= completed_frame_value);
+        self.pending = pending;
+
+        for capture in completed {
+            // SAFETY: the frame which recorded the copy has completed
+            match unsafe { capture.readback.save_png(&capture.path) } {
+                Ok(()) => log::info!(
+                    "Saved frame capture to {}",
+                    capture.path.display()
+                ),
+                Err(error) => log::warn!(
+                    "Unable to save frame capture to {}: {error:?}",
+                    capture.path.display()
+                ),
+            }
+        }
+    }
+}
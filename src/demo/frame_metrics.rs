@@ -11,6 +11,7 @@ pub struct FrameMetrics {
     ms_per_update: RollingAverage,
     ms_per_draw: RollingAverage,
     metrics: BTreeMap<String, RollingAverage>,
+    gpu_metrics: BTreeMap<String, RollingAverage>,
 }
 
 impl FrameMetrics {
@@ -32,6 +33,7 @@ impl FrameMetrics {
                 1.0 / target_fps as f32,
             ),
             metrics: BTreeMap::new(),
+            gpu_metrics: BTreeMap::new(),
         }
     }
 
@@ -92,9 +94,19 @@ impl FrameMetrics {
         metric.push(value);
     }
 
+    /// Saves a GPU duration, in milliseconds, to the frame metrics.
+    pub fn record_gpu_metric(&mut self, name: impl Into<String>, ms: f32) {
+        let metric = self
+            .gpu_metrics
+            .entry(name.into())
+            .or_insert_with(|| RollingAverage::new(self.target_fps, ms));
+        metric.push(ms);
+    }
+
     /// Resets all tracked metrics.
     pub fn reset_metrics(&mut self) {
         self.metrics.clear();
+        self.gpu_metrics.clear();
     }
 }
 
@@ -127,6 +139,16 @@ impl std::fmt::Display for FrameMetrics {
             f.write_fmt(format_args!("{:.*} : {}\n", precision, metric, name))?;
         }
 
+        if !self.gpu_metrics.is_empty() {
+            f.write_str("\n[GPU Metrics]\n-------------\n")?;
+        }
+        for (name, metric) in self.gpu_metrics.iter() {
+            f.write_fmt(format_args!(
+                "{:.*}ms : {}\n",
+                precision, metric, name
+            ))?;
+        }
+
         Ok(())
     }
 }
@@ -1,24 +1,35 @@
 mod egui_integration;
+mod frame_capture;
 mod frame_metrics;
+mod recording;
 mod rolling_average;
 
 use {
-    self::frame_metrics::FrameMetrics,
+    self::{
+        frame_capture::FrameCapture, frame_metrics::FrameMetrics,
+        recording::Recorder,
+    },
     crate::{
-        app::{app_main, App, AppState},
-        graphics::vulkan::{
-            Frame, FrameStatus, FramesInFlight, PresentImageStatus,
-            RequiredDeviceFeatures, Swapchain, VulkanContext,
+        app::{self, App, AppState},
+        graphics::{
+            vulkan::{
+                Frame, FrameStatus, FramesInFlight, GpuTimer,
+                PresentImageStatus, RequiredDeviceFeatures, Swapchain,
+                SwapchainSettings, SyncCommands, VulkanContext,
+            },
+            ImageReadback,
         },
         unwrap_here,
     },
-    anyhow::Result,
+    anyhow::{bail, Context, Result},
     ash::vk::{self},
     clap::Parser,
     spin_sleep_util::Interval,
     std::{
+        ffi::OsString,
+        path::PathBuf,
         sync::Arc,
-        time::{Duration, Instant},
+        time::{Duration, Instant, SystemTime},
     },
     winit::{
         dpi::PhysicalSize,
@@ -28,7 +39,10 @@ use {
     },
 };
 
-pub use self::egui_integration::EguiPainter;
+pub use self::{
+    egui_integration::EguiPainter,
+    recording::{RecordingArgs, RecordingFormat},
+};
 
 /// Standard graphics resources provided by the Demo.
 pub struct Graphics {
@@ -43,12 +57,237 @@ pub struct Graphics {
 
     pub metrics: FrameMetrics,
 
+    /// Records GPU timestamps for each frame. Scope timings are added to the
+    /// [FrameMetrics] once the frame completes.
+    pub gpu_timer: GpuTimer,
+
     fps_limiter: Interval,
+    swapchain_settings: SwapchainSettings,
+    frame_capture: FrameCapture,
+    recorder: Option<Recorder>,
+    frame_delta_seconds: f32,
+    elapsed_seconds: f64,
+    offscreen_size: Option<(u32, u32)>,
+
+    /// The swapchain image used by the most recently presented frame.
+    last_presented_image: Option<vk::Image>,
 
     swapchain_needs_rebuild: bool,
     paused: bool,
 }
 
+impl Graphics {
+    /// Seconds between the previous frame and the current frame.
+    ///
+    /// This is the fixed timestep while recording.
+    pub fn frame_delta_seconds(&self) -> f32 {
+        self.frame_delta_seconds
+    }
+
+    /// Seconds since the demo started.
+    ///
+    /// This is simulated time while recording, so it advances by exactly one
+    /// timestep for each recorded frame.
+    pub fn elapsed_seconds(&self) -> f64 {
+        self.elapsed_seconds
+    }
+
+    /// Returns true when every frame is being recorded to disk.
+    ///
+    /// See [RecordingArgs].
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Returns true when the demo renders to an offscreen swapchain without
+    /// a window.
+    ///
+    /// See [DemoArgs::offscreen].
+    pub fn is_offscreen(&self) -> bool {
+        self.offscreen_size.is_some()
+    }
+
+    /// Advances the demo clock by the wall-clock frame time, or by the fixed
+    /// timestep while recording.
+    fn tick_clock(&mut self, frame_seconds: f32) {
+        self.frame_delta_seconds = match &self.recorder {
+            Some(recorder) => recorder.timestep(),
+            None => frame_seconds,
+        };
+        self.elapsed_seconds += self.frame_delta_seconds as f64;
+    }
+
+    /// Enables or disables vsync.
+    ///
+    /// The swapchain is rebuilt before the next frame if the setting changed.
+    pub fn set_vsync(&mut self, enabled: bool) {
+        self.set_present_modes(if enabled {
+            Swapchain::VSYNC_PRESENT_MODES
+        } else {
+            Swapchain::NO_VSYNC_PRESENT_MODES
+        });
+    }
+
+    /// Returns true when the swapchain's present mode waits for vertical
+    /// blank.
+    pub fn vsync(&self) -> bool {
+        matches!(
+            self.swapchain.present_mode(),
+            vk::PresentModeKHR::FIFO | vk::PresentModeKHR::FIFO_RELAXED
+        )
+    }
+
+    /// Returns the settings used when creating the swapchain.
+    pub fn swapchain_settings(&self) -> &SwapchainSettings {
+        &self.swapchain_settings
+    }
+
+    /// Sets the present mode preferences used when creating the swapchain.
+    ///
+    /// The swapchain is rebuilt before the next frame if the preferences
+    /// changed.
+    pub fn set_present_modes(&mut self, present_modes: &[vk::PresentModeKHR]) {
+        self.set_swapchain_settings(SwapchainSettings {
+            present_modes: present_modes.to_vec(),
+            ..self.swapchain_settings.clone()
+        });
+    }
+
+    /// Saves the next presented frame to a PNG file at `path`.
+    ///
+    /// The file is written once the frame completes on the GPU. The swapchain
+    /// is rebuilt with TRANSFER_SRC usage if it wasn't already enabled.
+    ///
+    /// Fails without requesting a capture if the surface doesn't support
+    /// TRANSFER_SRC usage or the swapchain format can't be converted to PNG.
+    pub fn capture_next_frame(
+        &mut self,
+        path: impl Into<PathBuf>,
+    ) -> Result<()> {
+        let format = self.swapchain.format();
+        if !ImageReadback::supports_format(format) {
+            bail!("Unable to capture swapchain images with format {format:?}");
+        }
+        let usage = self.swapchain_settings.image_usage;
+        if !usage.contains(vk::ImageUsageFlags::TRANSFER_SRC) {
+            // offscreen images check their usage when they're created
+            if let Some(surface_khr) = &self.vulkan.surface_khr {
+                let capabilities = unwrap_here!(
+                    "Get surface capabilities for frame capture",
+                    unsafe {
+                        surface_khr
+                            .ext
+                            .get_physical_device_surface_capabilities(
+                                self.vulkan.physical_device,
+                                surface_khr.raw,
+                            )
+                    }
+                );
+                if !capabilities
+                    .supported_usage_flags
+                    .contains(vk::ImageUsageFlags::TRANSFER_SRC)
+                {
+                    bail!(
+                        "The surface does not support copying swapchain images"
+                    );
+                }
+            }
+            self.set_swapchain_settings(SwapchainSettings {
+                image_usage: usage | vk::ImageUsageFlags::TRANSFER_SRC,
+                ..self.swapchain_settings.clone()
+            });
+        }
+        self.frame_capture.request(path.into());
+        Ok(())
+    }
+
+    /// Waits for the most recently presented frame and returns a copy of its
+    /// swapchain image.
+    ///
+    /// This is meant for tests and tools which inspect offscreen output, see
+    /// [DemoArgs::offscreen]. It blocks until every frame in flight completes.
+    ///
+    /// Fails unless the swapchain is offscreen, because presented surface
+    /// images belong to the presentation engine, or if no frame has been
+    /// presented yet.
+    pub fn last_frame_pixels(&self) -> Result<image::RgbaImage> {
+        if !self.swapchain.is_offscreen() {
+            bail!("Only offscreen swapchain images can be read back");
+        }
+        let Some(image) = self.last_presented_image else {
+            bail!("No frame has been presented yet");
+        };
+        unwrap_here!(
+            "Wait for the last presented frame",
+            self.frames_in_flight.wait_for_all_frames_to_complete()
+        );
+
+        let readback = unwrap_here!(
+            "Create readback buffer for the last frame",
+            ImageReadback::new(
+                &self.vulkan,
+                self.swapchain.extent(),
+                self.swapchain.format(),
+            )
+        );
+        let sync_commands = SyncCommands::new(self.vulkan.clone())?;
+        unwrap_here!(
+            "Copy the last frame's swapchain image",
+            sync_commands.submit_and_wait(|command_buffer| {
+                readback.record_copy(
+                    &self.vulkan,
+                    command_buffer,
+                    image,
+                    frame_capture::RENDERED_SWAPCHAIN_IMAGE,
+                )
+            })
+        );
+
+        // SAFETY: submit_and_wait blocks until the copy completes
+        unsafe { readback.to_rgba_image() }
+    }
+
+    /// Sets the settings used when creating the swapchain.
+    ///
+    /// The swapchain is rebuilt before the next frame if the settings changed.
+    /// Demos must check the swapchain's format and usage in
+    /// [Demo::rebuild_swapchain_resources] if their resources depend on them.
+    pub fn set_swapchain_settings(&mut self, settings: SwapchainSettings) {
+        if self.swapchain_settings != settings {
+            self.swapchain_settings = settings;
+            self.swapchain_needs_rebuild = true;
+        }
+    }
+
+    /// Waits for every submitted frame and writes all of the recorder's
+    /// pending frames.
+    fn finish_recording(&mut self) -> Result<()> {
+        let Some(recorder) = &mut self.recorder else {
+            return Ok(());
+        };
+        unwrap_here!(
+            "Wait for the last recorded frames",
+            self.frames_in_flight.wait_for_all_frames_to_complete()
+        );
+        unwrap_here!(
+            "Write the last recorded frames",
+            recorder
+                .write_completed(self.frames_in_flight.submitted_frame_value())
+        );
+        Ok(())
+    }
+}
+
+impl Drop for Graphics {
+    /// Recordings can end by closing the window, so frames which are still
+    /// in flight are written before the recorder is dropped.
+    fn drop(&mut self) {
+        if let Err(error) = self.finish_recording() {
+            log::error!("Unable to finish recording!\n\n{:?}", error);
+        }
+    }
+}
+
 /// A demo is an opinionated application that automatically creates the
 /// VulkanContext, Swapchain, FramesInFlight and other common utilities.
 ///
@@ -60,18 +299,30 @@ pub struct Graphics {
 /// image. There are some operations that may not depend on the swapchain and
 /// can reasonably be started before the frame's command buffer is ready to be
 /// recorded. (ticking a physics simulation, etc...)
+///
+/// Pressing F12 saves the next frame to a PNG in the working directory. See
+/// [Graphics::capture_next_frame].
+///
+/// The window is None when the demo renders offscreen, see
+/// [DemoArgs::offscreen]. Window and device events are only delivered when
+/// there is a window.
 pub trait Demo {
-    type Args: Sized + Parser;
+    type Args: Sized + Parser + clap::Args;
     const FRAMES_IN_FLIGHT_COUNT: usize = 3;
     const INITIAL_WINDOW_SIZE: (i32, i32) = (1024, 768);
     const FRAMES_PER_SECOND: u32 = 120;
 
+    /// When true, [FramesInFlight] tracks frame completion with a timeline
+    /// semaphore rather than per-frame fences. The device's timeline_semaphore
+    /// feature is enabled automatically.
+    const TIMELINE_FRAMES_IN_FLIGHT: bool = false;
+
     /// Creates a new instance of the demo.
     /// The application is allowed to modify the window based on its own
     /// requirements. This includes modifying the polling state, fullscreen
     /// status, size, etc...
     fn new(
-        window: &mut Window,
+        window: Option<&mut Window>,
         gfx: &mut Graphics,
         args: &Self::Args,
     ) -> Result<Self>
@@ -83,6 +334,14 @@ pub trait Demo {
         RequiredDeviceFeatures::default()
     }
 
+    /// Returns the initial settings for the swapchain.
+    ///
+    /// See [Graphics::set_vsync] and [Graphics::set_swapchain_settings] to
+    /// change the settings at runtime.
+    fn swapchain_settings() -> SwapchainSettings {
+        SwapchainSettings::default()
+    }
+
     /// Handles a single window event.
     fn handle_window_event(
         &mut self,
@@ -113,7 +372,7 @@ pub trait Demo {
     /// [Self::draw].
     fn update(
         &mut self,
-        #[allow(unused_variables)] window: &mut Window,
+        #[allow(unused_variables)] window: Option<&mut Window>,
         #[allow(unused_variables)] gfx: &mut Graphics,
     ) -> Result<AppState> {
         Ok(AppState::Continue)
@@ -124,7 +383,7 @@ pub trait Demo {
     /// Called after update() once the Frame is started.
     fn draw(
         &mut self,
-        #[allow(unused_variables)] window: &mut Window,
+        #[allow(unused_variables)] window: Option<&mut Window>,
         #[allow(unused_variables)] gfx: &mut Graphics,
         #[allow(unused_variables)] frame: &Frame,
     ) -> Result<AppState> {
@@ -168,7 +427,7 @@ pub trait Demo {
     /// buffers are guaranteed to be finished.
     fn rebuild_swapchain_resources(
         &mut self,
-        #[allow(unused_variables)] window: &mut Window,
+        #[allow(unused_variables)] window: Option<&mut Window>,
         #[allow(unused_variables)] gfx: &mut Graphics,
     ) -> Result<()> {
         Ok(())
@@ -180,7 +439,7 @@ pub trait Demo {
     /// size of 0. This occurs when the application is minimized, etc..
     fn paused(
         &mut self,
-        #[allow(unused_variables)] window: &mut Window,
+        #[allow(unused_variables)] window: Option<&mut Window>,
         #[allow(unused_variables)] gfx: &mut Graphics,
     ) -> Result<()> {
         Ok(())
@@ -189,7 +448,7 @@ pub trait Demo {
     /// Called when the application is unpaused.
     fn unpaused(
         &mut self,
-        #[allow(unused_variables)] window: &mut Window,
+        #[allow(unused_variables)] window: Option<&mut Window>,
         #[allow(unused_variables)] gfx: &mut Graphics,
     ) -> Result<()> {
         Ok(())
@@ -204,11 +463,9 @@ struct DemoApp<D: Demo> {
 impl<D: Demo> DemoApp<D> {
     /// Called any time the framebuffer size may have changed.
     /// Returns the current paused status for convenience.
-    fn check_paused(&mut self, window: &mut Window) -> Result<bool> {
-        let PhysicalSize {
-            width: w,
-            height: h,
-        } = window.inner_size();
+    fn check_paused(&mut self, window: Option<&mut Window>) -> Result<bool> {
+        let (w, h) =
+            framebuffer_size(window.as_deref(), self.graphics.offscreen_size);
         let should_pause = w == 0 || h == 0;
 
         if should_pause {
@@ -225,39 +482,92 @@ impl<D: Demo> DemoApp<D> {
         }
         Ok(self.graphics.paused)
     }
-}
 
-impl<D: Demo + Sized> App for DemoApp<D> {
-    type Args = D::Args;
+    /// Creates the demo and renders the first frame.
+    ///
+    /// Without a window, the demo renders to an offscreen swapchain using a
+    /// headless Vulkan context.
+    fn create(
+        mut window: Option<&mut Window>,
+        args: &DemoArgs<D::Args>,
+    ) -> Result<Self> {
+        if let Some(window) = window.as_deref_mut() {
+            let _ = window.request_inner_size(PhysicalSize {
+                width: D::INITIAL_WINDOW_SIZE.0,
+                height: D::INITIAL_WINDOW_SIZE.1,
+            });
+            window.set_title(std::any::type_name::<D>());
+        }
 
-    fn new(window: &mut Window, args: &Self::Args) -> Result<Self>
-    where
-        Self: Sized,
-    {
-        let _ = window.request_inner_size(PhysicalSize {
-            width: D::INITIAL_WINDOW_SIZE.0,
-            height: D::INITIAL_WINDOW_SIZE.1,
-        });
-        window.set_title(std::any::type_name::<D>());
+        let mut required_device_features = D::required_device_features();
+        // Synchronization2 is core in Vulkan 1.3 and is required to record
+        // Barriers.
+        required_device_features
+            .physical_device_synchronization2_features
+            .synchronization2 = vk::TRUE;
+        if D::TIMELINE_FRAMES_IN_FLIGHT {
+            required_device_features
+                .physical_device_vulkan12_features
+                .timeline_semaphore = vk::TRUE;
+        }
+        let vulkan = match window.as_deref() {
+            Some(window) => unwrap_here!(
+                "Create Vulkan context",
+                VulkanContext::new(window, required_device_features)
+            ),
+            None => unwrap_here!(
+                "Create headless Vulkan context",
+                VulkanContext::headless(required_device_features)
+            ),
+        };
 
-        let vulkan = unwrap_here!(
-            "Create Vulkan context",
-            VulkanContext::new(window, D::required_device_features())
+        let recorder = unwrap_here!(
+            "Create frame recorder",
+            Recorder::new(&args.recording, D::FRAMES_IN_FLIGHT_COUNT)
         );
 
-        let PhysicalSize { width, height } = window.inner_size();
+        let offscreen_size = window.is_none().then(|| {
+            args.recording.record_size.unwrap_or((
+                D::INITIAL_WINDOW_SIZE.0 as u32,
+                D::INITIAL_WINDOW_SIZE.1 as u32,
+            ))
+        });
+
+        let mut swapchain_settings = D::swapchain_settings();
+        if recorder.is_some() || offscreen_size.is_some() {
+            swapchain_settings.image_usage |= vk::ImageUsageFlags::TRANSFER_SRC;
+        }
         let swapchain = unwrap_here!(
             "Create initial swapchain",
-            Swapchain::new(vulkan.clone(), (width, height), None)
+            create_swapchain(
+                &vulkan,
+                window.as_deref(),
+                offscreen_size,
+                None,
+                &swapchain_settings,
+            )
         );
 
         let frames_in_flight = unwrap_here!(
             "Create frames-in-flight",
-            FramesInFlight::new(
-                vulkan.clone(),
-                swapchain.images().len(),
-                D::FRAMES_IN_FLIGHT_COUNT,
-            )
+            if D::TIMELINE_FRAMES_IN_FLIGHT {
+                FramesInFlight::new_with_timeline_semaphore(
+                    vulkan.clone(),
+                    swapchain.images().len(),
+                    D::FRAMES_IN_FLIGHT_COUNT,
+                )
+            } else {
+                FramesInFlight::new(
+                    vulkan.clone(),
+                    swapchain.images().len(),
+                    D::FRAMES_IN_FLIGHT_COUNT,
+                )
+            }
+        );
+
+        let gpu_timer = unwrap_here!(
+            "Create GPU timer",
+            GpuTimer::new(vulkan.clone(), &frames_in_flight, 16)
         );
 
         let fps_limiter = spin_sleep_util::interval(Duration::from_secs_f64(
@@ -266,47 +576,40 @@ impl<D: Demo + Sized> App for DemoApp<D> {
 
         let mut graphics = Graphics {
             fps_limiter,
+            swapchain_settings,
+            frame_capture: FrameCapture::default(),
+            recorder,
+            frame_delta_seconds: 0.0,
+            elapsed_seconds: 0.0,
+            offscreen_size,
+            last_presented_image: None,
             vulkan,
             swapchain,
             frames_in_flight,
             metrics: FrameMetrics::new(D::FRAMES_PER_SECOND as usize),
+            gpu_timer,
             swapchain_needs_rebuild: false,
             paused: false,
         };
         let demo = unwrap_here!(
             "Initialize Demo",
-            D::new(window, &mut graphics, args)
+            D::new(window.as_deref_mut(), &mut graphics, &args.demo)
         );
 
         let mut app = Self { graphics, demo };
-        unwrap_here!("Render first frame", app.update(window));
+        unwrap_here!("Render first frame", app.tick(window.as_deref_mut()));
 
         // only show the window after rendering the first frame
-        window.set_visible(true);
+        if let Some(window) = window {
+            window.set_visible(true);
+        }
 
         Ok(app)
     }
 
-    fn handle_window_event(
-        &mut self,
-        window: &mut Window,
-        event: WindowEvent,
-    ) -> Result<AppState> {
-        self.demo
-            .handle_window_event(window, &mut self.graphics, event)
-    }
-
-    fn handle_device_event(
-        &mut self,
-        window: &mut Window,
-        event: DeviceEvent,
-    ) -> Result<AppState> {
-        self.demo
-            .handle_device_event(window, &mut self.graphics, event)
-    }
-
-    fn update(&mut self, window: &mut Window) -> Result<AppState> {
-        if self.graphics.paused && self.check_paused(window)? {
+    /// Updates the demo and renders a frame.
+    fn tick(&mut self, mut window: Option<&mut Window>) -> Result<AppState> {
+        if self.graphics.paused && self.check_paused(window.as_deref_mut())? {
             std::hint::spin_loop();
             return Ok(AppState::Continue);
         }
@@ -318,18 +621,20 @@ impl<D: Demo + Sized> App for DemoApp<D> {
                     .frames_in_flight
                     .wait_for_all_frames_to_complete()
             );
-            if self.check_paused(window)? {
+            if self.check_paused(window.as_deref_mut())? {
                 return Ok(AppState::Continue);
             }
-            let window_size = window.inner_size();
             self.graphics.swapchain = unwrap_here!(
                 "Swapchain needs rebuild - rebuild swapchain",
-                Swapchain::new(
-                    self.graphics.vulkan.clone(),
-                    (window_size.width, window_size.height),
+                create_swapchain(
+                    &self.graphics.vulkan,
+                    window.as_deref(),
+                    self.graphics.offscreen_size,
                     Some(self.graphics.swapchain.raw()),
+                    &self.graphics.swapchain_settings,
                 )
             );
+            self.graphics.last_presented_image = None;
             unwrap_here!(
                 "Rebuild swapchain semaphores for frames-in-flight sync",
                 self.graphics.frames_in_flight.rebuild_swapchain_semaphores(
@@ -340,14 +645,17 @@ impl<D: Demo + Sized> App for DemoApp<D> {
 
             unwrap_here!(
                 "Rebuild Demo's swapchain resources",
-                self.demo
-                    .rebuild_swapchain_resources(window, &mut self.graphics)
+                self.demo.rebuild_swapchain_resources(
+                    window.as_deref_mut(),
+                    &mut self.graphics
+                )
             );
 
             self.graphics.swapchain_needs_rebuild = false;
         }
 
-        self.graphics.metrics.frame_tick();
+        let frame_seconds = self.graphics.metrics.frame_tick();
+        self.graphics.tick_clock(frame_seconds);
 
         // Update application logic
         // ------------------------
@@ -355,15 +663,18 @@ impl<D: Demo + Sized> App for DemoApp<D> {
         let before_update = Instant::now();
         if unwrap_here!(
             "Demo::update()",
-            self.demo.update(window, &mut self.graphics)
+            self.demo.update(window.as_deref_mut(), &mut self.graphics)
         ) == AppState::Exit
         {
             return Ok(AppState::Exit);
         }
         self.graphics.metrics.update_tick(before_update);
 
-        // Limit FPS, wait just before acquiring the frame
-        self.graphics.fps_limiter.tick();
+        // Limit FPS, wait just before acquiring the frame. Recordings run as
+        // fast as possible.
+        if self.graphics.recorder.is_none() {
+            self.graphics.fps_limiter.tick();
+        }
 
         // Prepare frame command buffer and submit
         // ---------------------------------------
@@ -381,6 +692,36 @@ impl<D: Demo + Sized> App for DemoApp<D> {
             }
         };
 
+        if self.graphics.frame_capture.has_pending() {
+            let completed_frame_value = unwrap_here!(
+                "Get completed frame value",
+                self.graphics.frames_in_flight.completed_frame_value()
+            );
+            self.graphics
+                .frame_capture
+                .save_completed(completed_frame_value);
+        }
+
+        if let Some(recorder) = &mut self.graphics.recorder {
+            let completed_frame_value = unwrap_here!(
+                "Get completed frame value",
+                self.graphics.frames_in_flight.completed_frame_value()
+            );
+            unwrap_here!(
+                "Write recorded frames",
+                recorder.write_completed(completed_frame_value)
+            );
+        }
+
+        let gpu_timings = unwrap_here!(
+            "Read GPU timings",
+            self.graphics.gpu_timer.begin_frame(&frame)
+        );
+        for (name, ms) in gpu_timings {
+            self.graphics.metrics.record_gpu_metric(name, ms);
+        }
+        let frame_scope = self.graphics.gpu_timer.begin(&frame, "frame");
+
         if unwrap_here!(
             "Demo draw",
             self.demo.draw(window, &mut self.graphics, &frame)
@@ -389,20 +730,199 @@ impl<D: Demo + Sized> App for DemoApp<D> {
             return Ok(AppState::Exit);
         }
 
+        self.graphics.gpu_timer.end(&frame, frame_scope);
+
+        self.graphics.frame_capture.record(
+            &self.graphics.vulkan,
+            &frame,
+            &self.graphics.swapchain,
+        );
+        if let Some(recorder) = &mut self.graphics.recorder {
+            unwrap_here!(
+                "Record frame for recording",
+                recorder.record(
+                    &self.graphics.vulkan,
+                    &frame,
+                    &self.graphics.swapchain,
+                )
+            );
+        }
+
+        let presented_image = frame.swapchain_image();
         let result = self
             .graphics
             .frames_in_flight
             .present_frame(&self.graphics.swapchain, frame)?;
+        self.graphics.last_presented_image = Some(presented_image);
         if result == PresentImageStatus::SwapchainNeedsRebuild {
             self.graphics.swapchain_needs_rebuild = true;
         }
         self.graphics.metrics.draw_tick(before_draw);
 
+        if self
+            .graphics
+            .recorder
+            .as_ref()
+            .is_some_and(Recorder::is_finished)
+        {
+            self.graphics.finish_recording()?;
+            return Ok(AppState::Exit);
+        }
+
         Ok(AppState::Continue)
     }
 }
 
+/// Returns the size of the window's framebuffer, or the offscreen size when
+/// rendering offscreen.
+fn framebuffer_size(
+    window: Option<&Window>,
+    offscreen_size: Option<(u32, u32)>,
+) -> (u32, u32) {
+    match (offscreen_size, window) {
+        (Some(size), _) => size,
+        (None, Some(window)) => {
+            let PhysicalSize { width, height } = window.inner_size();
+            (width, height)
+        }
+        (None, None) => (0, 0),
+    }
+}
+
+/// Creates an offscreen swapchain when rendering offscreen, otherwise creates a
+/// swapchain for the window surface.
+fn create_swapchain(
+    vulkan: &Arc<VulkanContext>,
+    window: Option<&Window>,
+    offscreen_size: Option<(u32, u32)>,
+    previous_swapchain: Option<vk::SwapchainKHR>,
+    settings: &SwapchainSettings,
+) -> Result<Swapchain> {
+    let size = framebuffer_size(window, offscreen_size);
+    if offscreen_size.is_some() {
+        Swapchain::offscreen(vulkan.clone(), size, settings)
+    } else {
+        Swapchain::new(vulkan.clone(), size, previous_swapchain, settings)
+    }
+}
+
+/// The command line arguments for a demo.
+///
+/// Every demo accepts [RecordingArgs] and `--offscreen` alongside its own
+/// arguments.
+#[derive(Debug, Parser)]
+#[command(about = None, long_about = None)]
+pub struct DemoArgs<A: clap::Args> {
+    #[command(flatten)]
+    pub demo: A,
+
+    /// Render to offscreen images without creating a window or an event
+    /// loop. Combine with --record to save frames.
+    ///
+    /// The size comes from --record-size when given, otherwise the demo's
+    /// initial window size. The Vulkan context is headless, so no display
+    /// connection is required, and demos receive None in place of the
+    /// window. The demo runs until it exits or the recording finishes.
+    #[arg(long)]
+    pub offscreen: bool,
+
+    #[command(flatten)]
+    pub recording: RecordingArgs,
+}
+
+impl<D: Demo + Sized> App for DemoApp<D> {
+    type Args = DemoArgs<D::Args>;
+
+    fn new(window: &mut Window, args: &Self::Args) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::create(Some(window), args)
+    }
+
+    fn handle_window_event(
+        &mut self,
+        window: &mut Window,
+        event: WindowEvent,
+    ) -> Result<AppState> {
+        if let WindowEvent::KeyboardInput {
+            event: ref key_event,
+            ..
+        } = event
+        {
+            if key_event.state.is_pressed()
+                && !key_event.repeat
+                && key_event.physical_key == PhysicalKey::Code(KeyCode::F12)
+            {
+                let timestamp = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+                if let Err(error) = self
+                    .graphics
+                    .capture_next_frame(format!("screenshot-{timestamp}.png"))
+                {
+                    log::warn!("Unable to capture screenshot: {error:?}");
+                }
+            }
+        }
+        self.demo
+            .handle_window_event(window, &mut self.graphics, event)
+    }
+
+    fn handle_device_event(
+        &mut self,
+        window: &mut Window,
+        event: DeviceEvent,
+    ) -> Result<AppState> {
+        self.demo
+            .handle_device_event(window, &mut self.graphics, event)
+    }
+
+    fn update(&mut self, window: &mut Window) -> Result<AppState> {
+        self.tick(Some(window))
+    }
+}
+
+/// Runs the demo offscreen, without an event loop, until it exits.
+fn run_offscreen<D: Demo>(args: &DemoArgs<D::Args>) -> Result<()> {
+    let mut app =
+        DemoApp::<D>::create(None, args).context("Unable to initialize app")?;
+    while app
+        .tick(None)
+        .context("Unexpected error in App::update()!")?
+        == AppState::Continue
+    {}
+    Ok(())
+}
+
+/// Runs the demo with parsed args, offscreen or in a window.
+fn run_demo<D: Demo + 'static>(args: DemoArgs<D::Args>) -> Result<()> {
+    if args.offscreen {
+        run_offscreen::<D>(&args)
+    } else {
+        app::run_app::<DemoApp<D>>(args)
+    }
+}
+
 /// The main entrypoint for a demo.
 pub fn demo_main<D: Demo + 'static>() -> Result<()> {
-    app_main::<DemoApp<D>>()
+    app::report_exit_error(
+        app::setup_and_parse_args::<DemoArgs<D::Args>>()
+            .and_then(run_demo::<D>),
+    )
+}
+
+/// Runs a demo with the given command line arguments instead of the
+/// process's arguments, e.g. to run a demo with `--offscreen` from a test.
+///
+/// The first argument is the program name. Args files are not expanded.
+pub fn demo_main_with_args<D, I, T>(args: I) -> Result<()>
+where
+    D: Demo + 'static,
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    app::setup_logging();
+    app::report_exit_error(run_demo::<D>(DemoArgs::parse_from(args)))
 }
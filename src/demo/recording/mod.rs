@@ -0,0 +1,386 @@
+mod y4m;
+
+use {
+    self::y4m::Y4mWriter,
+    super::frame_capture::RENDERED_SWAPCHAIN_IMAGE,
+    crate::{
+        graphics::{
+            streaming_renderer::Texture,
+            vulkan::{Frame, Swapchain, VulkanContext},
+            Barriers, ImageReadback, ImageUsage,
+        },
+        unwrap_here,
+    },
+    anyhow::{bail, Context, Result},
+    ash::vk,
+    std::{
+        collections::VecDeque,
+        fs::File,
+        io::BufWriter,
+        path::{Path, PathBuf},
+    },
+};
+
+/// The file format used when recording frames.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, clap::ValueEnum)]
+pub enum RecordingFormat {
+    /// A numbered sequence of PNG files in the output directory.
+    Png,
+
+    /// A single uncompressed YUV4MPEG2 video file.
+    Y4m,
+}
+
+/// Command line arguments for recording every frame to disk.
+///
+/// These are accepted by every demo in addition to the demo's own arguments.
+#[derive(Debug, Clone, clap::Args)]
+pub struct RecordingArgs {
+    /// Record every frame to this path. This is a directory for PNG sequences
+    /// or a file for Y4M streams.
+    ///
+    /// While recording, the demo runs at a fixed timestep and is not limited
+    /// by the wall clock.
+    #[arg(long = "record")]
+    pub record_path: Option<PathBuf>,
+
+    /// The format used for recorded frames.
+    #[arg(long, value_enum, default_value_t = RecordingFormat::Png)]
+    pub record_format: RecordingFormat,
+
+    /// The simulated frames per second while recording.
+    #[arg(long, default_value_t = 60)]
+    pub record_fps: u32,
+
+    /// Exit after recording this many frames. Recording continues until the
+    /// window is closed when omitted.
+    #[arg(long)]
+    pub record_frames: Option<u64>,
+
+    /// The recorded resolution as WIDTHxHEIGHT. Frames are scaled from the
+    /// swapchain. Defaults to the swapchain's size on the first frame.
+    #[arg(long, value_parser = parse_resolution)]
+    pub record_size: Option<(u32, u32)>,
+}
+
+fn parse_resolution(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, got {value}"))?;
+    let parse = |dimension: &str| {
+        dimension
+            .trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|&size| size > 0)
+            .ok_or_else(|| format!("invalid dimension in {value}"))
+    };
+    Ok((parse(width)?, parse(height)?))
+}
+
+/// Where recorded frames are written.
+enum RecordingOutput {
+    Png(PathBuf),
+    Y4m(Y4mWriter<BufWriter<File>>),
+}
+
+/// The scaled copy of a frame's swapchain image.
+struct RecordingTarget {
+    texture: Texture,
+    filter: vk::Filter,
+}
+
+/// Copies every frame's swapchain image to disk in order.
+///
+/// Each swapchain image is blitted into a fixed-size target image, copied
+/// into a readback buffer, and written once the frame completes.
+pub(super) struct Recorder {
+    output: Option<RecordingOutput>,
+    output_path: PathBuf,
+    format: RecordingFormat,
+    fps: u32,
+    frame_limit: Option<u64>,
+    extent: Option<vk::Extent2D>,
+    targets: Vec<Option<RecordingTarget>>,
+    free_readbacks: Vec<ImageReadback>,
+    pending: VecDeque<(u64, ImageReadback)>,
+    frames_recorded: u64,
+    frames_written: u64,
+}
+
+impl Recorder {
+    /// Creates a new recorder, or returns None when recording wasn't
+    /// requested.
+    pub fn new(
+        args: &RecordingArgs,
+        frame_count: usize,
+    ) -> Result<Option<Self>> {
+        let Some(output_path) = args.record_path.clone() else {
+            return Ok(None);
+        };
+        if args.record_fps == 0 {
+            bail!("--record-fps must be greater than 0");
+        }
+        if args.record_format == RecordingFormat::Png {
+            unwrap_here!(
+                format!("Create recording directory {}", output_path.display()),
+                std::fs::create_dir_all(&output_path)
+            );
+        }
+        Ok(Some(Self {
+            output: None,
+            output_path,
+            format: args.record_format,
+            fps: args.record_fps,
+            frame_limit: args.record_frames,
+            extent: args
+                .record_size
+                .map(|(width, height)| vk::Extent2D { width, height }),
+            targets: (0..frame_count).map(|_| None).collect(),
+            free_readbacks: vec![],
+            pending: VecDeque::new(),
+            frames_recorded: 0,
+            frames_written: 0,
+        }))
+    }
+
+    /// The simulated time between frames in seconds.
+    pub fn timestep(&self) -> f32 {
+        1.0 / self.fps as f32
+    }
+
+    /// Returns true once every requested frame has been recorded.
+    pub fn is_finished(&self) -> bool {
+        self.frame_limit
+            .is_some_and(|limit| self.frames_recorded >= limit)
+    }
+
+    /// Records commands to copy the frame's swapchain image for recording.
+    ///
+    /// The swapchain image must be ready for presentation and the swapchain
+    /// must have been created with TRANSFER_SRC usage.
+    pub fn record(
+        &mut self,
+        ctx: &VulkanContext,
+        frame: &Frame,
+        swapchain: &Swapchain,
+    ) -> Result<()> {
+        if self.is_finished() {
+            return Ok(());
+        }
+        if !swapchain
+            .image_usage()
+            .contains(vk::ImageUsageFlags::TRANSFER_SRC)
+        {
+            bail!(
+                "Recording requires swapchain images with TRANSFER_SRC usage"
+            );
+        }
+
+        let extent = *self.extent.get_or_insert(swapchain.extent());
+        let target = match &mut self.targets[frame.frame_index()] {
+            Some(target) => target,
+            empty => empty.insert(unwrap_here!(
+                "Create recording target",
+                create_target(ctx, swapchain.format(), extent)
+            )),
+        };
+
+        // scale the swapchain image into the recording target
+        Barriers::new()
+            .image(
+                frame.swapchain_image(),
+                vk::ImageAspectFlags::COLOR,
+                RENDERED_SWAPCHAIN_IMAGE,
+                ImageUsage::TransferSrc,
+            )
+            .texture_discarding(&target.texture, ImageUsage::TransferDst)
+            .record(ctx, frame.command_buffer())?;
+        let swapchain_extent = swapchain.extent();
+        unsafe {
+            ctx.cmd_blit_image(
+                frame.command_buffer(),
+                frame.swapchain_image(),
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                target.texture.image().raw,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[vk::ImageBlit {
+                    src_subresource: color_layer(),
+                    src_offsets: [
+                        vk::Offset3D::default(),
+                        far_corner(swapchain_extent),
+                    ],
+                    dst_subresource: color_layer(),
+                    dst_offsets: [vk::Offset3D::default(), far_corner(extent)],
+                }],
+                target.filter,
+            );
+        }
+        Barriers::new()
+            .image(
+                frame.swapchain_image(),
+                vk::ImageAspectFlags::COLOR,
+                ImageUsage::TransferSrc,
+                ImageUsage::Present,
+            )
+            .record(ctx, frame.command_buffer())?;
+
+        let readback = match self.free_readbacks.pop() {
+            Some(readback) => readback,
+            None => unwrap_here!(
+                "Create recording readback buffer",
+                ImageReadback::for_texture(ctx, &target.texture)
+            ),
+        };
+        readback.record_texture_copy(
+            ctx,
+            frame.command_buffer(),
+            &target.texture,
+        )?;
+        self.pending.push_back((frame.frame_value(), readback));
+        self.frames_recorded += 1;
+        Ok(())
+    }
+
+    /// Writes every recorded frame whose commands have completed, in order.
+    pub fn write_completed(
+        &mut self,
+        completed_frame_value: u64,
+    ) -> Result<()> {
+        while self
+            .pending
+            .front()
+            .is_some_and(|(value, _)| *value <= completed_frame_value)
+        {
+            let (_, readback) = self.pending.pop_front().unwrap();
+            // SAFETY: the frame which recorded the copy has completed
+            let image = unwrap_here!("Read recorded frame", unsafe {
+                readback.to_rgba_image()
+            });
+            self.free_readbacks.push(readback);
+            unwrap_here!(
+                format!("Write recorded frame {}", self.frames_written),
+                self.write_frame(&image)
+            );
+            self.frames_written += 1;
+        }
+        if let Some(RecordingOutput::Y4m(writer)) = &mut self.output {
+            if self.pending.is_empty() {
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_frame(&mut self, image: &image::RgbaImage) -> Result<()> {
+        let output = match &mut self.output {
+            Some(output) => output,
+            empty => empty.insert(match self.format {
+                RecordingFormat::Png => {
+                    RecordingOutput::Png(self.output_path.clone())
+                }
+                RecordingFormat::Y4m => RecordingOutput::Y4m(Y4mWriter::new(
+                    BufWriter::new(create_file(&self.output_path)?),
+                    image.width(),
+                    image.height(),
+                    self.fps,
+                )?),
+            }),
+        };
+        match output {
+            RecordingOutput::Png(directory) => {
+                let path = directory
+                    .join(format!("frame-{:06}.png", self.frames_written));
+                image
+                    .save_with_format(&path, image::ImageFormat::Png)
+                    .with_context(|| {
+                        format!("Unable to save {}", path.display())
+                    })
+            }
+            RecordingOutput::Y4m(writer) => writer.write_frame(image.as_raw()),
+        }
+    }
+}
+
+fn create_file(path: &Path) -> Result<File> {
+    File::create(path)
+        .with_context(|| format!("Unable to create {}", path.display()))
+}
+
+/// Creates the image which swapchain images are scaled into.
+///
+/// The target is 8-bit RGBA so it can be written without conversion. sRGB
+/// swapchains use an sRGB target so the blit preserves the encoded values.
+fn create_target(
+    ctx: &VulkanContext,
+    swapchain_format: vk::Format,
+    extent: vk::Extent2D,
+) -> Result<RecordingTarget> {
+    let features = unsafe {
+        ctx.instance
+            .get_physical_device_format_properties(
+                ctx.physical_device,
+                swapchain_format,
+            )
+            .optimal_tiling_features
+    };
+    if !features.contains(vk::FormatFeatureFlags::BLIT_SRC) {
+        bail!(
+            "Swapchain format {:?} does not support blitting",
+            swapchain_format
+        );
+    }
+    let filter = if features
+        .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR)
+    {
+        vk::Filter::LINEAR
+    } else {
+        vk::Filter::NEAREST
+    };
+    let format = match swapchain_format {
+        vk::Format::B8G8R8A8_SRGB | vk::Format::R8G8B8A8_SRGB => {
+            vk::Format::R8G8B8A8_SRGB
+        }
+        _ => vk::Format::R8G8B8A8_UNORM,
+    };
+    let texture = Texture::builder()
+        .ctx(ctx)
+        .dimensions((extent.width, extent.height))
+        .format(format)
+        .image_usage_flags(
+            vk::ImageUsageFlags::TRANSFER_DST
+                | vk::ImageUsageFlags::TRANSFER_SRC,
+        )
+        .memory_property_flags(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+        .build()?;
+    Ok(RecordingTarget { texture, filter })
+}
+
+fn color_layer() -> vk::ImageSubresourceLayers {
+    vk::ImageSubresourceLayers {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        mip_level: 0,
+        base_array_layer: 0,
+        layer_count: 1,
+    }
+}
+
+fn far_corner(extent: vk::Extent2D) -> vk::Offset3D {
+    vk::Offset3D {
+        x: extent.width as i32,
+        y: extent.height as i32,
+        z: 1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn resolutions_should_parse_width_and_height() {
+        assert_eq!(parse_resolution("1920x1080"), Ok((1920, 1080)));
+        assert!(parse_resolution("1920").is_err());
+        assert!(parse_resolution("0x1080").is_err());
+    }
+}
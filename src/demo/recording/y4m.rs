@@ -0,0 +1,102 @@
+use {
+    anyhow::{bail, Result},
+    std::io::Write,
+};
+
+/// Writes RGBA frames as an uncompressed YUV4MPEG2 stream.
+///
+/// Frames are stored as 4:4:4 BT.601 limited range YCbCr, which most video
+/// tools (e.g. ffmpeg) accept directly.
+pub(super) struct Y4mWriter<W: Write> {
+    writer: W,
+    width: u32,
+    height: u32,
+    planes: [Vec<u8>; 3],
+}
+
+impl<W: Write> Y4mWriter<W> {
+    /// Creates a new writer and writes the stream header.
+    pub fn new(
+        mut writer: W,
+        width: u32,
+        height: u32,
+        fps: u32,
+    ) -> Result<Self> {
+        writeln!(writer, "YUV4MPEG2 W{width} H{height} F{fps}:1 Ip A1:1 C444")?;
+        let pixel_count = (width * height) as usize;
+        Ok(Self {
+            writer,
+            width,
+            height,
+            planes: [
+                vec![0; pixel_count],
+                vec![0; pixel_count],
+                vec![0; pixel_count],
+            ],
+        })
+    }
+
+    /// Writes a single frame of tightly packed 8-bit RGBA pixels.
+    pub fn write_frame(&mut self, rgba: &[u8]) -> Result<()> {
+        let expected = (self.width * self.height * 4) as usize;
+        if rgba.len() != expected {
+            bail!(
+                "Expected a {}x{} frame ({} bytes) but got {} bytes",
+                self.width,
+                self.height,
+                expected,
+                rgba.len()
+            );
+        }
+
+        let [y_plane, cb_plane, cr_plane] = &mut self.planes;
+        for (index, pixel) in rgba.chunks_exact(4).enumerate() {
+            let (y, cb, cr) = rgb_to_ycbcr(pixel[0], pixel[1], pixel[2]);
+            y_plane[index] = y;
+            cb_plane[index] = cb;
+            cr_plane[index] = cr;
+        }
+
+        self.writer.write_all(b"FRAME\n")?;
+        for plane in &self.planes {
+            self.writer.write_all(plane)?;
+        }
+        Ok(())
+    }
+
+    /// Flushes any buffered output.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Converts gamma encoded RGB to BT.601 limited range YCbCr.
+fn rgb_to_ycbcr(r: u8, g: u8, b: u8) -> (u8, u8, u8) {
+    let (r, g, b) = (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+    let y = 16.0 + 65.481 * r + 128.553 * g + 24.966 * b;
+    let cb = 128.0 - 37.797 * r - 74.203 * g + 112.0 * b;
+    let cr = 128.0 + 112.0 * r - 93.786 * g - 18.214 * b;
+    (y.round() as u8, cb.round() as u8, cr.round() as u8)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn frames_should_be_written_as_planar_ycbcr() {
+        let mut output = vec![];
+        let mut writer = Y4mWriter::new(&mut output, 2, 1, 30).unwrap();
+        writer
+            .write_frame(&[255, 255, 255, 255, 0, 0, 0, 255])
+            .unwrap();
+
+        let header = b"YUV4MPEG2 W2 H1 F30:1 Ip A1:1 C444\n";
+        assert_eq!(&output[..header.len()], header);
+        assert_eq!(
+            &output[header.len()..],
+            b"FRAME\n\xeb\x10\x80\x80\x80\x80".as_slice()
+        );
+    }
+}
@@ -0,0 +1,538 @@
+use {
+    crate::graphics::{streaming_renderer::Texture, vulkan::VulkanContext},
+    anyhow::{bail, Result},
+    ash::vk,
+};
+
+/// The pipeline stages, memory access, and image layout used when accessing
+/// an image.
+///
+/// This is the state tracked by a [Texture] between barriers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ImageAccess {
+    pub stage_mask: vk::PipelineStageFlags2,
+    pub access_mask: vk::AccessFlags2,
+    pub layout: vk::ImageLayout,
+}
+
+impl ImageAccess {
+    /// The state of a freshly created image. Any existing contents are
+    /// discarded when transitioning from this state.
+    pub const UNDEFINED: Self = Self {
+        stage_mask: vk::PipelineStageFlags2::NONE,
+        access_mask: vk::AccessFlags2::NONE,
+        layout: vk::ImageLayout::UNDEFINED,
+    };
+
+    /// Returns true when the access writes to the image.
+    pub fn is_write(&self) -> bool {
+        self.access_mask.intersects(WRITE_ACCESS)
+    }
+}
+
+/// Describes the next use of an image.
+///
+/// Each usage maps to the stages, access, and layout needed for that use. See
+/// [ImageUsage::access].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ImageUsage {
+    /// The image's contents are not needed and can be discarded.
+    Undefined,
+
+    /// The image is sampled by a vertex, fragment, or compute shader.
+    Sampled,
+
+    /// The image is read as a storage image by a fragment or compute shader.
+    StorageRead,
+
+    /// The image is written as a storage image by a fragment or compute
+    /// shader.
+    StorageWrite,
+
+    /// The image is read and written as a storage image by a fragment or
+    /// compute shader.
+    StorageReadWrite,
+
+    /// The image is used as a color attachment while rendering.
+    ColorAttachment,
+
+    /// The image is used as a depth (and possibly stencil) attachment while
+    /// rendering.
+    DepthAttachment,
+
+    /// The image is the source of a copy or blit.
+    TransferSrc,
+
+    /// The image is the destination of a copy, blit, or clear.
+    TransferDst,
+
+    /// The image is about to be presented to the swapchain.
+    Present,
+
+    /// The image is in the general layout and can be used for anything.
+    General,
+}
+
+impl ImageUsage {
+    /// Returns the stages, access, and layout for this usage.
+    pub fn access(self) -> ImageAccess {
+        let (stage_mask, access_mask, layout) = match self {
+            Self::Undefined => (
+                vk::PipelineStageFlags2::NONE,
+                vk::AccessFlags2::NONE,
+                vk::ImageLayout::UNDEFINED,
+            ),
+            Self::Sampled => (
+                vk::PipelineStageFlags2::VERTEX_SHADER
+                    | vk::PipelineStageFlags2::FRAGMENT_SHADER
+                    | vk::PipelineStageFlags2::COMPUTE_SHADER,
+                vk::AccessFlags2::SHADER_SAMPLED_READ,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ),
+            Self::StorageRead => (
+                vk::PipelineStageFlags2::FRAGMENT_SHADER
+                    | vk::PipelineStageFlags2::COMPUTE_SHADER,
+                vk::AccessFlags2::SHADER_STORAGE_READ,
+                vk::ImageLayout::GENERAL,
+            ),
+            Self::StorageWrite => (
+                vk::PipelineStageFlags2::FRAGMENT_SHADER
+                    | vk::PipelineStageFlags2::COMPUTE_SHADER,
+                vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                vk::ImageLayout::GENERAL,
+            ),
+            Self::StorageReadWrite => (
+                vk::PipelineStageFlags2::FRAGMENT_SHADER
+                    | vk::PipelineStageFlags2::COMPUTE_SHADER,
+                vk::AccessFlags2::SHADER_STORAGE_READ
+                    | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                vk::ImageLayout::GENERAL,
+            ),
+            Self::ColorAttachment => (
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags2::COLOR_ATTACHMENT_READ
+                    | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ),
+            Self::DepthAttachment => (
+                vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+                vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ),
+            Self::TransferSrc => (
+                vk::PipelineStageFlags2::ALL_TRANSFER,
+                vk::AccessFlags2::TRANSFER_READ,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ),
+            Self::TransferDst => (
+                vk::PipelineStageFlags2::ALL_TRANSFER,
+                vk::AccessFlags2::TRANSFER_WRITE,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            ),
+            Self::Present => (
+                vk::PipelineStageFlags2::NONE,
+                vk::AccessFlags2::NONE,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+            ),
+            Self::General => (
+                vk::PipelineStageFlags2::ALL_COMMANDS,
+                vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+                vk::ImageLayout::GENERAL,
+            ),
+        };
+        ImageAccess {
+            stage_mask,
+            access_mask,
+            layout,
+        }
+    }
+}
+
+impl ImageUsage {
+    /// Returns the image usage flags an image needs to support this usage.
+    ///
+    /// [Self::General] is typically used for storage images which are also
+    /// sampled, so it requires both. [Self::Undefined] and [Self::Present]
+    /// don't require any usage flags.
+    pub fn image_usage_flags(self) -> vk::ImageUsageFlags {
+        match self {
+            Self::Sampled => vk::ImageUsageFlags::SAMPLED,
+            Self::StorageRead | Self::StorageWrite | Self::StorageReadWrite => {
+                vk::ImageUsageFlags::STORAGE
+            }
+            Self::ColorAttachment => vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            Self::DepthAttachment => {
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+            }
+            Self::TransferSrc => vk::ImageUsageFlags::TRANSFER_SRC,
+            Self::TransferDst => vk::ImageUsageFlags::TRANSFER_DST,
+            Self::General => {
+                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED
+            }
+            Self::Undefined | Self::Present => vk::ImageUsageFlags::empty(),
+        }
+    }
+}
+
+impl From<ImageUsage> for ImageAccess {
+    fn from(usage: ImageUsage) -> Self {
+        usage.access()
+    }
+}
+
+/// Describes how a range of a buffer is accessed on either side of a barrier.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BufferUsage {
+    /// Nothing has accessed the buffer yet.
+    None,
+
+    /// The buffer is written by the host.
+    HostWrite,
+
+    /// The buffer is read by the host.
+    HostRead,
+
+    /// The buffer is read as vertex data, either with vertex input or through
+    /// a device address in the vertex shader.
+    VertexRead,
+
+    /// The buffer is read as index data.
+    IndexRead,
+
+    /// The buffer holds indirect draw or dispatch commands.
+    IndirectRead,
+
+    /// The buffer is read as a uniform buffer.
+    UniformRead,
+
+    /// The buffer is read as a storage buffer by any shader.
+    StorageRead,
+
+    /// The buffer is written as a storage buffer by any shader.
+    StorageWrite,
+
+    /// The buffer is read and written as a storage buffer by any shader.
+    StorageReadWrite,
+
+    /// The buffer is the source of a copy.
+    TransferSrc,
+
+    /// The buffer is the destination of a copy or fill.
+    TransferDst,
+}
+
+impl BufferUsage {
+    /// Returns true when the usage writes to the buffer.
+    pub fn is_write(self) -> bool {
+        self.access().1.intersects(WRITE_ACCESS)
+    }
+
+    /// Returns the stages and access for this usage.
+    pub fn access(self) -> (vk::PipelineStageFlags2, vk::AccessFlags2) {
+        let all_shaders = vk::PipelineStageFlags2::VERTEX_SHADER
+            | vk::PipelineStageFlags2::FRAGMENT_SHADER
+            | vk::PipelineStageFlags2::COMPUTE_SHADER;
+        match self {
+            Self::None => {
+                (vk::PipelineStageFlags2::NONE, vk::AccessFlags2::NONE)
+            }
+            Self::HostWrite => {
+                (vk::PipelineStageFlags2::HOST, vk::AccessFlags2::HOST_WRITE)
+            }
+            Self::HostRead => {
+                (vk::PipelineStageFlags2::HOST, vk::AccessFlags2::HOST_READ)
+            }
+            Self::VertexRead => (
+                vk::PipelineStageFlags2::VERTEX_INPUT
+                    | vk::PipelineStageFlags2::VERTEX_SHADER,
+                vk::AccessFlags2::VERTEX_ATTRIBUTE_READ
+                    | vk::AccessFlags2::SHADER_STORAGE_READ,
+            ),
+            Self::IndexRead => (
+                vk::PipelineStageFlags2::INDEX_INPUT,
+                vk::AccessFlags2::INDEX_READ,
+            ),
+            Self::IndirectRead => (
+                vk::PipelineStageFlags2::DRAW_INDIRECT,
+                vk::AccessFlags2::INDIRECT_COMMAND_READ,
+            ),
+            Self::UniformRead => (all_shaders, vk::AccessFlags2::UNIFORM_READ),
+            Self::StorageRead => {
+                (all_shaders, vk::AccessFlags2::SHADER_STORAGE_READ)
+            }
+            Self::StorageWrite => {
+                (all_shaders, vk::AccessFlags2::SHADER_STORAGE_WRITE)
+            }
+            Self::StorageReadWrite => (
+                all_shaders,
+                vk::AccessFlags2::SHADER_STORAGE_READ
+                    | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            ),
+            Self::TransferSrc => (
+                vk::PipelineStageFlags2::ALL_TRANSFER,
+                vk::AccessFlags2::TRANSFER_READ,
+            ),
+            Self::TransferDst => (
+                vk::PipelineStageFlags2::ALL_TRANSFER,
+                vk::AccessFlags2::TRANSFER_WRITE,
+            ),
+        }
+    }
+}
+
+/// Every access flag which writes to memory.
+const WRITE_ACCESS: vk::AccessFlags2 = vk::AccessFlags2::from_raw(
+    vk::AccessFlags2::SHADER_WRITE.as_raw()
+        | vk::AccessFlags2::SHADER_STORAGE_WRITE.as_raw()
+        | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE.as_raw()
+        | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE.as_raw()
+        | vk::AccessFlags2::TRANSFER_WRITE.as_raw()
+        | vk::AccessFlags2::HOST_WRITE.as_raw()
+        | vk::AccessFlags2::MEMORY_WRITE.as_raw(),
+);
+
+/// Returns the image aspects present in an image with the given format.
+pub fn aspect_mask_for_format(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D16_UNORM
+        | vk::Format::X8_D24_UNORM_PACK32
+        | vk::Format::D32_SFLOAT => vk::ImageAspectFlags::DEPTH,
+        vk::Format::S8_UINT => vk::ImageAspectFlags::STENCIL,
+        vk::Format::D16_UNORM_S8_UINT
+        | vk::Format::D24_UNORM_S8_UINT
+        | vk::Format::D32_SFLOAT_S8_UINT => {
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        }
+        _ => vk::ImageAspectFlags::COLOR,
+    }
+}
+
+/// A batch of image and buffer barriers which are recorded with a single call
+/// to `vkCmdPipelineBarrier2`.
+///
+/// Requires the `synchronization2` device feature.
+///
+/// # Example
+///
+/// ```ignore
+/// Barriers::new()
+///     .texture(&draw_target, ImageUsage::TransferSrc)
+///     .image(
+///         frame.swapchain_image(),
+///         vk::ImageAspectFlags::COLOR,
+///         ImageUsage::Undefined,
+///         ImageUsage::TransferDst,
+///     )
+///     .record(&ctx, frame.command_buffer())?;
+/// ```
+#[derive(Debug, Default)]
+pub struct Barriers {
+    image_barriers: Vec<vk::ImageMemoryBarrier2<'static>>,
+    buffer_barriers: Vec<vk::BufferMemoryBarrier2<'static>>,
+}
+
+impl Barriers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Transitions the texture from its tracked state to `usage`.
+    ///
+    /// The texture's tracked state is updated immediately, so barriers must be
+    /// recorded in the same order they are added.
+    ///
+    /// No barrier is added when the texture is already in a read-only state
+    /// with the same layout.
+    pub fn texture(self, texture: &Texture, usage: ImageUsage) -> Self {
+        self.texture_access(texture, usage.access())
+    }
+
+    /// Transitions the texture to `usage` and discards its current contents.
+    ///
+    /// This still waits for prior accesses to finish, but lets the driver skip
+    /// preserving the image's data. Useful for render targets which are
+    /// cleared every frame.
+    pub fn texture_discarding(
+        mut self,
+        texture: &Texture,
+        usage: impl Into<ImageAccess>,
+    ) -> Self {
+        let access = usage.into();
+        let previous = texture.set_access(access);
+        self.image_barriers.push(image_barrier(
+            texture.image().raw,
+            texture.subresource_range(),
+            ImageAccess {
+                layout: vk::ImageLayout::UNDEFINED,
+                ..previous
+            },
+            access,
+        ));
+        self
+    }
+
+    /// Transitions the texture from its tracked state to an arbitrary access.
+    pub fn texture_access(
+        mut self,
+        texture: &Texture,
+        access: ImageAccess,
+    ) -> Self {
+        let previous = texture.set_access(access);
+        if previous == access && !access.is_write() {
+            return self;
+        }
+        self.image_barriers.push(image_barrier(
+            texture.image().raw,
+            texture.subresource_range(),
+            previous,
+            access,
+        ));
+        self
+    }
+
+    /// Transitions an untracked image, like a swapchain image, between two
+    /// usages.
+    ///
+    /// The barrier covers every mip level and array layer of the image.
+    pub fn image(
+        mut self,
+        image: vk::Image,
+        aspect_mask: vk::ImageAspectFlags,
+        from: impl Into<ImageAccess>,
+        to: impl Into<ImageAccess>,
+    ) -> Self {
+        self.image_barriers.push(image_barrier(
+            image,
+            vk::ImageSubresourceRange {
+                aspect_mask,
+                base_mip_level: 0,
+                level_count: vk::REMAINING_MIP_LEVELS,
+                base_array_layer: 0,
+                layer_count: vk::REMAINING_ARRAY_LAYERS,
+            },
+            from.into(),
+            to.into(),
+        ));
+        self
+    }
+
+    /// Adds a barrier for `size` bytes of the buffer starting at `offset`.
+    ///
+    /// Use `vk::WHOLE_SIZE` to cover the rest of the buffer.
+    pub fn buffer(
+        mut self,
+        buffer: vk::Buffer,
+        offset: u64,
+        size: u64,
+        from: BufferUsage,
+        to: BufferUsage,
+    ) -> Self {
+        let (src_stage_mask, src_access_mask) = from.access();
+        let (dst_stage_mask, dst_access_mask) = to.access();
+        self.buffer_barriers.push(vk::BufferMemoryBarrier2 {
+            src_stage_mask,
+            src_access_mask,
+            dst_stage_mask,
+            dst_access_mask,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            buffer,
+            offset,
+            size,
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Returns true when there are no barriers to record.
+    pub fn is_empty(&self) -> bool {
+        self.image_barriers.is_empty() && self.buffer_barriers.is_empty()
+    }
+
+    /// Records all of the barriers into the command buffer.
+    ///
+    /// Nothing is recorded when the batch is empty. Fails if the device was
+    /// created without the `synchronization2` feature.
+    pub fn record(
+        self,
+        ctx: &VulkanContext,
+        command_buffer: vk::CommandBuffer,
+    ) -> Result<()> {
+        if !ctx.synchronization2_enabled() {
+            bail!("Barriers require the synchronization2 device feature");
+        }
+        if self.is_empty() {
+            return Ok(());
+        }
+        unsafe {
+            ctx.cmd_pipeline_barrier2(
+                command_buffer,
+                &vk::DependencyInfo::default()
+                    .image_memory_barriers(&self.image_barriers)
+                    .buffer_memory_barriers(&self.buffer_barriers),
+            );
+        }
+        Ok(())
+    }
+}
+
+fn image_barrier(
+    image: vk::Image,
+    subresource_range: vk::ImageSubresourceRange,
+    from: ImageAccess,
+    to: ImageAccess,
+) -> vk::ImageMemoryBarrier2<'static> {
+    vk::ImageMemoryBarrier2 {
+        src_stage_mask: from.stage_mask,
+        src_access_mask: from.access_mask,
+        dst_stage_mask: to.stage_mask,
+        dst_access_mask: to.access_mask,
+        old_layout: from.layout,
+        new_layout: to.layout,
+        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        image,
+        subresource_range,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn depth_formats_should_use_the_depth_aspect() {
+        assert_eq!(
+            aspect_mask_for_format(vk::Format::D32_SFLOAT),
+            vk::ImageAspectFlags::DEPTH
+        );
+        assert_eq!(
+            aspect_mask_for_format(vk::Format::D24_UNORM_S8_UINT),
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        );
+        assert_eq!(
+            aspect_mask_for_format(vk::Format::R16G16B16A16_SFLOAT),
+            vk::ImageAspectFlags::COLOR
+        );
+    }
+
+    #[test]
+    pub fn read_only_usages_should_not_be_writes() {
+        assert!(!ImageUsage::Sampled.access().is_write());
+        assert!(!ImageUsage::TransferSrc.access().is_write());
+        assert!(ImageUsage::StorageWrite.access().is_write());
+        assert!(ImageUsage::ColorAttachment.access().is_write());
+    }
+
+    #[test]
+    pub fn general_usage_should_require_storage_and_sampled_images() {
+        assert_eq!(
+            ImageUsage::General.image_usage_flags(),
+            vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED
+        );
+        assert!(ImageUsage::Present.image_usage_flags().is_empty());
+    }
+}
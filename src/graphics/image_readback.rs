@@ -0,0 +1,260 @@
+use {
+    crate::{
+        graphics::{
+            streaming_renderer::Texture,
+            vulkan::{CPUBuffer, VulkanContext},
+            Barriers, BufferUsage, ImageAccess, ImageUsage,
+        },
+        unwrap_here,
+    },
+    anyhow::{bail, Context, Result},
+    ash::vk,
+    std::path::Path,
+};
+
+/// A host-visible buffer which receives a copy of a color image, e.g. a
+/// swapchain image or a [Texture], so it can be saved to disk.
+///
+/// Only the first mip level and array layer are copied. Copies are recorded
+/// with [Barriers], so the `synchronization2` device feature is required.
+#[derive(Debug)]
+pub struct ImageReadback {
+    buffer: CPUBuffer<u8>,
+    extent: vk::Extent2D,
+    format: vk::Format,
+}
+
+impl ImageReadback {
+    /// Allocates a buffer large enough to hold an image with the given extent
+    /// and format.
+    ///
+    /// Fails if the format can't be converted to 8-bit RGBA.
+    pub fn new(
+        ctx: &VulkanContext,
+        extent: vk::Extent2D,
+        format: vk::Format,
+    ) -> Result<Self> {
+        if !Self::supports_format(format) {
+            bail!("Unsupported image readback format: {:?}", format);
+        }
+        let buffer = unwrap_here!(
+            "Allocate image readback buffer",
+            CPUBuffer::allocate(
+                ctx,
+                (extent.width * extent.height * 4) as usize,
+                vk::BufferUsageFlags::TRANSFER_DST,
+            )
+        );
+        Ok(Self {
+            buffer,
+            extent,
+            format,
+        })
+    }
+
+    /// Returns true when images with `format` can be read back and converted
+    /// to 8-bit RGBA.
+    pub fn supports_format(format: vk::Format) -> bool {
+        matches!(
+            format,
+            vk::Format::R8G8B8A8_UNORM
+                | vk::Format::R8G8B8A8_SRGB
+                | vk::Format::B8G8R8A8_UNORM
+                | vk::Format::B8G8R8A8_SRGB
+                | vk::Format::A2B10G10R10_UNORM_PACK32
+                | vk::Format::A2R10G10B10_UNORM_PACK32
+        )
+    }
+
+    /// Allocates a buffer large enough to hold the texture's first mip level.
+    pub fn for_texture(ctx: &VulkanContext, texture: &Texture) -> Result<Self> {
+        Self::new(ctx, texture.extent(), texture.format())
+    }
+
+    /// The extent of the copied image.
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    /// Records a copy of the image into the readback buffer.
+    ///
+    /// The image is transitioned from `access` for the copy and transitioned
+    /// back to `access` afterwards. The image must have been created with
+    /// TRANSFER_SRC usage.
+    pub fn record_copy(
+        &self,
+        ctx: &VulkanContext,
+        command_buffer: vk::CommandBuffer,
+        image: vk::Image,
+        access: impl Into<ImageAccess>,
+    ) -> Result<()> {
+        let access = access.into();
+        Barriers::new()
+            .image(
+                image,
+                vk::ImageAspectFlags::COLOR,
+                access,
+                ImageUsage::TransferSrc,
+            )
+            .record(ctx, command_buffer)?;
+        self.record_copy_to_buffer(ctx, command_buffer, image)?;
+        Barriers::new()
+            .image(
+                image,
+                vk::ImageAspectFlags::COLOR,
+                ImageUsage::TransferSrc,
+                access,
+            )
+            .record(ctx, command_buffer)
+    }
+
+    /// Records a copy of the texture into the readback buffer.
+    ///
+    /// The texture is left in the [ImageUsage::TransferSrc] state.
+    pub fn record_texture_copy(
+        &self,
+        ctx: &VulkanContext,
+        command_buffer: vk::CommandBuffer,
+        texture: &Texture,
+    ) -> Result<()> {
+        Barriers::new()
+            .texture(texture, ImageUsage::TransferSrc)
+            .record(ctx, command_buffer)?;
+        self.record_copy_to_buffer(ctx, command_buffer, texture.image().raw)
+    }
+
+    /// Converts the copied pixels to an 8-bit RGBA image.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the commands recorded by the most recent copy must have completed.
+    pub unsafe fn to_rgba_image(&self) -> Result<image::RgbaImage> {
+        let mut pixels = vec![0u8; self.buffer.capacity()];
+        self.buffer.read_data(0, &mut pixels)?;
+        let rgba = convert_to_rgba8(self.format, pixels)?;
+        image::RgbaImage::from_raw(self.extent.width, self.extent.height, rgba)
+            .context("Readback buffer does not match the image extent")
+    }
+
+    /// Saves the copied pixels as a PNG file.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the commands recorded by the most recent copy must have completed.
+    pub unsafe fn save_png(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let image = self.to_rgba_image()?;
+        image
+            .save_with_format(path, image::ImageFormat::Png)
+            .with_context(|| format!("Unable to save {}", path.display()))
+    }
+
+    fn record_copy_to_buffer(
+        &self,
+        ctx: &VulkanContext,
+        command_buffer: vk::CommandBuffer,
+        image: vk::Image,
+    ) -> Result<()> {
+        unsafe {
+            ctx.cmd_copy_image_to_buffer(
+                command_buffer,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                self.buffer.buffer(),
+                &[vk::BufferImageCopy {
+                    buffer_offset: 0,
+                    buffer_row_length: 0,
+                    buffer_image_height: 0,
+                    image_subresource: vk::ImageSubresourceLayers {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        mip_level: 0,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    },
+                    image_offset: vk::Offset3D::default(),
+                    image_extent: vk::Extent3D {
+                        width: self.extent.width,
+                        height: self.extent.height,
+                        depth: 1,
+                    },
+                }],
+            );
+        }
+        Barriers::new()
+            .buffer(
+                self.buffer.buffer(),
+                0,
+                vk::WHOLE_SIZE,
+                BufferUsage::TransferDst,
+                BufferUsage::HostRead,
+            )
+            .record(ctx, command_buffer)
+    }
+}
+
+/// Converts tightly packed 32-bit pixels to 8-bit RGBA.
+///
+/// Color values are copied without any color space conversion, so sRGB and
+/// UNORM formats produce the same bytes.
+fn convert_to_rgba8(
+    format: vk::Format,
+    mut pixels: Vec<u8>,
+) -> Result<Vec<u8>> {
+    match format {
+        vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SRGB => {}
+        vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB => {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        vk::Format::A2B10G10R10_UNORM_PACK32
+        | vk::Format::A2R10G10B10_UNORM_PACK32 => {
+            for pixel in pixels.chunks_exact_mut(4) {
+                let packed = u32::from_le_bytes([
+                    pixel[0], pixel[1], pixel[2], pixel[3],
+                ]);
+                let low = ((packed & 0x3ff) >> 2) as u8;
+                let green = (((packed >> 10) & 0x3ff) >> 2) as u8;
+                let high = (((packed >> 20) & 0x3ff) >> 2) as u8;
+                let alpha = ((packed >> 30) * 85) as u8;
+                let (red, blue) =
+                    if format == vk::Format::A2B10G10R10_UNORM_PACK32 {
+                        (low, high)
+                    } else {
+                        (high, low)
+                    };
+                pixel.copy_from_slice(&[red, green, blue, alpha]);
+            }
+        }
+        _ => bail!("Unsupported image readback format: {:?}", format),
+    }
+    Ok(pixels)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn bgra_pixels_should_be_swizzled_to_rgba() {
+        let rgba =
+            convert_to_rgba8(vk::Format::B8G8R8A8_SRGB, vec![1, 2, 3, 4])
+                .unwrap();
+
+        assert_eq!(rgba, vec![3, 2, 1, 4]);
+    }
+
+    #[test]
+    pub fn ten_bit_pixels_should_be_truncated_to_eight_bits() {
+        let packed: u32 = (3 << 30) | (0x3ff << 20) | (0x200 << 10) | 0x004;
+        let rgba = convert_to_rgba8(
+            vk::Format::A2B10G10R10_UNORM_PACK32,
+            packed.to_le_bytes().to_vec(),
+        )
+        .unwrap();
+
+        assert_eq!(rgba, vec![1, 128, 255, 255]);
+    }
+}
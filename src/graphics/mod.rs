@@ -1,3 +1,6 @@
+mod barrier;
+mod image_readback;
+pub mod render_graph;
 pub mod streaming_renderer;
 pub mod vulkan;
 
@@ -6,6 +9,13 @@ use {
     nalgebra::Matrix4,
 };
 
+pub use self::{
+    barrier::{
+        aspect_mask_for_format, Barriers, BufferUsage, ImageAccess, ImageUsage,
+    },
+    image_readback::ImageReadback,
+};
+
 pub fn ortho_projection(aspect: f32, height: f32) -> Matrix4<f32> {
     let w = height * aspect;
     let h = height;
@@ -21,6 +31,9 @@ pub fn ortho_projection(aspect: f32, height: f32) -> Matrix4<f32> {
 
 /// Write a pipeline barrier to the command buffer to transition the image
 /// layout.
+///
+/// See [Barriers] for a synchronization2 alternative which batches barriers
+/// and tracks [streaming_renderer::Texture] layouts.
 #[builder]
 pub fn image_memory_barrier(
     ctx: &VulkanContext,
@@ -56,7 +69,7 @@ pub fn image_memory_barrier(
                 vk::ImageAspectFlags::COLOR
             },
             base_mip_level: 0,
-            level_count: 1,
+            level_count: vk::REMAINING_MIP_LEVELS,
             base_array_layer: 0,
             layer_count: 1,
         },
@@ -0,0 +1,626 @@
+//! A per-frame render graph which records barriers and allocates transient
+//! images automatically.
+//!
+//! Passes declare the images and buffers they read and write along with the
+//! [ImageUsage] or [BufferUsage] for each access. When the graph executes it:
+//!
+//! - culls passes which don't contribute to an output
+//! - allocates transient images from a [TransientImagePool]
+//! - records the barriers and layout transitions between passes
+//! - transitions the swapchain image for presentation
+//!
+//! # Example
+//!
+//! ```ignore
+//! let mut graph = RenderGraph::new();
+//! let target = graph.transient_image(extent, vk::Format::R16G16B16A16_SFLOAT);
+//! let swapchain_image = graph.import_swapchain_image(frame, &gfx.swapchain);
+//! graph
+//!     .add_pass("draw")
+//!     .write_image(target, ImageUsage::ColorAttachment)
+//!     .execute(|pass| { /* record draw commands */ Ok(()) });
+//! graph
+//!     .add_pass("blit")
+//!     .read_image(target, ImageUsage::TransferSrc)
+//!     .write_image(swapchain_image, ImageUsage::TransferDst)
+//!     .execute(|pass| { /* blit */ Ok(()) });
+//! graph.execute(&gfx.vulkan, frame, &mut transient_pool)?;
+//! ```
+
+mod pass_context;
+mod transient_pool;
+
+use {
+    crate::{
+        graphics::{
+            streaming_renderer::Texture,
+            vulkan::{Frame, Swapchain, VulkanContext},
+            Barriers, BufferUsage, ImageAccess, ImageUsage,
+        },
+        unwrap_here,
+    },
+    anyhow::{bail, Result},
+    ash::vk,
+};
+
+pub use self::{
+    pass_context::{PassContext, ResolvedImage},
+    transient_pool::TransientImagePool,
+};
+
+/// Identifies an image within a single [RenderGraph].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ImageId(usize);
+
+/// Identifies a buffer within a single [RenderGraph].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BufferId(usize);
+
+type PassFn<'a> = Box<dyn FnOnce(&PassContext) -> Result<()> + 'a>;
+
+enum GraphImage<'a> {
+    /// A texture owned by the application. Its tracked layout is used and
+    /// updated by the graph.
+    Texture(&'a Texture),
+
+    /// The frame's swapchain image.
+    Swapchain {
+        image: vk::Image,
+        view: vk::ImageView,
+        extent: vk::Extent2D,
+        format: vk::Format,
+    },
+
+    /// A texture allocated by the graph which only lives for the frame.
+    Transient {
+        extent: vk::Extent2D,
+        format: vk::Format,
+    },
+}
+
+struct GraphBuffer {
+    buffer: vk::Buffer,
+    last_usage: BufferUsage,
+}
+
+struct Pass<'a> {
+    name: String,
+    images: Vec<(ImageId, ImageUsage, Access)>,
+    buffers: Vec<(BufferId, BufferUsage, Access)>,
+    always_execute: bool,
+    execute: PassFn<'a>,
+}
+
+impl Pass<'_> {
+    fn resources(&self, access: Access) -> Vec<Resource> {
+        let images = self
+            .images
+            .iter()
+            .filter(|(_, _, a)| *a == access)
+            .map(|(id, _, _)| Resource::Image(id.0));
+        let buffers = self
+            .buffers
+            .iter()
+            .filter(|(_, _, a)| *a == access)
+            .map(|(id, _, _)| Resource::Buffer(id.0));
+        images.chain(buffers).collect()
+    }
+
+    fn deps(&self) -> PassDeps {
+        PassDeps {
+            reads: self.resources(Access::Read),
+            writes: self.resources(Access::Write),
+            always_execute: self.always_execute,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Access {
+    Read,
+    Write,
+}
+
+/// A collection of passes and the resources they use for a single frame.
+///
+/// Passes execute in the order they are added. See the [module
+/// docs](self) for an example.
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    images: Vec<GraphImage<'a>>,
+    buffers: Vec<GraphBuffer>,
+    outputs: Vec<ImageId>,
+    passes: Vec<Pass<'a>>,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an application-owned texture to the graph.
+    ///
+    /// Barriers for the texture start from its tracked access, and the
+    /// texture's tracked access is updated as the graph executes.
+    pub fn import_texture(&mut self, texture: &'a Texture) -> ImageId {
+        self.images.push(GraphImage::Texture(texture));
+        ImageId(self.images.len() - 1)
+    }
+
+    /// Adds the frame's swapchain image to the graph.
+    ///
+    /// The swapchain image is always an output. Its previous contents are
+    /// discarded and it is transitioned for presentation after the last pass.
+    pub fn import_swapchain_image(
+        &mut self,
+        frame: &Frame,
+        swapchain: &Swapchain,
+    ) -> ImageId {
+        self.images.push(GraphImage::Swapchain {
+            image: frame.swapchain_image(),
+            view: frame.swapchain_image_view(),
+            extent: swapchain.extent(),
+            format: swapchain.format(),
+        });
+        let id = ImageId(self.images.len() - 1);
+        self.outputs.push(id);
+        id
+    }
+
+    /// Adds an image which is allocated by the graph and only lives for the
+    /// frame.
+    ///
+    /// The image's usage flags are derived from the passes which use it.
+    /// Transient images are never preserved between frames.
+    pub fn transient_image(
+        &mut self,
+        extent: vk::Extent2D,
+        format: vk::Format,
+    ) -> ImageId {
+        self.images.push(GraphImage::Transient { extent, format });
+        ImageId(self.images.len() - 1)
+    }
+
+    /// Adds an application-owned buffer to the graph.
+    ///
+    /// `last_usage` is how the buffer was used before the graph executes. It's
+    /// used to create the barrier for the buffer's first use.
+    pub fn import_buffer(
+        &mut self,
+        buffer: vk::Buffer,
+        last_usage: BufferUsage,
+    ) -> BufferId {
+        self.buffers.push(GraphBuffer { buffer, last_usage });
+        BufferId(self.buffers.len() - 1)
+    }
+
+    /// Marks an image as an output of the graph so passes which write to it
+    /// are never culled.
+    pub fn mark_output(&mut self, image: ImageId) {
+        self.outputs.push(image);
+    }
+
+    /// Starts a new pass. The pass is added to the graph when
+    /// [PassBuilder::execute] is called.
+    pub fn add_pass(&mut self, name: impl Into<String>) -> PassBuilder<'_, 'a> {
+        PassBuilder {
+            graph: self,
+            name: name.into(),
+            images: vec![],
+            buffers: vec![],
+            always_execute: false,
+        }
+    }
+
+    /// Records every live pass into the frame's command buffer.
+    pub fn execute(
+        self,
+        ctx: &VulkanContext,
+        frame: &Frame,
+        transient_pool: &mut TransientImagePool,
+    ) -> Result<()> {
+        let live = live_passes(
+            &self.passes.iter().map(Pass::deps).collect::<Vec<_>>(),
+            &self
+                .outputs
+                .iter()
+                .map(|id| Resource::Image(id.0))
+                .collect::<Vec<_>>(),
+        );
+
+        // Collect the usage flags for each image used by a live pass.
+        let mut used = vec![false; self.images.len()];
+        let mut transient_usage =
+            vec![vk::ImageUsageFlags::empty(); self.images.len()];
+        for (pass, _) in self.passes.iter().zip(&live).filter(|(_, l)| **l) {
+            for (id, usage, _) in &pass.images {
+                used[id.0] = true;
+                transient_usage[id.0] |= usage.image_usage_flags();
+            }
+        }
+
+        // Allocate transient textures
+        let mut transients: Vec<Option<Texture>> = Vec::new();
+        for (index, image) in self.images.iter().enumerate() {
+            let texture = match image {
+                GraphImage::Transient { .. }
+                    if used[index] && transient_usage[index].is_empty() =>
+                {
+                    bail!(
+                        "Transient image {} has no usage flags, it's only \
+                         used as Undefined or Present",
+                        index
+                    );
+                }
+                GraphImage::Transient { extent, format } if used[index] => {
+                    Some(unwrap_here!(
+                        "Take a transient image from the pool",
+                        transient_pool.take(
+                            ctx,
+                            frame,
+                            *extent,
+                            *format,
+                            transient_usage[index],
+                        )
+                    ))
+                }
+                _ => None,
+            };
+            transients.push(texture);
+        }
+
+        let resolved_images = self
+            .images
+            .iter()
+            .zip(&transients)
+            .map(|(image, transient)| match (image, transient) {
+                (GraphImage::Texture(texture), _) => {
+                    Some(ResolvedImage::for_texture(texture))
+                }
+                (GraphImage::Transient { .. }, Some(texture)) => {
+                    Some(ResolvedImage::for_texture(texture))
+                }
+                (
+                    GraphImage::Swapchain {
+                        image,
+                        view,
+                        extent,
+                        format,
+                    },
+                    _,
+                ) => Some(ResolvedImage {
+                    image: *image,
+                    view: *view,
+                    extent: *extent,
+                    format: *format,
+                }),
+                (GraphImage::Transient { .. }, None) => None,
+            })
+            .collect::<Vec<_>>();
+        let resolved_buffers = self
+            .buffers
+            .iter()
+            .map(|buffer| buffer.buffer)
+            .collect::<Vec<_>>();
+
+        // Track the state of untracked resources as passes execute.
+        let mut swapchain_access = vec![
+            ImageAccess {
+                // Frames wait for the swapchain image to be acquired at the
+                // color attachment output stage, so the first transition
+                // must be chained to that stage.
+                stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                access_mask: vk::AccessFlags2::NONE,
+                layout: vk::ImageLayout::UNDEFINED,
+            };
+            self.images.len()
+        ];
+        let mut transient_first_use = vec![true; self.images.len()];
+        let mut buffer_usage = self
+            .buffers
+            .iter()
+            .map(|buffer| buffer.last_usage)
+            .collect::<Vec<_>>();
+
+        for (pass, _) in self.passes.into_iter().zip(&live).filter(|(_, l)| **l)
+        {
+            log::trace!("Render graph pass: {}", pass.name);
+            let image_accesses = unwrap_here!(
+                format!("Merge image usages for pass '{}'", pass.name),
+                merged_image_accesses(&pass.images)
+            );
+            let mut barriers = Barriers::new();
+            for (id, access) in image_accesses {
+                barriers = match &self.images[id.0] {
+                    GraphImage::Texture(texture) => {
+                        barriers.texture_access(texture, access)
+                    }
+                    GraphImage::Transient { .. } => {
+                        let Some(texture) = transients[id.0].as_ref() else {
+                            bail!(
+                                "Transient image {} was not allocated for \
+                                 pass '{}'",
+                                id.0,
+                                pass.name
+                            );
+                        };
+                        if transient_first_use[id.0] {
+                            transient_first_use[id.0] = false;
+                            barriers.texture_discarding(texture, access)
+                        } else {
+                            barriers.texture_access(texture, access)
+                        }
+                    }
+                    GraphImage::Swapchain { image, .. } => {
+                        let previous = swapchain_access[id.0];
+                        swapchain_access[id.0] = access;
+                        if previous == access && !access.is_write() {
+                            barriers
+                        } else {
+                            barriers.image(
+                                *image,
+                                vk::ImageAspectFlags::COLOR,
+                                previous,
+                                access,
+                            )
+                        }
+                    }
+                };
+            }
+            for (id, usage, _) in &pass.buffers {
+                let previous = buffer_usage[id.0];
+                buffer_usage[id.0] = *usage;
+                if previous == BufferUsage::None
+                    || (!previous.is_write() && !usage.is_write())
+                {
+                    continue;
+                }
+                barriers = barriers.buffer(
+                    self.buffers[id.0].buffer,
+                    0,
+                    vk::WHOLE_SIZE,
+                    previous,
+                    *usage,
+                );
+            }
+            barriers.record(ctx, frame.command_buffer())?;
+
+            let pass_context = PassContext {
+                ctx,
+                frame,
+                images: &resolved_images,
+                buffers: &resolved_buffers,
+            };
+            unwrap_here!(
+                format!("Execute render graph pass '{}'", pass.name),
+                (pass.execute)(&pass_context)
+            );
+        }
+
+        // Transition swapchain images for presentation
+        let mut barriers = Barriers::new();
+        for (index, image) in self.images.iter().enumerate() {
+            if let GraphImage::Swapchain { image, .. } = image {
+                barriers = barriers.image(
+                    *image,
+                    vk::ImageAspectFlags::COLOR,
+                    swapchain_access[index],
+                    ImageUsage::Present,
+                );
+            }
+        }
+        barriers.record(ctx, frame.command_buffer())?;
+
+        transient_pool
+            .restore(frame, transients.into_iter().flatten().collect());
+
+        Ok(())
+    }
+}
+
+/// Merges every usage a pass declares for an image into a single access, so
+/// each image gets at most one barrier per pass.
+///
+/// A pass which reads and writes the same image (e.g. a color attachment with
+/// LOAD_OP_LOAD) declares the same layout twice. Fails if the usages need
+/// different layouts.
+fn merged_image_accesses(
+    images: &[(ImageId, ImageUsage, Access)],
+) -> Result<Vec<(ImageId, ImageAccess)>> {
+    let mut merged: Vec<(ImageId, ImageAccess)> = vec![];
+    for (id, usage, _) in images {
+        let access = usage.access();
+        let Some((_, merged_access)) =
+            merged.iter_mut().find(|(merged_id, _)| merged_id == id)
+        else {
+            merged.push((*id, access));
+            continue;
+        };
+        if merged_access.layout != access.layout {
+            bail!(
+                "Image {} is used with both {:?} and {:?} in the same pass",
+                id.0,
+                merged_access.layout,
+                access.layout
+            );
+        }
+        merged_access.stage_mask |= access.stage_mask;
+        merged_access.access_mask |= access.access_mask;
+    }
+    Ok(merged)
+}
+
+/// Declares the resources used by a pass.
+pub struct PassBuilder<'g, 'a> {
+    graph: &'g mut RenderGraph<'a>,
+    name: String,
+    images: Vec<(ImageId, ImageUsage, Access)>,
+    buffers: Vec<(BufferId, BufferUsage, Access)>,
+    always_execute: bool,
+}
+
+impl<'a> PassBuilder<'_, 'a> {
+    /// The pass reads the image with the given usage.
+    pub fn read_image(mut self, image: ImageId, usage: ImageUsage) -> Self {
+        self.images.push((image, usage, Access::Read));
+        self
+    }
+
+    /// The pass writes the image with the given usage.
+    ///
+    /// Writing does not imply reading. If the pass depends on the image's
+    /// previous contents (e.g. a color attachment with LOAD_OP_LOAD) then it
+    /// should also declare a read so the previous writers are not culled. The
+    /// read and write are merged into a single barrier.
+    pub fn write_image(mut self, image: ImageId, usage: ImageUsage) -> Self {
+        self.images.push((image, usage, Access::Write));
+        self
+    }
+
+    /// The pass reads the buffer with the given usage.
+    pub fn read_buffer(mut self, buffer: BufferId, usage: BufferUsage) -> Self {
+        self.buffers.push((buffer, usage, Access::Read));
+        self
+    }
+
+    /// The pass writes the buffer with the given usage.
+    pub fn write_buffer(
+        mut self,
+        buffer: BufferId,
+        usage: BufferUsage,
+    ) -> Self {
+        self.buffers.push((buffer, usage, Access::Write));
+        self
+    }
+
+    /// Keeps the pass even when it doesn't contribute to a graph output.
+    /// Useful for passes with side effects, like GPU readback.
+    pub fn always_execute(mut self) -> Self {
+        self.always_execute = true;
+        self
+    }
+
+    /// Adds the pass to the graph. `execute` is called to record the pass's
+    /// commands when the graph executes, after barriers for the declared
+    /// resources are recorded.
+    pub fn execute(
+        self,
+        execute: impl FnOnce(&PassContext) -> Result<()> + 'a,
+    ) {
+        self.graph.passes.push(Pass {
+            name: self.name,
+            images: self.images,
+            buffers: self.buffers,
+            always_execute: self.always_execute,
+            execute: Box::new(execute),
+        });
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Resource {
+    Image(usize),
+    Buffer(usize),
+}
+
+/// The resources read and written by a pass, used for culling.
+#[derive(Debug)]
+struct PassDeps {
+    reads: Vec<Resource>,
+    writes: Vec<Resource>,
+    always_execute: bool,
+}
+
+/// Returns a flag for each pass indicating whether it contributes to one of
+/// the outputs.
+///
+/// Passes are walked in reverse. A pass is live if it writes a resource that's
+/// needed by a later live pass or an output. The resources read by a live
+/// pass are then needed by the passes before it.
+fn live_passes(passes: &[PassDeps], outputs: &[Resource]) -> Vec<bool> {
+    let mut needed = outputs.to_vec();
+    let mut live = vec![false; passes.len()];
+    for (index, pass) in passes.iter().enumerate().rev() {
+        let contributes = pass.always_execute
+            || pass.writes.iter().any(|write| needed.contains(write));
+        if !contributes {
+            continue;
+        }
+        live[index] = true;
+        needed.extend(pass.reads.iter().copied());
+    }
+    live
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pass(reads: &[Resource], writes: &[Resource]) -> PassDeps {
+        PassDeps {
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            always_execute: false,
+        }
+    }
+
+    #[test]
+    pub fn passes_that_do_not_reach_an_output_should_be_culled() {
+        let output = Resource::Image(0);
+        let target = Resource::Image(1);
+        let unused = Resource::Image(2);
+        let passes = [
+            pass(&[], &[target]),
+            pass(&[], &[unused]),
+            pass(&[target], &[output]),
+        ];
+
+        assert_eq!(live_passes(&passes, &[output]), vec![true, false, true]);
+    }
+
+    #[test]
+    pub fn always_execute_passes_should_not_be_culled() {
+        let output = Resource::Image(0);
+        let buffer = Resource::Buffer(0);
+        let image = Resource::Image(1);
+        let passes = [
+            pass(&[], &[image]),
+            PassDeps {
+                reads: vec![image],
+                writes: vec![buffer],
+                always_execute: true,
+            },
+        ];
+
+        assert_eq!(live_passes(&passes, &[output]), vec![true, true]);
+    }
+
+    #[test]
+    pub fn image_usages_should_merge_into_one_access_per_image() {
+        let target = ImageId(0);
+        let other = ImageId(1);
+        let images = [
+            (target, ImageUsage::ColorAttachment, Access::Read),
+            (other, ImageUsage::StorageRead, Access::Read),
+            (target, ImageUsage::ColorAttachment, Access::Write),
+            (other, ImageUsage::StorageWrite, Access::Write),
+        ];
+
+        let merged = merged_image_accesses(&images).unwrap();
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0], (target, ImageUsage::ColorAttachment.access()));
+        assert_eq!(merged[1], (other, ImageUsage::StorageReadWrite.access()));
+    }
+
+    #[test]
+    pub fn image_usages_with_different_layouts_should_not_merge() {
+        let target = ImageId(0);
+        let images = [
+            (target, ImageUsage::Sampled, Access::Read),
+            (target, ImageUsage::ColorAttachment, Access::Write),
+        ];
+
+        assert!(merged_image_accesses(&images).is_err());
+    }
+}
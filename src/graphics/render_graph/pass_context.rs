@@ -0,0 +1,70 @@
+use {
+    super::{BufferId, ImageId},
+    crate::graphics::{
+        streaming_renderer::Texture,
+        vulkan::{Frame, VulkanContext},
+    },
+    anyhow::{Context, Result},
+    ash::vk,
+};
+
+/// The Vulkan handles for an image used by the graph.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ResolvedImage {
+    pub image: vk::Image,
+    pub view: vk::ImageView,
+    pub extent: vk::Extent2D,
+    pub format: vk::Format,
+}
+
+impl ResolvedImage {
+    pub(super) fn for_texture(texture: &Texture) -> Self {
+        Self {
+            image: texture.image().raw,
+            view: texture.view().raw,
+            extent: texture.extent(),
+            format: texture.format(),
+        }
+    }
+}
+
+/// Provided to each pass while it records commands.
+///
+/// Barriers for the pass's declared resources have already been recorded by
+/// the time the pass executes.
+pub struct PassContext<'p> {
+    pub(super) ctx: &'p VulkanContext,
+    pub(super) frame: &'p Frame,
+    pub(super) images: &'p [Option<ResolvedImage>],
+    pub(super) buffers: &'p [vk::Buffer],
+}
+
+impl PassContext<'_> {
+    pub fn vulkan(&self) -> &VulkanContext {
+        self.ctx
+    }
+
+    pub fn frame(&self) -> &Frame {
+        self.frame
+    }
+
+    /// The frame's command buffer.
+    pub fn command_buffer(&self) -> vk::CommandBuffer {
+        self.frame.command_buffer()
+    }
+
+    /// Returns the Vulkan handles for an image.
+    ///
+    /// Fails if the image is a transient image that isn't used by any live
+    /// pass. This can't happen for images declared by the executing pass.
+    pub fn image(&self, id: ImageId) -> Result<ResolvedImage> {
+        self.images[id.0].with_context(|| {
+            format!("Transient image {} was never allocated", id.0)
+        })
+    }
+
+    /// Returns the buffer's Vulkan handle.
+    pub fn buffer(&self, id: BufferId) -> vk::Buffer {
+        self.buffers[id.0]
+    }
+}
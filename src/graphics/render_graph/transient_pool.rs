@@ -0,0 +1,106 @@
+use {
+    crate::{
+        graphics::{
+            streaming_renderer::Texture,
+            vulkan::{Frame, FramesInFlight, VulkanContext},
+        },
+        unwrap_here,
+    },
+    anyhow::Result,
+    ash::vk,
+};
+
+/// Everything needed to decide if a pooled texture can be reused.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct TransientKey {
+    extent: vk::Extent2D,
+    format: vk::Format,
+    usage: vk::ImageUsageFlags,
+}
+
+struct PooledTexture {
+    key: TransientKey,
+    texture: Texture,
+}
+
+/// Owns the textures backing a [RenderGraph](super::RenderGraph)'s transient
+/// images.
+///
+/// The pool keeps separate textures for each frame in flight. Textures are
+/// only handed out to graphs executing with the same frame index, which
+/// guarantees the GPU has finished with them by the time they are reused.
+pub struct TransientImagePool {
+    frames: Vec<Vec<PooledTexture>>,
+}
+
+impl TransientImagePool {
+    pub fn new(frames_in_flight: &FramesInFlight) -> Self {
+        Self {
+            frames: (0..frames_in_flight.frame_count())
+                .map(|_| vec![])
+                .collect(),
+        }
+    }
+
+    /// Drops every pooled texture.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that no pending frames reference the textures.
+    pub unsafe fn clear(&mut self) {
+        for frame in &mut self.frames {
+            frame.clear();
+        }
+    }
+
+    /// Takes a matching texture from the frame's pool, or creates a new one.
+    pub(super) fn take(
+        &mut self,
+        ctx: &VulkanContext,
+        frame: &Frame,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+    ) -> Result<Texture> {
+        let key = TransientKey {
+            extent,
+            format,
+            usage,
+        };
+        let pool = &mut self.frames[frame.frame_index()];
+        if let Some(index) = pool.iter().position(|pooled| pooled.key == key) {
+            return Ok(pool.swap_remove(index).texture);
+        }
+
+        log::trace!("Allocating transient image {:#?}", key);
+        let texture = unwrap_here!(
+            "Create transient render graph texture",
+            Texture::builder()
+                .ctx(ctx)
+                .dimensions((extent.width, extent.height))
+                .format(format)
+                .image_usage_flags(usage)
+                .memory_property_flags(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+                .build()
+        );
+        Ok(texture)
+    }
+
+    /// Returns textures used by the frame's graph to the pool.
+    ///
+    /// Any textures left in the frame's pool were not used by the graph and
+    /// are dropped. This is safe because the frame's previous commands have
+    /// completed.
+    pub(super) fn restore(&mut self, frame: &Frame, textures: Vec<Texture>) {
+        let pool = &mut self.frames[frame.frame_index()];
+        pool.clear();
+        pool.extend(textures.into_iter().map(|texture| PooledTexture {
+            key: TransientKey {
+                extent: texture.extent(),
+                format: texture.format(),
+                usage: texture.usage(),
+            },
+            texture,
+        }));
+    }
+}
@@ -0,0 +1,219 @@
+use {
+    super::Vertex,
+    crate::{
+        graphics::vulkan::{raii, spirv_words, VulkanContext},
+        unwrap_here,
+    },
+    anyhow::Result,
+    ash::vk,
+};
+
+/// The number of draws culled by each compute workgroup.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// An axis-aligned bounding box in mesh space.
+///
+/// Bounds are used to frustum-cull meshes on the GPU, see
+/// [StreamingRenderer::cull_batch](super::StreamingRenderer::cull_batch).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Bounds {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Bounds {
+    pub fn new(min: [f32; 3], max: [f32; 3]) -> Self {
+        Self { min, max }
+    }
+
+    /// Returns the smallest bounds containing every vertex, or None if there
+    /// are no vertices.
+    pub fn from_vertices(vertices: &[Vertex]) -> Option<Self> {
+        let (first, rest) = vertices.split_first()?;
+        Some(
+            rest.iter().fold(
+                Self::new(first.pos, first.pos),
+                |bounds, vertex| {
+                    bounds.union(&Self::new(vertex.pos, vertex.pos))
+                },
+            ),
+        )
+    }
+
+    /// Returns the smallest bounds containing both bounds.
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: std::array::from_fn(|i| self.min[i].min(other.min[i])),
+            max: std::array::from_fn(|i| self.max[i].max(other.max[i])),
+        }
+    }
+}
+
+/// A single draw as read by the culling shader.
+#[repr(C, align(16))]
+#[derive(Debug, Copy, Clone)]
+pub(super) struct CullInput {
+    pub command: vk::DrawIndexedIndirectCommand,
+    pub run_index: u32,
+    pub run_first_command: u32,
+    pub transform_index: u32,
+    pub bounds_min: [f32; 4],
+    pub bounds_max: [f32; 4],
+}
+
+impl CullInput {
+    pub fn new(
+        command: vk::DrawIndexedIndirectCommand,
+        run_index: u32,
+        run_first_command: u32,
+        transform_index: u32,
+        bounds: Option<Bounds>,
+    ) -> Self {
+        let (bounds_min, bounds_max) = match bounds {
+            Some(Bounds { min, max }) => {
+                ([min[0], min[1], min[2], 1.0], [max[0], max[1], max[2], 1.0])
+            }
+            None => ([0.0; 4], [0.0; 4]),
+        };
+        Self {
+            command,
+            run_index,
+            run_first_command,
+            transform_index,
+            bounds_min,
+            bounds_max,
+        }
+    }
+}
+
+/// The compute pipeline which frustum-culls draws and compacts the visible
+/// draws into indirect command buffers.
+pub(super) struct GpuCulling {
+    pipeline: raii::Pipeline,
+    pipeline_layout: raii::PipelineLayout,
+}
+
+impl GpuCulling {
+    pub fn new(ctx: &VulkanContext) -> Result<Self> {
+        let push_constant_ranges = [vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            offset: 0,
+            // inputs, commands, counts, transforms, draw count + padding
+            size: 8 + 8 + 8 + 8 + 8,
+        }];
+        let pipeline_layout = unwrap_here!(
+            "Create culling pipeline layout",
+            raii::PipelineLayout::new(
+                "GpuCulling",
+                ctx.device.clone(),
+                &vk::PipelineLayoutCreateInfo {
+                    push_constant_range_count: push_constant_ranges.len()
+                        as u32,
+                    p_push_constant_ranges: push_constant_ranges.as_ptr(),
+                    ..Default::default()
+                },
+            )
+        );
+
+        let shader_words = unwrap_here!(
+            "Pack culling shader source",
+            spirv_words(include_bytes!("./shaders/cull.comp.spv"))
+        );
+        let shader_module = unwrap_here!(
+            "Create culling shader module",
+            raii::ShaderModule::new(
+                "CullingComputeShader",
+                ctx.device.clone(),
+                &vk::ShaderModuleCreateInfo {
+                    code_size: shader_words.len() * 4,
+                    p_code: shader_words.as_ptr(),
+                    ..Default::default()
+                },
+            )
+        );
+        let pipeline = unwrap_here!(
+            "Create culling compute pipeline",
+            raii::Pipeline::new_compute_pipeline(
+                ctx.device.clone(),
+                &vk::ComputePipelineCreateInfo {
+                    stage: vk::PipelineShaderStageCreateInfo {
+                        stage: vk::ShaderStageFlags::COMPUTE,
+                        module: shader_module.raw,
+                        p_name: c"main".as_ptr(),
+                        ..Default::default()
+                    },
+                    layout: pipeline_layout.raw,
+                    ..Default::default()
+                },
+            )
+        );
+
+        Ok(Self {
+            pipeline,
+            pipeline_layout,
+        })
+    }
+
+    /// Records the culling dispatch.
+    ///
+    /// Counts must be zeroed before the dispatch executes. The caller is
+    /// responsible for the barrier which makes the commands and counts visible
+    /// to indirect draws.
+    pub fn record(
+        &self,
+        ctx: &VulkanContext,
+        command_buffer: vk::CommandBuffer,
+        addresses: [vk::DeviceAddress; 4],
+        draw_count: u32,
+    ) {
+        let mut push_constants = Vec::with_capacity(40);
+        for address in addresses {
+            push_constants.extend_from_slice(&address.to_le_bytes());
+        }
+        push_constants.extend_from_slice(&draw_count.to_le_bytes());
+        unsafe {
+            ctx.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline.raw,
+            );
+            ctx.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout.raw,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                &push_constants,
+            );
+            ctx.cmd_dispatch(
+                command_buffer,
+                draw_count.div_ceil(WORKGROUP_SIZE),
+                1,
+                1,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn bounds_should_contain_every_vertex() {
+        let vertex = |pos| Vertex::new(pos, [0.0, 0.0], [1.0; 4], -1);
+        assert_eq!(Bounds::from_vertices(&[]), None);
+        assert_eq!(
+            Bounds::from_vertices(&[
+                vertex([1.0, -2.0, 0.0]),
+                vertex([-1.0, 3.0, 0.5]),
+                vertex([0.0, 0.0, -0.5]),
+            ]),
+            Some(Bounds::new([-1.0, -2.0, -0.5], [1.0, 3.0, 0.5]))
+        );
+    }
+
+    #[test]
+    pub fn cull_input_should_match_std430_layout() {
+        assert_eq!(std::mem::size_of::<CullInput>(), 64);
+    }
+}
@@ -0,0 +1,140 @@
+use {
+    super::{BlendMode, Mesh},
+    ash::vk::Handle,
+    nalgebra::Vector4,
+    std::cmp::Ordering,
+};
+
+/// Controls the order meshes in a batch are drawn.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum DrawOrder {
+    /// Meshes are drawn in the order they were provided.
+    #[default]
+    Submission,
+
+    /// Meshes are drawn in order of their [Mesh::layer]. Within a layer,
+    /// opaque meshes are drawn first, grouped by pipeline and then by
+    /// parameter buffer to minimize state changes, followed by transparent
+    /// meshes from back to front.
+    ///
+    /// A mesh is opaque when its material uses [BlendMode::Opaque]. Depth is
+    /// the clip-space depth of the mesh transform's origin.
+    ///
+    /// Batches culled with
+    /// [StreamingRenderer::cull_batch](super::StreamingRenderer::cull_batch)
+    /// lose the back-to-front order, because visible draws within each run are
+    /// drawn in an unspecified order.
+    Sorted,
+}
+
+/// The values used to order a single draw when sorting.
+#[derive(Debug, Copy, Clone)]
+pub(super) struct SortKey {
+    layer: i32,
+    transparent: bool,
+    pipeline: u64,
+    parameters: u64,
+    depth: f32,
+}
+
+impl SortKey {
+    pub fn for_mesh<VertexT>(mesh: &dyn Mesh<VertexT>) -> Self {
+        let material = mesh.material();
+        let origin = mesh.transform() * Vector4::new(0.0, 0.0, 0.0, 1.0);
+        let depth = if origin.w != 0.0 {
+            origin.z / origin.w
+        } else {
+            origin.z
+        };
+        Self::new(
+            mesh.layer(),
+            material.state().blend != BlendMode::Opaque,
+            material.pipeline().raw.as_raw(),
+            material.parameters_address(),
+            depth,
+        )
+    }
+
+    fn new(
+        layer: i32,
+        transparent: bool,
+        pipeline: u64,
+        parameters: u64,
+        depth: f32,
+    ) -> Self {
+        Self {
+            layer,
+            transparent,
+            pipeline,
+            parameters,
+            depth,
+        }
+    }
+
+    /// Orders draws by layer, then opaque before transparent. Opaque draws
+    /// are grouped by pipeline, then by parameter buffer, and transparent
+    /// draws are ordered from the largest depth to the smallest.
+    pub fn compare(&self, other: &Self) -> Ordering {
+        self.layer
+            .cmp(&other.layer)
+            .then(self.transparent.cmp(&other.transparent))
+            .then_with(|| {
+                if self.transparent {
+                    other.depth.total_cmp(&self.depth)
+                } else {
+                    self.pipeline
+                        .cmp(&other.pipeline)
+                        .then(self.parameters.cmp(&other.parameters))
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn sorted_draws_should_draw_transparent_meshes_back_to_front() {
+        let mut keys = [
+            SortKey::new(0, true, 1, 0, 0.2),
+            SortKey::new(0, false, 2, 0, 0.5),
+            SortKey::new(1, false, 1, 0, 0.0),
+            SortKey::new(0, true, 1, 0, 0.8),
+            SortKey::new(0, false, 1, 0, 0.9),
+            SortKey::new(-1, true, 3, 0, 0.0),
+        ];
+        keys.sort_by(SortKey::compare);
+
+        let order: Vec<(i32, bool, u64, f32)> = keys
+            .iter()
+            .map(|key| (key.layer, key.transparent, key.pipeline, key.depth))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                (-1, true, 3, 0.0),
+                (0, false, 1, 0.9),
+                (0, false, 2, 0.5),
+                (0, true, 1, 0.8),
+                (0, true, 1, 0.2),
+                (1, false, 1, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn sorted_draws_should_group_opaque_meshes_by_parameters() {
+        let mut keys = [
+            SortKey::new(0, false, 1, 20, 0.1),
+            SortKey::new(0, false, 1, 10, 0.2),
+            SortKey::new(0, false, 1, 20, 0.3),
+            SortKey::new(0, false, 1, 10, 0.4),
+        ];
+        keys.sort_by(SortKey::compare);
+
+        let parameters: Vec<u64> =
+            keys.iter().map(|key| key.parameters).collect();
+        assert_eq!(parameters, vec![10, 10, 20, 20]);
+    }
+}
@@ -5,12 +5,18 @@ use {
     ash::vk,
 };
 
-/// An automatically resizable CPU buffer that reallocates the underlying buffer
-/// if needed.
+/// An automatically resizable, append-only CPU buffer that reallocates the
+/// underlying buffer if needed.
 pub struct DynamicBuffer<DataT: Copy> {
     usage: vk::BufferUsageFlags,
     cpu_buffer: CPUBuffer<DataT>,
     buffer_device_address: vk::DeviceAddress,
+
+    /// The index where the next append begins.
+    cursor: usize,
+
+    /// Buffers replaced since the last reset which may still be in use.
+    retired: Vec<CPUBuffer<DataT>>,
 }
 
 impl<DataT: Copy> DynamicBuffer<DataT> {
@@ -42,6 +48,8 @@ impl<DataT: Copy> DynamicBuffer<DataT> {
             usage,
             cpu_buffer,
             buffer_device_address: address,
+            cursor: 0,
+            retired: Vec::new(),
         })
     }
 
@@ -49,8 +57,8 @@ impl<DataT: Copy> DynamicBuffer<DataT> {
     ///
     /// # Safety
     ///
-    /// Note that the returned buffer handle can be invalidated by calls to
-    /// write_data.
+    /// Note that the returned buffer handle can be replaced by calls to
+    /// append_chunked_data.
     pub fn raw(&self) -> vk::Buffer {
         self.cpu_buffer.buffer()
     }
@@ -63,102 +71,91 @@ impl<DataT: Copy> DynamicBuffer<DataT> {
         self.buffer_device_address
     }
 
-    /// Writes the provided data to the underlying buffer.
+    /// Frees buffers retired by earlier appends and starts appending at the
+    /// beginning of the buffer again.
     ///
     /// # Safety
     ///
-    /// The caller is responsible for synchronizing access to the underlying
-    /// buffer. This method can reallocate the buffer, which will cause
-    /// problems if the GPU is still accessing the buffer when this function
-    /// is called.
-    pub unsafe fn write_chunked_data(
-        &mut self,
-        ctx: &VulkanContext,
-        data: &[&[DataT]],
-    ) -> Result<bool> {
-        let reallocated = unsafe {
-            self.maybe_reallocate(
-                ctx,
-                data.iter().map(|chunk| chunk.len()).sum(),
-            )?
-        };
-
-        let mut offset = 0;
-        for chunk in data {
-            unsafe {
-                self.cpu_buffer.write_data(offset, chunk)?;
-            }
-            offset += chunk.len();
-        }
-
-        Ok(reallocated)
+    /// The caller must ensure the GPU is no longer accessing any data written
+    /// since the last reset.
+    pub unsafe fn reset(&mut self) {
+        self.retired.clear();
+        self.cursor = 0;
     }
 
-    /// Writes iterated data to the underlying buffer.
+    /// Appends the provided data after any data written since the last reset.
     ///
-    /// Must be an exact size iterator so the buffer can be reallocated if
-    /// necessary without an additional copy on the CPU.
+    /// If there isn't enough space, a larger buffer is allocated and the data
+    /// is written to its beginning. The previous buffer is retired rather
+    /// than freed, so data appended earlier remains valid until the next
+    /// reset.
+    ///
+    /// # Returns
+    ///
+    /// Returns the index of the first appended element within the current
+    /// buffer.
     ///
     /// # Safety
     ///
     /// The caller is responsible for synchronizing access to the underlying
-    /// buffer. This method can reallocate the buffer, which will cause
-    /// problems if the GPU is still accessing the buffer when this function
-    /// is called.
-    pub unsafe fn write_iterated_data<I>(
+    /// buffer. The handle returned by [Self::raw] can change with every
+    /// append.
+    pub unsafe fn append_chunked_data(
         &mut self,
         ctx: &VulkanContext,
-        data: I,
-    ) -> Result<bool>
-    where
-        I: ExactSizeIterator<Item = DataT>,
-    {
-        let reallocated = unsafe {
-            self.maybe_reallocate(
-                ctx,
-                data.len() * std::mem::size_of::<DataT>(),
-            )?
-        };
+        data: &[&[DataT]],
+    ) -> Result<usize> {
+        let count: usize = data.iter().map(|chunk| chunk.len()).sum();
+        unsafe {
+            self.maybe_reallocate(ctx, self.cursor + count)?;
+        }
 
-        for (index, item) in data.enumerate() {
-            unsafe { self.cpu_buffer.write_data(index, &[item])? }
+        let first = self.cursor;
+        for chunk in data {
+            unsafe {
+                self.cpu_buffer.write_data(self.cursor, chunk)?;
+            }
+            self.cursor += chunk.len();
         }
 
-        Ok(reallocated)
+        Ok(first)
     }
 
-    /// Checks that the buffer has enough space for `required_size` and
-    /// reallocates the underlying storage if needed.
+    /// Checks that the buffer has enough space for `required_count` elements
+    /// and replaces the underlying storage if needed.
     ///
-    /// # Returns
-    ///
-    /// Returns `true` when the buffer was reallocated and `false` if not.
+    /// Replaced buffers are retired until the next reset and appends continue
+    /// from the beginning of the new buffer.
     ///
     /// # Safety
     ///
-    /// Unsafe because the caller must synchronize access to the buffer and
-    /// ensure it is not in use by the GPU when this function is called as
-    /// the buffer _could_ be deleted during reallocation.
+    /// Unsafe because the caller must synchronize access to the buffer.
     unsafe fn maybe_reallocate(
         &mut self,
         ctx: &VulkanContext,
-        required_size: usize,
-    ) -> Result<bool> {
-        if self.cpu_buffer.capacity() >= required_size {
-            return Ok(false);
+        required_count: usize,
+    ) -> Result<()> {
+        if self.cpu_buffer.capacity() >= required_count {
+            return Ok(());
         }
 
-        let new_size = round_to_power_of_two(required_size);
+        let required_count = required_count - self.cursor;
+        let new_count = round_to_power_of_two(
+            required_count.max(self.cpu_buffer.capacity() * 2),
+        );
 
         log::trace!(
             "Reallocating. Current size: {}, Required size: {}, New size: {}",
             self.cpu_buffer.capacity(),
-            required_size,
-            new_size
+            required_count,
+            new_count
         );
 
-        self.cpu_buffer = CPUBuffer::allocate(ctx, new_size, self.usage)
+        let cpu_buffer = CPUBuffer::allocate(ctx, new_count, self.usage)
             .context("Unable to reallocate new buffer!")?;
+        self.retired
+            .push(std::mem::replace(&mut self.cpu_buffer, cpu_buffer));
+        self.cursor = 0;
 
         if self
             .usage
@@ -172,6 +169,6 @@ impl<DataT: Copy> DynamicBuffer<DataT> {
             }
         }
 
-        Ok(true)
+        Ok(())
     }
 }
@@ -0,0 +1,148 @@
+use {
+    super::{Bounds, Material, Mesh, TrianglesMesh, Vertex},
+    ash::vk,
+    nalgebra::Matrix4,
+    std::sync::Arc,
+};
+
+/// Per-instance data applied to every vertex of an instanced draw.
+///
+/// The instance transform is applied before the mesh transform, and the
+/// instance color is multiplied with the vertex color.
+#[repr(C, align(16))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Instance {
+    pub transform: [[f32; 4]; 4],
+    pub color: [f32; 4],
+
+    /// Replaces the vertex texture index when non-negative.
+    pub texture_index: i32,
+}
+
+impl Instance {
+    /// An instance which leaves its vertices unchanged.
+    pub const IDENTITY: Self = Self {
+        transform: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+        color: [1.0, 1.0, 1.0, 1.0],
+        texture_index: -1,
+    };
+
+    pub fn new(
+        transform: Matrix4<f32>,
+        color: [f32; 4],
+        texture_index: i32,
+    ) -> Self {
+        Self {
+            transform: transform.data.0,
+            color,
+            texture_index,
+        }
+    }
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// A mesh whose geometry is drawn once for every instance with a single
+/// instanced draw call.
+///
+/// This is useful for particle fields, repeated glyphs, and other geometry
+/// that is repeated many times with small variations.
+pub struct InstancedMesh<VertexT: Copy = Vertex> {
+    geometry: TrianglesMesh<VertexT>,
+    instances: Vec<Instance>,
+}
+
+impl<VertexT: Copy> Mesh<VertexT> for InstancedMesh<VertexT> {
+    fn vertices(&self) -> &[VertexT] {
+        self.geometry.vertices()
+    }
+
+    fn indices(&self) -> &[u32] {
+        self.geometry.indices()
+    }
+
+    fn material(&self) -> &Arc<Material> {
+        self.geometry.material()
+    }
+
+    fn transform(&self) -> &Matrix4<f32> {
+        self.geometry.transform()
+    }
+
+    fn scissor(&self) -> vk::Rect2D {
+        self.geometry.scissor()
+    }
+
+    fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    fn layer(&self) -> i32 {
+        self.geometry.layer()
+    }
+
+    /// The geometry's bounds, which must contain every instance.
+    fn bounds(&self) -> Option<Bounds> {
+        self.geometry.bounds()
+    }
+}
+
+impl<VertexT: Copy> InstancedMesh<VertexT> {
+    /// Creates a new instanced mesh with no instances.
+    ///
+    /// The geometry's transform and scissor apply to every instance.
+    pub fn new(
+        geometry: TrianglesMesh<VertexT>,
+        initial_capacity: usize,
+    ) -> Self {
+        Self {
+            geometry,
+            instances: Vec::with_capacity(initial_capacity),
+        }
+    }
+
+    /// Returns the geometry drawn for each instance.
+    pub fn geometry(&self) -> &TrianglesMesh<VertexT> {
+        &self.geometry
+    }
+
+    /// Returns the geometry drawn for each instance.
+    pub fn geometry_mut(&mut self) -> &mut TrianglesMesh<VertexT> {
+        &mut self.geometry
+    }
+
+    /// Adds an instance to the mesh.
+    pub fn push_instance(&mut self, instance: Instance) {
+        self.instances.push(instance);
+    }
+
+    /// Removes all instances while retaining the geometry and any allocated
+    /// memory.
+    pub fn clear_instances(&mut self) {
+        self.instances.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn instance_should_match_std430_layout() {
+        // mat4 + vec4 + int, rounded up to the 16 byte struct alignment
+        assert_eq!(std::mem::size_of::<Instance>(), 96);
+        assert_eq!(
+            Instance::new(Matrix4::identity(), [1.0; 4], -1),
+            Instance::IDENTITY
+        );
+    }
+}
@@ -1,8 +1,12 @@
 use {
-    crate::graphics::vulkan::{raii, VulkanContext},
+    super::{MaterialState, RenderTarget},
+    crate::{
+        graphics::vulkan::{raii, OwnedBlock, VulkanContext},
+        unwrap_here,
+    },
     anyhow::{Context, Result},
     ash::vk,
-    std::ffi::CStr,
+    std::{ffi::CStr, sync::Arc},
 };
 
 /// The shader entrypoint name, always defaults to 'main'.
@@ -10,10 +14,43 @@ const SHADER_ENTRYPOINT: &CStr = c"main";
 
 /// Materials are used to style mesh properties.
 ///
-/// Materials are immutable and can be shared by meshes.
+/// Materials are immutable and can be shared by meshes. Pipelines are created
+/// with a [RenderTarget::DEPTH_FORMAT] depth attachment, see [RenderTarget].
+/// Blending, depth, culling, and topology are configured with
+/// [MaterialState].
+///
+/// # Material Parameters
+///
+/// Materials created with [new_material_with_parameters] own a buffer of
+/// parameters, e.g. a noise scale or palette, which both shader stages can
+/// read through the `parameters` push constant at offset 40. The GLSL struct
+/// must have the same std430 layout as the Rust type. The address is 0 for
+/// materials without parameters.
+///
+/// [new_material_with_parameters]:
+///     super::StreamingRenderer::new_material_with_parameters
+///
+/// ```glsl
+/// struct Parameters {
+///     vec4 tint;
+///     float noise_scale;
+/// };
+///
+/// layout(buffer_reference, std430) readonly buffer ParametersBuffer {
+///     Parameters data;
+/// };
+/// layout(push_constant) uniform constants {
+///     layout(offset = 40) ParametersBuffer parameters;
+/// } pc_Constants;
+/// ```
 ///
 /// # Vertex Shader
 ///
+/// The default vertex shader reads [Vertex](super::Vertex). Renderers with a
+/// custom vertex type declare a matching `Vertex` struct, which must have the
+/// same std430 layout as the Rust type, and can read any attributes they
+/// need. The push constants are the same for every vertex type.
+///
 /// ```glsl
 /// #version 460
 /// #pragma shader_stage(vertex)
@@ -38,6 +75,12 @@ const SHADER_ENTRYPOINT: &CStr = c"main";
 ///     mat4 transform;
 /// };
 ///
+/// struct Instance {
+///     mat4 transform;
+///     vec4 color;
+///     int texture_index;
+/// };
+///
 /// // textures bound to set 0
 /// layout(set = 0, binding = 0) uniform sampler u_Sampler;
 /// layout(set = 0, binding = 1) uniform texture2D u_Textures[];
@@ -54,10 +97,22 @@ const SHADER_ENTRYPOINT: &CStr = c"main";
 /// layout(buffer_reference, std430) readonly buffer TransformBuffer {
 ///     MeshTransform data[];
 /// };
+/// layout(buffer_reference, std430) readonly buffer InstanceBuffer {
+///     Instance data[];
+/// };
+/// layout(buffer_reference, std430) readonly buffer TransformIndexBuffer {
+///     uint32_t data[];
+/// };
 /// layout(push_constant) uniform constants {
 ///     VertexBuffer vertices;
 ///     TransformBuffer mesh_transforms;
+///
+///     // Only set for direct draws, prefer instance_transform_indices which is
+///     // valid for both direct and indirect draws.
 ///     uint32_t transform_index;
+///
+///     InstanceBuffer instances;
+///     TransformIndexBuffer instance_transform_indices;
 /// } pc_Constants;
 ///
 /// // Per-Vertex outputs
@@ -67,14 +122,20 @@ const SHADER_ENTRYPOINT: &CStr = c"main";
 ///
 /// void main() {
 ///     Vertex vert = pc_Constants.vertices.data[gl_VertexIndex];
-///     out_VertexColor = vert.color;
-///     out_TextureIndex = vert.texture_index;
+///     Instance instance = pc_Constants.instances.data[gl_InstanceIndex];
+///     out_VertexColor = vert.color * instance.color;
+///     out_TextureIndex = instance.texture_index >= 0
+///         ? instance.texture_index
+///         : vert.texture_index;
 ///     out_UV = vec2(vert.uv_x, vert.uv_y);
 ///
+///     uint32_t transform_index =
+///         pc_Constants.instance_transform_indices.data[gl_InstanceIndex];
 ///     mat4 transform =
-///         pc_Constants.mesh_transforms.data[pc_Constants.transform_index].transform;
+///         pc_Constants.mesh_transforms.data[transform_index].transform
+///         * instance.transform;
 ///
-///     gl_Position = transform * vec4(vert.pos.x, vert.pos.y, 0.0, 1.0);
+///     gl_Position = transform * vec4(vert.pos.x, vert.pos.y, vert.pos.z, 1.0);
 /// }
 /// ```
 ///
@@ -115,10 +176,23 @@ const SHADER_ENTRYPOINT: &CStr = c"main";
 /// ```
 #[derive(Debug)]
 pub struct Material {
-    pipeline: raii::Pipeline,
+    pipeline: Arc<raii::Pipeline>,
+    state: MaterialState,
+    parameters: Option<ParameterBuffer>,
+}
+
+/// A host-visible buffer holding a material's parameters.
+#[derive(Debug)]
+struct ParameterBuffer {
+    _buffer: raii::Buffer,
+    _block: OwnedBlock,
+    address: vk::DeviceAddress,
 }
 
 impl Material {
+    /// The push constant offset of the material parameters address.
+    pub(super) const PARAMETERS_OFFSET: u32 = 40;
+
     /// Builds a pipeline layout for use with Material pipelines.
     pub(super) fn create_pipeline_layout(
         ctx: &VulkanContext,
@@ -129,11 +203,21 @@ impl Material {
             texture_atlas_descriptor_set_layout.raw,
             frame_constants_descriptor_set_layout.raw,
         ];
-        let push_constant_ranges = [vk::PushConstantRange {
-            stage_flags: vk::ShaderStageFlags::VERTEX,
-            offset: 0,
-            size: 8 + 8 + 4,
-        }];
+        let push_constant_ranges = [
+            vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::VERTEX,
+                offset: 0,
+                // vertices, transforms, transform index + padding, instances,
+                // instance transform indices, material parameters
+                size: 8 + 8 + 8 + 8 + 8 + 8,
+            },
+            vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::FRAGMENT,
+                offset: Self::PARAMETERS_OFFSET,
+                // material parameters
+                size: 8,
+            },
+        ];
         raii::PipelineLayout::new(
             "FirstTriangle",
             ctx.device.clone(),
@@ -148,9 +232,14 @@ impl Material {
     }
 
     /// Creates a new material for use when rendering meshes.
+    ///
+    /// `samples` must match the sample count of the color and depth
+    /// attachments the material renders to.
     pub(super) fn new(
         ctx: &VulkanContext,
         image_format: vk::Format,
+        samples: vk::SampleCountFlags,
+        state: MaterialState,
         pipeline_layout: &raii::PipelineLayout,
         vertex_shader_module: &raii::ShaderModule,
         fragment_shader_module: &raii::ShaderModule,
@@ -176,7 +265,7 @@ impl Material {
             ..Default::default()
         };
         let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo {
-            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            topology: state.topology,
             primitive_restart_enable: vk::FALSE,
             ..Default::default()
         };
@@ -187,9 +276,9 @@ impl Material {
         let rasterization_state = vk::PipelineRasterizationStateCreateInfo {
             depth_clamp_enable: vk::FALSE,
             rasterizer_discard_enable: vk::FALSE,
-            polygon_mode: vk::PolygonMode::FILL,
-            cull_mode: vk::CullModeFlags::NONE,
-            front_face: vk::FrontFace::CLOCKWISE,
+            polygon_mode: state.polygon_mode,
+            cull_mode: state.cull_mode,
+            front_face: state.front_face,
             depth_bias_enable: vk::FALSE,
             depth_bias_constant_factor: 0.0,
             depth_bias_clamp: 0.0,
@@ -198,7 +287,7 @@ impl Material {
             ..Default::default()
         };
         let multisample_state = vk::PipelineMultisampleStateCreateInfo {
-            rasterization_samples: vk::SampleCountFlags::TYPE_1,
+            rasterization_samples: samples,
             sample_shading_enable: vk::FALSE,
             min_sample_shading: 1.0,
             p_sample_mask: std::ptr::null(),
@@ -207,26 +296,16 @@ impl Material {
             ..Default::default()
         };
         let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo {
-            depth_test_enable: vk::TRUE,
-            depth_write_enable: vk::TRUE,
-            depth_compare_op: vk::CompareOp::LESS,
+            depth_test_enable: state.depth_test.into(),
+            depth_write_enable: state.depth_write.into(),
+            depth_compare_op: state.depth_compare_op,
             depth_bounds_test_enable: vk::FALSE,
             stencil_test_enable: vk::FALSE,
             min_depth_bounds: 0.0,
             max_depth_bounds: 1.0,
             ..Default::default()
         };
-        let color_blend_statetachment_state =
-            vk::PipelineColorBlendAttachmentState {
-                blend_enable: vk::TRUE,
-                src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
-                dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
-                color_blend_op: vk::BlendOp::ADD,
-                src_alpha_blend_factor: vk::BlendFactor::ONE,
-                dst_alpha_blend_factor: vk::BlendFactor::ZERO,
-                alpha_blend_op: vk::BlendOp::ADD,
-                color_write_mask: vk::ColorComponentFlags::RGBA,
-            };
+        let color_blend_statetachment_state = state.blend.attachment_state();
         let color_blend_state = vk::PipelineColorBlendStateCreateInfo {
             logic_op_enable: vk::FALSE,
             logic_op: vk::LogicOp::COPY,
@@ -255,7 +334,7 @@ impl Material {
             view_mask: 0,
             color_attachment_count: 1,
             p_color_attachment_formats: color_attachment_formats.as_ptr(),
-            depth_attachment_format: vk::Format::D32_SFLOAT,
+            depth_attachment_format: RenderTarget::DEPTH_FORMAT,
             ..Default::default()
         };
 
@@ -286,11 +365,77 @@ impl Material {
         )
         .context("Unable to create pipeline!")?;
 
-        Ok(Self { pipeline })
+        Ok(Self {
+            pipeline: Arc::new(pipeline),
+            state,
+            parameters: None,
+        })
+    }
+
+    /// Creates a material which shares this material's pipeline and owns a
+    /// buffer containing `parameters`.
+    pub(super) fn with_parameters<ParametersT: Copy>(
+        &self,
+        ctx: &VulkanContext,
+        parameters: ParametersT,
+    ) -> Result<Self> {
+        let (block, buffer) = unwrap_here!(
+            "Allocate material parameter buffer",
+            OwnedBlock::allocate_buffer(
+                ctx.allocator.clone(),
+                &vk::BufferCreateInfo {
+                    // Vulkan buffers cannot be empty
+                    size: size_of::<ParametersT>().max(1) as u64,
+                    usage: vk::BufferUsageFlags::STORAGE_BUFFER
+                        | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+                    sharing_mode: vk::SharingMode::EXCLUSIVE,
+                    queue_family_index_count: 1,
+                    p_queue_family_indices: &ctx.graphics_queue_family_index,
+                    ..Default::default()
+                },
+                vk::MemoryPropertyFlags::HOST_VISIBLE
+                    | vk::MemoryPropertyFlags::HOST_COHERENT,
+            )
+        );
+
+        // SAFETY: the buffer is new, so the GPU cannot be reading it
+        let address = unsafe {
+            std::ptr::write_unaligned(
+                block.mapped_ptr() as *mut ParametersT,
+                parameters,
+            );
+            ctx.get_buffer_device_address(&vk::BufferDeviceAddressInfo {
+                buffer: buffer.raw,
+                ..Default::default()
+            })
+        };
+
+        Ok(Self {
+            pipeline: self.pipeline.clone(),
+            state: self.state,
+            parameters: Some(ParameterBuffer {
+                _buffer: buffer,
+                _block: block,
+                address,
+            }),
+        })
+    }
+
+    /// Returns the device address of the material's parameters, or 0 if the
+    /// material has no parameters.
+    pub fn parameters_address(&self) -> vk::DeviceAddress {
+        self.parameters
+            .as_ref()
+            .map_or(0, |parameters| parameters.address)
     }
 
     /// Returns the pipeline used by this material.
     pub fn pipeline(&self) -> &raii::Pipeline {
         &self.pipeline
     }
+
+    /// Returns the fixed-function state the material was created with.
+    pub fn state(&self) -> &MaterialState {
+        &self.state
+    }
 }
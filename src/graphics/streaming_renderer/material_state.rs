@@ -0,0 +1,137 @@
+use {ash::vk, bon::Builder};
+
+/// How a material's output color is combined with the color already in the
+/// target.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// The output replaces the target.
+    Opaque,
+
+    /// Straight (non-premultiplied) alpha blending.
+    #[default]
+    Alpha,
+
+    /// Alpha blending for colors which are already multiplied by alpha.
+    Premultiplied,
+
+    /// The output, scaled by alpha, is added to the target.
+    Additive,
+
+    /// The target is multiplied by the output.
+    Multiply,
+
+    /// The inverse of multiplying the inverted output and target. This
+    /// brightens like additive blending but never exceeds 1.0.
+    Screen,
+
+    /// Keeps the per-channel minimum of the output and target.
+    Min,
+
+    /// Keeps the per-channel maximum of the output and target.
+    Max,
+}
+
+impl BlendMode {
+    /// Returns the color blend attachment state for this blend mode.
+    pub fn attachment_state(self) -> vk::PipelineColorBlendAttachmentState {
+        use vk::{BlendFactor as F, BlendOp as Op};
+        let (color, alpha) = match self {
+            Self::Opaque => {
+                return vk::PipelineColorBlendAttachmentState {
+                    blend_enable: vk::FALSE,
+                    color_write_mask: vk::ColorComponentFlags::RGBA,
+                    ..Default::default()
+                };
+            }
+            Self::Alpha => (
+                (F::SRC_ALPHA, F::ONE_MINUS_SRC_ALPHA, Op::ADD),
+                (F::ONE, F::ZERO, Op::ADD),
+            ),
+            Self::Premultiplied => (
+                (F::ONE, F::ONE_MINUS_SRC_ALPHA, Op::ADD),
+                (F::ONE, F::ONE_MINUS_SRC_ALPHA, Op::ADD),
+            ),
+            Self::Additive => {
+                ((F::SRC_ALPHA, F::ONE, Op::ADD), (F::ONE, F::ONE, Op::ADD))
+            }
+            Self::Multiply => (
+                (F::DST_COLOR, F::ZERO, Op::ADD),
+                (F::DST_ALPHA, F::ZERO, Op::ADD),
+            ),
+            Self::Screen => (
+                (F::ONE, F::ONE_MINUS_SRC_COLOR, Op::ADD),
+                (F::ONE, F::ONE_MINUS_SRC_ALPHA, Op::ADD),
+            ),
+            // blend factors are ignored by the MIN and MAX ops
+            Self::Min => ((F::ONE, F::ONE, Op::MIN), (F::ONE, F::ONE, Op::MIN)),
+            Self::Max => ((F::ONE, F::ONE, Op::MAX), (F::ONE, F::ONE, Op::MAX)),
+        };
+        vk::PipelineColorBlendAttachmentState {
+            blend_enable: vk::TRUE,
+            src_color_blend_factor: color.0,
+            dst_color_blend_factor: color.1,
+            color_blend_op: color.2,
+            src_alpha_blend_factor: alpha.0,
+            dst_alpha_blend_factor: alpha.1,
+            alpha_blend_op: alpha.2,
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+        }
+    }
+}
+
+/// The fixed-function pipeline state for a [Material](super::Material).
+///
+/// Materials with the same shaders and state share a single pipeline. See
+/// [StreamingRenderer::new_material_with_state](super::StreamingRenderer::new_material_with_state).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Builder)]
+pub struct MaterialState {
+    #[builder(default)]
+    pub blend: BlendMode,
+
+    #[builder(default = true)]
+    pub depth_test: bool,
+
+    #[builder(default = true)]
+    pub depth_write: bool,
+
+    #[builder(default = vk::CompareOp::LESS)]
+    pub depth_compare_op: vk::CompareOp,
+
+    #[builder(default = vk::CullModeFlags::NONE)]
+    pub cull_mode: vk::CullModeFlags,
+
+    #[builder(default = vk::FrontFace::CLOCKWISE)]
+    pub front_face: vk::FrontFace,
+
+    /// LINE and POINT modes require the `fill_mode_non_solid` device feature,
+    /// otherwise creating the material fails.
+    #[builder(default = vk::PolygonMode::FILL)]
+    pub polygon_mode: vk::PolygonMode,
+
+    /// Point topologies require the vertex shader to write `gl_PointSize`.
+    /// Mesh indices are interpreted according to the topology.
+    #[builder(default = vk::PrimitiveTopology::TRIANGLE_LIST)]
+    pub topology: vk::PrimitiveTopology,
+}
+
+impl Default for MaterialState {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn opaque_materials_should_not_blend() {
+        assert_eq!(
+            BlendMode::Opaque.attachment_state().blend_enable,
+            vk::FALSE
+        );
+        let max = BlendMode::Max.attachment_state();
+        assert_eq!(max.blend_enable, vk::TRUE);
+        assert_eq!(max.color_blend_op, vk::BlendOp::MAX);
+    }
+}
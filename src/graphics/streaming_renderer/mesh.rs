@@ -1,10 +1,13 @@
 use {
-    super::Material,
+    super::{Bounds, Instance, Material, StaticGeometry},
     ash::vk,
     nalgebra::{Matrix4, Vector3},
     std::sync::Arc,
 };
 
+/// The vertex format used by the default shaders.
+///
+/// Meshes and renderers can use any other `Copy` vertex type, see [Mesh].
 #[repr(C, align(16))]
 #[derive(Debug, Copy, Clone)]
 pub struct Vertex {
@@ -35,26 +38,57 @@ impl Vertex {
 /// A Mesh is the minimal unit of rendering.
 ///
 /// Data is streamed from the CPU to the GPU each frame from each Mesh.
-pub trait Mesh {
-    fn vertices(&self) -> &[Vertex];
+///
+/// Vertices are copied into the renderer's vertex buffer as-is, so
+/// `VertexT` must match the std430 layout of the vertex struct read by the
+/// material's vertex shader, see [Material].
+pub trait Mesh<VertexT = Vertex> {
+    fn vertices(&self) -> &[VertexT];
     fn indices(&self) -> &[u32];
     fn material(&self) -> &Arc<Material>;
     fn transform(&self) -> &Matrix4<f32>;
     fn scissor(&self) -> vk::Rect2D;
+
+    /// The instances to draw, see [InstancedMesh](super::InstancedMesh).
+    ///
+    /// Meshes are drawn once with [Instance::IDENTITY] by default.
+    fn instances(&self) -> &[Instance] {
+        std::slice::from_ref(&Instance::IDENTITY)
+    }
+
+    /// Device-local geometry drawn instead of the streamed vertices and
+    /// indices, see [StaticMesh](super::StaticMesh).
+    fn static_geometry(&self) -> Option<&Arc<StaticGeometry>> {
+        None
+    }
+
+    /// Meshes in lower layers are drawn first when the renderer uses
+    /// [DrawOrder::Sorted](super::DrawOrder::Sorted).
+    fn layer(&self) -> i32 {
+        0
+    }
+
+    /// Mesh-space bounds used to cull the mesh on the GPU. Bounds must
+    /// contain every instance. Meshes without bounds are never culled.
+    fn bounds(&self) -> Option<Bounds> {
+        None
+    }
 }
 
 /// This mesh supports drawing arbitrary triangles and quads in three
 /// dimensions.
-pub struct TrianglesMesh {
-    vertices: Vec<Vertex>,
+pub struct TrianglesMesh<VertexT: Copy = Vertex> {
+    vertices: Vec<VertexT>,
     indices: Vec<u32>,
     material: Arc<Material>,
     transform: Matrix4<f32>,
     scissor: vk::Rect2D,
+    layer: i32,
+    bounds: Option<Bounds>,
 }
 
-impl Mesh for TrianglesMesh {
-    fn vertices(&self) -> &[Vertex] {
+impl<VertexT: Copy> Mesh<VertexT> for TrianglesMesh<VertexT> {
+    fn vertices(&self) -> &[VertexT] {
         &self.vertices
     }
 
@@ -73,9 +107,17 @@ impl Mesh for TrianglesMesh {
     fn scissor(&self) -> vk::Rect2D {
         self.scissor
     }
+
+    fn layer(&self) -> i32 {
+        self.layer
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
 }
 
-impl TrianglesMesh {
+impl<VertexT: Copy> TrianglesMesh<VertexT> {
     /// Creates a new empty Mesh with pre-allocated internal memory for
     /// vertex data.
     pub fn new(initial_capacity: usize, material: Arc<Material>) -> Self {
@@ -91,6 +133,8 @@ impl TrianglesMesh {
                     height: 1,
                 },
             },
+            layer: 0,
+            bounds: None,
         }
     }
 
@@ -98,6 +142,19 @@ impl TrianglesMesh {
         self.scissor = rect;
     }
 
+    /// Sets the layer used when sorting draws, see [Mesh::layer].
+    pub fn set_layer(&mut self, layer: i32) {
+        self.layer = layer;
+    }
+
+    /// Sets the bounds used for GPU culling, see [Mesh::bounds].
+    ///
+    /// Bounds are not updated as geometry changes, use
+    /// [Bounds::from_vertices] to compute them.
+    pub fn set_bounds(&mut self, bounds: Option<Bounds>) {
+        self.bounds = bounds;
+    }
+
     /// Set the matrix transformation matrix.
     pub fn set_transform(&mut self, projection: Matrix4<f32>) {
         self.transform = projection;
@@ -111,14 +168,16 @@ impl TrianglesMesh {
 
     pub fn indexed_triangles<V, I>(&mut self, vertices: V, indices: I)
     where
-        V: Iterator<Item = Vertex>,
+        V: Iterator<Item = VertexT>,
         I: Iterator<Item = u32>,
     {
         let base_index = self.vertices.len() as u32;
         self.vertices.extend(vertices);
         self.indices.extend(indices.map(|index| base_index + index));
     }
+}
 
+impl TrianglesMesh {
     /// Adds a triangle to the mesh.
     ///
     /// Note: triangles must be in clockwise winding order, else they will be
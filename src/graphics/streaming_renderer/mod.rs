@@ -1,27 +1,50 @@
 //! Mesh, Material, Texture, and pipeline resources for rendering geometry
 //! that's expected to change every frame.
 
+mod culling;
+mod draw_order;
 mod dynamic_buffer;
 mod frame_constants;
+mod instanced_mesh;
 mod material;
+mod material_state;
 mod mesh;
+mod render_target;
+mod static_mesh;
 mod texture;
 pub(crate) mod utility;
 
 use {
-    self::frame_constants::FrameConstants,
-    crate::graphics::vulkan::{
-        raii, spirv_words, Frame, FramesInFlight, VulkanContext,
+    self::{
+        culling::{CullInput, GpuCulling},
+        draw_order::SortKey,
+        frame_constants::FrameConstants,
     },
-    anyhow::{Context, Result},
-    ash::vk,
+    crate::graphics::{
+        vulkan::{
+            raii, spirv_words, Frame, FramesInFlight, GpuScope,
+            PipelineStatistics, ShaderInvocations, VulkanContext,
+        },
+        Barriers, BufferUsage,
+    },
+    anyhow::{bail, Context, Result},
+    ash::vk::{self, Handle},
     dynamic_buffer::DynamicBuffer,
-    material::Material,
-    std::sync::Arc,
+    std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    },
 };
 
 pub use self::{
+    culling::Bounds,
+    draw_order::DrawOrder,
+    instanced_mesh::{Instance, InstancedMesh},
+    material::Material,
+    material_state::{BlendMode, MaterialState},
     mesh::{Mesh, TrianglesMesh, Vertex},
+    render_target::RenderTarget,
+    static_mesh::{StaticGeometry, StaticMesh},
     texture::{Texture, TextureAtlas, TextureLoader},
 };
 
@@ -32,9 +55,71 @@ struct DrawParams {
     index_offset: u32,
     vertex_offset: u32,
     index_count: u32,
+    instance_offset: u32,
+    instance_count: u32,
     material: Arc<Material>,
     transform_index: u32,
     scissor: vk::Rect2D,
+
+    /// Drawn instead of the frame's streamed vertices and indices when set.
+    static_geometry: Option<Arc<StaticGeometry>>,
+
+    sort_key: SortKey,
+    bounds: Option<Bounds>,
+}
+
+/// A vertex buffer address and index buffer bound together.
+type Geometry = (vk::DeviceAddress, vk::Buffer);
+
+impl DrawParams {
+    /// Returns the geometry to bind for the draw along with the first index
+    /// and vertex offset within it.
+    fn geometry(&self, streamed: Geometry) -> (Geometry, u32, u32) {
+        match &self.static_geometry {
+            Some(geometry) => (
+                (geometry.vertex_buffer_address(), geometry.index_buffer()),
+                0,
+                0,
+            ),
+            None => (streamed, self.index_offset, self.vertex_offset),
+        }
+    }
+
+    /// Returns true when both draws can share a single indirect draw call.
+    fn shares_state_with(&self, other: &Self, streamed: Geometry) -> bool {
+        let scissor = |draw: &Self| {
+            let vk::Rect2D { offset, extent } = draw.scissor;
+            (offset.x, offset.y, extent.width, extent.height)
+        };
+        self.material.pipeline().raw == other.material.pipeline().raw
+            && self.material.parameters_address()
+                == other.material.parameters_address()
+            && scissor(self) == scissor(other)
+            && self.geometry(streamed).0 == other.geometry(streamed).0
+    }
+}
+
+/// The state most recently bound while writing draw commands.
+#[derive(Default)]
+struct BoundState {
+    pipeline: vk::Pipeline,
+    geometry: Geometry,
+
+    /// None until the first material's parameters are pushed.
+    parameters: Option<vk::DeviceAddress>,
+}
+
+/// Materials are cached by their shader modules and state.
+type MaterialKey = (vk::ShaderModule, vk::ShaderModule, MaterialState);
+
+/// A cached material along with the shader modules its key refers to.
+///
+/// Holding the modules keeps their handles from being destroyed and reused by
+/// the driver for a different module while the cache entry exists.
+struct CachedMaterial {
+    material: Arc<Material>,
+    _vertex_shader: Arc<raii::ShaderModule>,
+    _fragment_shader: Arc<raii::ShaderModule>,
 }
 
 #[repr(C, align(16))]
@@ -43,18 +128,79 @@ struct MeshTransform {
     matrix: [[f32; 4]; 4],
 }
 
+/// Identifies a group of meshes prepared with
+/// [StreamingRenderer::prepare_meshes].
+///
+/// Batches are only valid for the frame they were prepared for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Batch {
+    frame_value: u64,
+    index: usize,
+}
+
+/// The buffers and draw parameters for a single batch.
+///
+/// Buffer handles are captured when the batch is prepared because later
+/// batches can move the frame's buffers.
+struct BatchDraw {
+    vertex_buffer_address: vk::DeviceAddress,
+    index_buffer: vk::Buffer,
+    transforms_address: vk::DeviceAddress,
+    instances_address: vk::DeviceAddress,
+    instance_transform_indices_address: vk::DeviceAddress,
+    draw_params: Vec<DrawParams>,
+
+    /// Set once the batch has been culled and is drawn indirectly.
+    indirect: Option<IndirectDraws>,
+}
+
+/// Consecutive draws which share a pipeline, scissor, and geometry and are
+/// drawn with a single indirect draw call.
+struct IndirectRun {
+    /// The draw params used to bind state for the run.
+    first_draw: usize,
+    max_draw_count: u32,
+}
+
+/// The indirect commands written by the culling pass for a batch.
+///
+/// Each run's commands are stored contiguously, starting at
+/// `commands_offset`, and each run has a draw count, starting at
+/// `counts_offset`.
+struct IndirectDraws {
+    commands_buffer: vk::Buffer,
+    commands_offset: vk::DeviceSize,
+    counts_buffer: vk::Buffer,
+    counts_offset: vk::DeviceSize,
+    runs: Vec<IndirectRun>,
+}
+
 /// All of the resources required to assemble draw commands for a frame.
 ///
 /// The renderer keeps one instance per frame-in-flight so resources can be
-/// updated freely while constructing the frame.
-struct FrameDraw {
-    vertex_buffer: DynamicBuffer<Vertex>,
+/// updated freely while constructing the frame. Each batch is appended to the
+/// frame's buffers.
+struct FrameDraw<VertexT: Copy> {
+    vertex_buffer: DynamicBuffer<VertexT>,
     index_buffer: DynamicBuffer<u32>,
     transforms: DynamicBuffer<MeshTransform>,
-    draw_params: Vec<DrawParams>,
+    instances: DynamicBuffer<Instance>,
+
+    /// The mesh transform index for every instance. Indirect draws can't
+    /// push a transform index per draw so shaders index this buffer with
+    /// `gl_InstanceIndex` instead.
+    instance_transform_indices: DynamicBuffer<u32>,
+
+    cull_inputs: DynamicBuffer<CullInput>,
+    indirect_commands: DynamicBuffer<vk::DrawIndexedIndirectCommand>,
+    indirect_counts: DynamicBuffer<u32>,
+
+    /// The frame value the batches were prepared for.
+    frame_value: u64,
+    batches: Vec<BatchDraw>,
 }
 
-impl FrameDraw {
+impl<VertexT: Copy> FrameDraw<VertexT> {
     pub fn new(ctx: &VulkanContext) -> Result<Self> {
         Ok(Self {
             vertex_buffer: DynamicBuffer::new(
@@ -74,30 +220,178 @@ impl FrameDraw {
                 vk::BufferUsageFlags::STORAGE_BUFFER
                     | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
             )?,
-            draw_params: Vec::with_capacity(4),
+            instances: DynamicBuffer::new(
+                ctx,
+                INITIAL_CAPACITY,
+                vk::BufferUsageFlags::STORAGE_BUFFER
+                    | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            )?,
+            instance_transform_indices: DynamicBuffer::new(
+                ctx,
+                INITIAL_CAPACITY,
+                vk::BufferUsageFlags::STORAGE_BUFFER
+                    | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            )?,
+            cull_inputs: DynamicBuffer::new(
+                ctx,
+                INITIAL_CAPACITY,
+                vk::BufferUsageFlags::STORAGE_BUFFER
+                    | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            )?,
+            indirect_commands: DynamicBuffer::new(
+                ctx,
+                INITIAL_CAPACITY,
+                vk::BufferUsageFlags::STORAGE_BUFFER
+                    | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
+                    | vk::BufferUsageFlags::INDIRECT_BUFFER,
+            )?,
+            indirect_counts: DynamicBuffer::new(
+                ctx,
+                INITIAL_CAPACITY,
+                vk::BufferUsageFlags::STORAGE_BUFFER
+                    | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
+                    | vk::BufferUsageFlags::INDIRECT_BUFFER,
+            )?,
+            frame_value: 0,
+            batches: Vec::with_capacity(4),
         })
     }
+
+    /// Fails if the batch wasn't prepared for the frame.
+    fn check_batch(&self, frame: &Frame, batch: Batch) -> Result<()> {
+        if batch.frame_value != frame.frame_value()
+            || self.frame_value != frame.frame_value()
+        {
+            bail!(
+                "Batch {:?} was not prepared for frame {}",
+                batch,
+                frame.frame_value()
+            );
+        }
+        Ok(())
+    }
+
+    /// Starts a new frame if `frame` differs from the frame the existing
+    /// batches were prepared for.
+    fn begin_frame(&mut self, frame: &Frame) {
+        if self.frame_value == frame.frame_value() {
+            return;
+        }
+        self.frame_value = frame.frame_value();
+        self.batches.clear();
+
+        // SAFETY: the previous submission for this frame index has completed
+        // before the frame was started
+        unsafe {
+            self.vertex_buffer.reset();
+            self.index_buffer.reset();
+            self.transforms.reset();
+            self.instances.reset();
+            self.instance_transform_indices.reset();
+            self.cull_inputs.reset();
+            self.indirect_commands.reset();
+            self.indirect_counts.reset();
+        }
+    }
+}
+
+/// Pipeline statistics recorded around each run of draws which share a
+/// pipeline.
+struct RunStatistics {
+    queries: PipelineStatistics,
+
+    /// The frame the queries were most recently reset for. Scopes are only
+    /// recorded in that frame.
+    frame_value: Option<u64>,
+}
+
+impl RunStatistics {
+    /// Ends the current scope and begins a new one if `pipeline` starts a new
+    /// run of draws.
+    fn next_draw(
+        &mut self,
+        frame: &Frame,
+        pipeline: vk::Pipeline,
+        bound: &BoundState,
+        scope: &mut Option<GpuScope>,
+    ) {
+        if pipeline == bound.pipeline
+            || self.frame_value != Some(frame.frame_value())
+        {
+            return;
+        }
+        self.end(frame, scope.take());
+        *scope = Some(
+            self.queries
+                .begin(frame, format!("pipeline {:#x}", pipeline.as_raw())),
+        );
+    }
+
+    /// Ends the current scope, if any.
+    fn end(&mut self, frame: &Frame, scope: Option<GpuScope>) {
+        if let Some(scope) = scope {
+            self.queries.end(frame, scope);
+        }
+    }
 }
 
 /// A renderer optimized for streaming new vertex data to the GPU every frame.
-pub struct StreamingRenderer<PerFrameDataT: Copy = ()> {
-    frame_draw_resources: Vec<FrameDraw>,
+///
+/// Draw commands are recorded within dynamic rendering that has a
+/// [RenderTarget::DEPTH_FORMAT] depth attachment, e.g. between
+/// [RenderTarget::begin_rendering] and [RenderTarget::end_rendering].
+///
+/// Meshes are drawn with vertices of type `VertexT`. The vertex buffer
+/// address push constant refers to an array of `VertexT`, so its stride is
+/// `size_of::<VertexT>()`, and shaders index it with `gl_VertexIndex` which
+/// includes each draw's vertex offset. The default shaders read [Vertex], so
+/// renderers with any other vertex type must create materials with a custom
+/// vertex shader.
+pub struct StreamingRenderer<PerFrameDataT: Copy = (), VertexT: Copy = Vertex> {
+    frame_draw_resources: Vec<FrameDraw<VertexT>>,
     frame_constants: FrameConstants<PerFrameDataT>,
 
     pipeline_layout: raii::PipelineLayout,
 
-    default_vertex_shader_module: raii::ShaderModule,
-    default_fragment_shader_module: raii::ShaderModule,
+    default_vertex_shader_module: Arc<raii::ShaderModule>,
+    default_fragment_shader_module: Arc<raii::ShaderModule>,
     default_material: Arc<Material>,
+    materials: Mutex<HashMap<MaterialKey, CachedMaterial>>,
     image_format: vk::Format,
+    samples: vk::SampleCountFlags,
+    draw_order: DrawOrder,
+    culling: GpuCulling,
+    statistics: Option<RunStatistics>,
 }
 
-impl<PerFrameDataT: Copy> StreamingRenderer<PerFrameDataT> {
+impl<PerFrameDataT: Copy, VertexT: Copy>
+    StreamingRenderer<PerFrameDataT, VertexT>
+{
     pub fn new(
         ctx: &VulkanContext,
         image_format: vk::Format,
         frames_in_flight: &FramesInFlight,
         texture_atlas: &TextureAtlas,
+    ) -> Result<Self> {
+        Self::new_with_samples(
+            ctx,
+            image_format,
+            vk::SampleCountFlags::TYPE_1,
+            frames_in_flight,
+            texture_atlas,
+        )
+    }
+
+    /// Creates a renderer whose materials render with multisampling.
+    ///
+    /// Draws must target attachments with the same sample count, e.g. a
+    /// [RenderTarget] created with [RenderTarget::new_multisampled].
+    pub fn new_with_samples(
+        ctx: &VulkanContext,
+        image_format: vk::Format,
+        samples: vk::SampleCountFlags,
+        frames_in_flight: &FramesInFlight,
+        texture_atlas: &TextureAtlas,
     ) -> Result<Self> {
         let frame_constants = FrameConstants::new(ctx, frames_in_flight)
             .context("Unable to create FrameData instance")?;
@@ -126,36 +420,44 @@ impl<PerFrameDataT: Copy> StreamingRenderer<PerFrameDataT> {
             let vertex_shader_words =
                 spirv_words(include_bytes!("./shaders/triangle.vert.spv"))
                     .context("Unable to pack default vertex shader source")?;
-            raii::ShaderModule::new(
-                "DefaultVertexShader",
-                ctx.device.clone(),
-                &vk::ShaderModuleCreateInfo {
-                    code_size: vertex_shader_words.len() * 4,
-                    p_code: vertex_shader_words.as_ptr(),
-                    ..Default::default()
-                },
+            Arc::new(
+                raii::ShaderModule::new(
+                    "DefaultVertexShader",
+                    ctx.device.clone(),
+                    &vk::ShaderModuleCreateInfo {
+                        code_size: vertex_shader_words.len() * 4,
+                        p_code: vertex_shader_words.as_ptr(),
+                        ..Default::default()
+                    },
+                )
+                .context("Unable to create default vertex shader module")?,
             )
-            .context("Unable to create default vertex shader module")?
         };
         let default_fragment_shader_module = {
             let fragment_shader_words =
                 spirv_words(include_bytes!("./shaders/triangle.frag.spv"))
                     .context("Unable to pack default fragment shader source")?;
-            raii::ShaderModule::new(
-                "DefaultFragmentShader",
-                ctx.device.clone(),
-                &vk::ShaderModuleCreateInfo {
-                    code_size: fragment_shader_words.len() * 4,
-                    p_code: fragment_shader_words.as_ptr(),
-                    ..Default::default()
-                },
+            Arc::new(
+                raii::ShaderModule::new(
+                    "DefaultFragmentShader",
+                    ctx.device.clone(),
+                    &vk::ShaderModuleCreateInfo {
+                        code_size: fragment_shader_words.len() * 4,
+                        p_code: fragment_shader_words.as_ptr(),
+                        ..Default::default()
+                    },
+                )
+                .context("Unable to create default fragment shader module")?,
             )
-            .context("Unable to create default fragment shader module")?
         };
+        let culling = GpuCulling::new(ctx)
+            .context("Unable to create culling pipeline")?;
         let default_material = Arc::new(
             Material::new(
                 ctx,
                 image_format,
+                samples,
+                MaterialState::default(),
                 &pipeline_layout,
                 &default_vertex_shader_module,
                 &default_fragment_shader_module,
@@ -168,10 +470,26 @@ impl<PerFrameDataT: Copy> StreamingRenderer<PerFrameDataT> {
             frame_constants,
 
             pipeline_layout,
+            materials: Mutex::new(HashMap::from([(
+                (
+                    default_vertex_shader_module.raw,
+                    default_fragment_shader_module.raw,
+                    MaterialState::default(),
+                ),
+                CachedMaterial {
+                    material: default_material.clone(),
+                    _vertex_shader: default_vertex_shader_module.clone(),
+                    _fragment_shader: default_fragment_shader_module.clone(),
+                },
+            )])),
             default_vertex_shader_module,
             default_fragment_shader_module,
             default_material,
             image_format,
+            samples,
+            draw_order: DrawOrder::default(),
+            culling,
+            statistics: None,
         })
     }
 
@@ -183,18 +501,99 @@ impl<PerFrameDataT: Copy> StreamingRenderer<PerFrameDataT> {
     pub fn new_material(
         &self,
         ctx: &VulkanContext,
-        vertex_shader: Option<&raii::ShaderModule>,
-        fragment_shader: Option<&raii::ShaderModule>,
+        vertex_shader: Option<&Arc<raii::ShaderModule>>,
+        fragment_shader: Option<&Arc<raii::ShaderModule>>,
     ) -> Result<Arc<Material>> {
-        let material = Material::new(
+        self.new_material_with_state(
             ctx,
-            self.image_format,
-            &self.pipeline_layout,
-            vertex_shader.unwrap_or(&self.default_vertex_shader_module),
-            fragment_shader.unwrap_or(&self.default_fragment_shader_module),
+            vertex_shader,
+            fragment_shader,
+            MaterialState::default(),
         )
-        .context("Unable to create new material!")?;
-        Ok(Arc::new(material))
+    }
+
+    /// Returns a material with the given blend, depth, cull, and topology
+    /// state, e.g. `MaterialState::builder().blend(BlendMode::Additive)`.
+    ///
+    /// Each combination of shaders and state creates a single pipeline which
+    /// is reused by later calls. The cache keeps the shader modules alive, so
+    /// callers can drop their own references once the material is created.
+    ///
+    /// Fails if the state uses a LINE or POINT polygon mode and the device
+    /// was created without the `fill_mode_non_solid` feature.
+    pub fn new_material_with_state(
+        &self,
+        ctx: &VulkanContext,
+        vertex_shader: Option<&Arc<raii::ShaderModule>>,
+        fragment_shader: Option<&Arc<raii::ShaderModule>>,
+        state: MaterialState,
+    ) -> Result<Arc<Material>> {
+        if state.polygon_mode != vk::PolygonMode::FILL
+            && !ctx.fill_mode_non_solid_enabled()
+        {
+            bail!(
+                "{:?} polygon mode requires the fill_mode_non_solid device \
+                 feature",
+                state.polygon_mode
+            );
+        }
+        let vertex_shader =
+            vertex_shader.unwrap_or(&self.default_vertex_shader_module);
+        let fragment_shader =
+            fragment_shader.unwrap_or(&self.default_fragment_shader_module);
+        let mut materials = self.materials.lock().unwrap();
+        let key = (vertex_shader.raw, fragment_shader.raw, state);
+        if let Some(cached) = materials.get(&key) {
+            return Ok(cached.material.clone());
+        }
+        let material = Arc::new(
+            Material::new(
+                ctx,
+                self.image_format,
+                self.samples,
+                state,
+                &self.pipeline_layout,
+                vertex_shader,
+                fragment_shader,
+            )
+            .context("Unable to create new material!")?,
+        );
+        materials.insert(
+            key,
+            CachedMaterial {
+                material: material.clone(),
+                _vertex_shader: vertex_shader.clone(),
+                _fragment_shader: fragment_shader.clone(),
+            },
+        );
+        Ok(material)
+    }
+
+    /// Returns a material whose shaders can read `parameters`, e.g. a noise
+    /// scale or palette. See [Material] for the shader interface.
+    ///
+    /// The pipeline is shared with other materials that use the same shaders
+    /// and state, but every call allocates a new parameter buffer. Parameters
+    /// are written once and cannot change after the material is created.
+    pub fn new_material_with_parameters<ParametersT: Copy>(
+        &self,
+        ctx: &VulkanContext,
+        vertex_shader: Option<&Arc<raii::ShaderModule>>,
+        fragment_shader: Option<&Arc<raii::ShaderModule>>,
+        state: MaterialState,
+        parameters: ParametersT,
+    ) -> Result<Arc<Material>> {
+        let material = self.new_material_with_state(
+            ctx,
+            vertex_shader,
+            fragment_shader,
+            state,
+        )?;
+        Ok(Arc::new(
+            material
+                .with_parameters(ctx, parameters)
+                .context("Unable to create material parameters!")?,
+        ))
     }
 
     /// Returns the image format this renderer is compatible with.
@@ -202,73 +601,209 @@ impl<PerFrameDataT: Copy> StreamingRenderer<PerFrameDataT> {
         self.image_format
     }
 
+    /// Returns the sample count this renderer's materials render with.
+    pub fn samples(&self) -> vk::SampleCountFlags {
+        self.samples
+    }
+
+    /// Sets the order meshes are drawn in batches prepared after this call.
+    pub fn set_draw_order(&mut self, draw_order: DrawOrder) {
+        self.draw_order = draw_order;
+    }
+
+    /// Returns the order meshes are drawn in.
+    pub fn draw_order(&self) -> DrawOrder {
+        self.draw_order
+    }
+
+    /// Records pipeline statistics around each run of draws which share a
+    /// material's pipeline.
+    ///
+    /// Each frame must call [Self::read_pipeline_statistics] before drawing
+    /// any batches, otherwise that frame's draws are not recorded. Runs
+    /// beyond `max_runs_per_frame` are not recorded.
+    ///
+    /// Fails if the device was created without the
+    /// `pipeline_statistics_query` feature.
+    pub fn enable_pipeline_statistics(
+        &mut self,
+        ctx: Arc<VulkanContext>,
+        frames_in_flight: &FramesInFlight,
+        max_runs_per_frame: u32,
+    ) -> Result<()> {
+        let queries =
+            PipelineStatistics::new(ctx, frames_in_flight, max_runs_per_frame)
+                .context("Unable to create pipeline statistics queries")?;
+        self.statistics = Some(RunStatistics {
+            queries,
+            frame_value: None,
+        });
+        Ok(())
+    }
+
+    /// Reads back the statistics recorded the last time this frame index was
+    /// used and resets the frame's queries.
+    ///
+    /// Each run is named after its pipeline handle, so a name repeats when a
+    /// pipeline is drawn in more than one run. Returns nothing unless
+    /// statistics were enabled with [Self::enable_pipeline_statistics].
+    ///
+    /// This must be recorded once per frame, before any batches are drawn and
+    /// outside of rendering.
+    pub fn read_pipeline_statistics(
+        &mut self,
+        frame: &Frame,
+    ) -> Result<Vec<(String, ShaderInvocations)>> {
+        let Some(statistics) = &mut self.statistics else {
+            return Ok(vec![]);
+        };
+        let results = statistics.queries.begin_frame(frame)?;
+        statistics.frame_value = Some(frame.frame_value());
+        Ok(results)
+    }
+
     /// Returns the default material for use by meshes without special material
     /// requirements.
     pub fn default_material(&self) -> &Arc<Material> {
         &self.default_material
     }
 
-    /// Prepares the meshes for this frame.
+    /// Prepares a batch of meshes for this frame.
+    ///
+    /// This can be called any number of times per frame, e.g. once for each
+    /// render pass. Each call returns a [Batch] which is drawn with
+    /// [Self::write_batch_draw_commands].
     ///
-    /// This should only be called once per frame, calling it repeatedly will
-    /// only render whatever meshes were provided last.
+    /// Streamed geometry is copied into the frame's buffers. [StaticMesh]
+    /// geometry is drawn in place and kept alive until the frame is prepared
+    /// again.
     pub fn prepare_meshes(
         &mut self,
         ctx: &VulkanContext,
         frame: &Frame,
-        meshes: &[&dyn Mesh],
-    ) -> Result<()> {
+        meshes: &[&dyn Mesh<VertexT>],
+    ) -> Result<Batch> {
         let frame_draw = &mut self.frame_draw_resources[frame.frame_index()];
-        frame_draw.draw_params.clear();
+        frame_draw.begin_frame(frame);
 
-        // collect the vertex and index references and assemble the draw params
-        let (vertex_data, index_data) = {
+        // collect the vertex, index, and instance references and assemble the
+        // draw params relative to the start of the batch
+        let mut draw_params = Vec::with_capacity(meshes.len());
+        let (vertex_data, index_data, instance_data, transform_data) = {
             let mut vertex_data = Vec::with_capacity(meshes.len());
             let mut index_data = Vec::with_capacity(meshes.len());
+            let mut instance_data = Vec::with_capacity(meshes.len());
+            let mut transform_data = Vec::with_capacity(meshes.len());
             let mut index_offset = 0;
             let mut vertex_offset = 0;
+            let mut instance_offset = 0;
 
             for (transform_index, mesh) in meshes.iter().enumerate() {
                 let vertices = mesh.vertices();
                 let indices = mesh.indices();
+                let index_count = match mesh.static_geometry() {
+                    Some(geometry) => geometry.index_count(),
+                    None => indices.len() as u32,
+                };
+                let instances = mesh.instances();
                 vertex_data.push(vertices);
                 index_data.push(indices);
+                instance_data.push(instances);
+                transform_data.push(MeshTransform {
+                    matrix: mesh.transform().data.0,
+                });
 
-                frame_draw.draw_params.push(DrawParams {
+                draw_params.push(DrawParams {
                     index_offset,
                     vertex_offset,
-                    index_count: indices.len() as u32,
+                    index_count,
+                    instance_offset,
+                    instance_count: instances.len() as u32,
                     material: mesh.material().clone(),
                     transform_index: transform_index as u32,
                     scissor: mesh.scissor(),
+                    static_geometry: mesh.static_geometry().cloned(),
+                    sort_key: SortKey::for_mesh(*mesh),
+                    bounds: mesh.bounds(),
                 });
 
                 index_offset += indices.len() as u32;
                 vertex_offset += vertices.len() as u32;
+                instance_offset += instances.len() as u32;
             }
 
-            (vertex_data, index_data)
+            (vertex_data, index_data, instance_data, transform_data)
         };
+        if self.draw_order == DrawOrder::Sorted {
+            // stable, so equal keys keep submission order
+            draw_params.sort_by(|a, b| a.sort_key.compare(&b.sort_key));
+        }
 
-        // write mesh data into frame-specific buffers
-        unsafe {
-            frame_draw
-                .vertex_buffer
-                .write_chunked_data(ctx, &vertex_data)
-                .context("Unable to write frame vertex data!")?;
-            frame_draw
-                .index_buffer
-                .write_chunked_data(ctx, &index_data)
-                .context("Unable to write index data!")?;
-            frame_draw.transforms.write_iterated_data(
-                ctx,
-                meshes.iter().map(|mesh| MeshTransform {
-                    matrix: mesh.transform().data.0,
-                }),
-            )?;
+        // append mesh data to the frame-specific buffers
+        let (first_vertex, first_index, first_instance, first_transform) = unsafe {
+            (
+                frame_draw
+                    .vertex_buffer
+                    .append_chunked_data(ctx, &vertex_data)
+                    .context("Unable to write frame vertex data!")?,
+                frame_draw
+                    .index_buffer
+                    .append_chunked_data(ctx, &index_data)
+                    .context("Unable to write index data!")?,
+                frame_draw
+                    .instances
+                    .append_chunked_data(ctx, &instance_data)
+                    .context("Unable to write instance data!")?,
+                frame_draw
+                    .transforms
+                    .append_chunked_data(ctx, &[&transform_data])
+                    .context("Unable to write transform data!")?,
+            )
+        };
+        for params in &mut draw_params {
+            params.vertex_offset += first_vertex as u32;
+            params.index_offset += first_index as u32;
+            params.instance_offset += first_instance as u32;
+            params.transform_index += first_transform as u32;
         }
 
-        Ok(())
+        // every instance in the batch refers back to its mesh transform
+        let instance_transform_indices: Vec<u32> = meshes
+            .iter()
+            .enumerate()
+            .flat_map(|(transform_index, mesh)| {
+                std::iter::repeat_n(
+                    (first_transform + transform_index) as u32,
+                    mesh.instances().len(),
+                )
+            })
+            .collect();
+        let first_instance_transform_index = unsafe {
+            frame_draw
+                .instance_transform_indices
+                .append_chunked_data(ctx, &[&instance_transform_indices])
+                .context("Unable to write instance transform indices!")?
+        };
+        // instance indices are shared by both buffers, so they must line up
+        debug_assert_eq!(first_instance_transform_index, first_instance);
+
+        frame_draw.batches.push(BatchDraw {
+            vertex_buffer_address: frame_draw
+                .vertex_buffer
+                .buffer_device_address(),
+            index_buffer: frame_draw.index_buffer.raw(),
+            transforms_address: frame_draw.transforms.buffer_device_address(),
+            instances_address: frame_draw.instances.buffer_device_address(),
+            instance_transform_indices_address: frame_draw
+                .instance_transform_indices
+                .buffer_device_address(),
+            draw_params,
+            indirect: None,
+        });
+        Ok(Batch {
+            frame_value: frame.frame_value(),
+            index: frame_draw.batches.len() - 1,
+        })
     }
 
     pub fn set_frame_constants(
@@ -302,16 +837,187 @@ impl<PerFrameDataT: Copy> StreamingRenderer<PerFrameDataT> {
         }
     }
 
-    /// Emits draw commands for all of the meshes in the current frame.
-    ///
-    /// NOTE: it is incorrect to call this multiple times for the same frame as
-    ///       there is only one internal vertex buffer per frame.
+    /// Emits draw commands for the most recently prepared batch in the
+    /// current frame.
     pub fn write_draw_commands(
         &mut self,
         ctx: &VulkanContext,
         frame: &Frame,
     ) -> Result<()> {
+        let frame_draw = &self.frame_draw_resources[frame.frame_index()];
+        if frame_draw.frame_value != frame.frame_value() {
+            return Ok(());
+        }
+        let Some(index) = frame_draw.batches.len().checked_sub(1) else {
+            return Ok(());
+        };
+        self.write_batch_draw_commands(
+            ctx,
+            frame,
+            Batch {
+                frame_value: frame.frame_value(),
+                index,
+            },
+        )
+    }
+
+    /// Culls a prepared batch on the GPU so it is drawn indirectly.
+    ///
+    /// This records a compute dispatch which frustum-culls every draw using
+    /// its [Mesh::bounds] and compacts the visible draws into indirect
+    /// commands. [Self::write_batch_draw_commands] then issues a single
+    /// `vkCmdDrawIndexedIndirectCount` for each run of consecutive draws that
+    /// share a material, scissor, and geometry, so CPU cost depends on the
+    /// number of runs rather than the number of meshes.
+    ///
+    /// This must be recorded outside of rendering, before the batch is drawn,
+    /// and fails unless the Vulkan 1.2 `draw_indirect_count` device feature
+    /// is enabled. Visible draws within a run are drawn in an unspecified
+    /// order.
+    pub fn cull_batch(
+        &mut self,
+        ctx: &VulkanContext,
+        frame: &Frame,
+        batch: Batch,
+    ) -> Result<()> {
+        if !ctx.draw_indirect_count_enabled() {
+            bail!(
+                "GPU culling requires the draw_indirect_count device feature"
+            );
+        }
         let frame_draw = &mut self.frame_draw_resources[frame.frame_index()];
+        frame_draw.check_batch(frame, batch)?;
+        let batch_draw = &frame_draw.batches[batch.index];
+        if batch_draw.indirect.is_some() {
+            bail!("Batch {:?} has already been culled", batch);
+        }
+
+        // group the draws into runs which can share an indirect draw call
+        let streamed_geometry =
+            (batch_draw.vertex_buffer_address, batch_draw.index_buffer);
+        let draw_params = &batch_draw.draw_params;
+        let mut runs: Vec<IndirectRun> = Vec::new();
+        let mut inputs = Vec::with_capacity(draw_params.len());
+        for (index, params) in draw_params.iter().enumerate() {
+            let starts_run = runs.last().is_none_or(|run| {
+                !draw_params[run.first_draw]
+                    .shares_state_with(params, streamed_geometry)
+            });
+            if starts_run {
+                runs.push(IndirectRun {
+                    first_draw: index,
+                    max_draw_count: 0,
+                });
+            }
+            let run_index = runs.len() - 1;
+            let run = &mut runs[run_index];
+            run.max_draw_count += 1;
+
+            let (_, first_index, vertex_offset) =
+                params.geometry(streamed_geometry);
+            inputs.push(CullInput::new(
+                vk::DrawIndexedIndirectCommand {
+                    index_count: params.index_count,
+                    instance_count: params.instance_count,
+                    first_index,
+                    vertex_offset: vertex_offset as i32,
+                    first_instance: params.instance_offset,
+                },
+                run_index as u32,
+                run.first_draw as u32,
+                params.transform_index,
+                params.bounds,
+            ));
+        }
+
+        // the culling shader writes every visible draw into its run's
+        // commands and counts, so counts start at zero
+        let (first_input, first_command, first_count) = unsafe {
+            (
+                frame_draw
+                    .cull_inputs
+                    .append_chunked_data(ctx, &[&inputs])
+                    .context("Unable to write cull inputs!")?,
+                frame_draw
+                    .indirect_commands
+                    .append_chunked_data(
+                        ctx,
+                        &[&vec![
+                            vk::DrawIndexedIndirectCommand::default();
+                            inputs.len()
+                        ]],
+                    )
+                    .context("Unable to allocate indirect commands!")?,
+                frame_draw
+                    .indirect_counts
+                    .append_chunked_data(ctx, &[&vec![0; runs.len()]])
+                    .context("Unable to allocate indirect counts!")?,
+            )
+        };
+        let commands_offset = (first_command
+            * size_of::<vk::DrawIndexedIndirectCommand>())
+            as vk::DeviceSize;
+        let counts_offset = (first_count * size_of::<u32>()) as vk::DeviceSize;
+
+        if !inputs.is_empty() {
+            self.culling.record(
+                ctx,
+                frame.command_buffer(),
+                [
+                    frame_draw.cull_inputs.buffer_device_address()
+                        + (first_input * size_of::<CullInput>())
+                            as vk::DeviceSize,
+                    frame_draw.indirect_commands.buffer_device_address()
+                        + commands_offset,
+                    frame_draw.indirect_counts.buffer_device_address()
+                        + counts_offset,
+                    batch_draw.transforms_address,
+                ],
+                inputs.len() as u32,
+            );
+
+            // make the culled commands and counts visible to indirect draws
+            Barriers::new()
+                .buffer(
+                    frame_draw.indirect_commands.raw(),
+                    commands_offset,
+                    (inputs.len() * size_of::<vk::DrawIndexedIndirectCommand>())
+                        as vk::DeviceSize,
+                    BufferUsage::StorageReadWrite,
+                    BufferUsage::IndirectRead,
+                )
+                .buffer(
+                    frame_draw.indirect_counts.raw(),
+                    counts_offset,
+                    (runs.len() * size_of::<u32>()) as vk::DeviceSize,
+                    BufferUsage::StorageReadWrite,
+                    BufferUsage::IndirectRead,
+                )
+                .record(ctx, frame.command_buffer())?;
+        }
+
+        frame_draw.batches[batch.index].indirect = Some(IndirectDraws {
+            commands_buffer: frame_draw.indirect_commands.raw(),
+            commands_offset,
+            counts_buffer: frame_draw.indirect_counts.raw(),
+            counts_offset,
+            runs,
+        });
+        Ok(())
+    }
+
+    /// Emits draw commands for all of the meshes in a batch.
+    ///
+    /// Fails if the batch was prepared for a different frame.
+    pub fn write_batch_draw_commands(
+        &mut self,
+        ctx: &VulkanContext,
+        frame: &Frame,
+        batch: Batch,
+    ) -> Result<()> {
+        let frame_draw = &self.frame_draw_resources[frame.frame_index()];
+        frame_draw.check_batch(frame, batch)?;
+        let batch_draw = &frame_draw.batches[batch.index];
         unsafe {
             ctx.cmd_bind_descriptor_sets(
                 frame.command_buffer(),
@@ -321,70 +1027,185 @@ impl<PerFrameDataT: Copy> StreamingRenderer<PerFrameDataT> {
                 &[self.frame_constants.descriptor_set_for_frame(frame)],
                 &[],
             );
-            ctx.cmd_bind_index_buffer(
+            ctx.cmd_push_constants(
                 frame.command_buffer(),
-                frame_draw.index_buffer.raw(),
-                0,
-                vk::IndexType::UINT32,
+                self.pipeline_layout.raw,
+                vk::ShaderStageFlags::VERTEX,
+                8,
+                &batch_draw.transforms_address.to_le_bytes(),
             );
             ctx.cmd_push_constants(
                 frame.command_buffer(),
                 self.pipeline_layout.raw,
                 vk::ShaderStageFlags::VERTEX,
-                0,
-                &frame_draw
-                    .vertex_buffer
-                    .buffer_device_address()
-                    .to_le_bytes(),
+                24,
+                &batch_draw.instances_address.to_le_bytes(),
             );
             ctx.cmd_push_constants(
                 frame.command_buffer(),
                 self.pipeline_layout.raw,
                 vk::ShaderStageFlags::VERTEX,
-                8,
-                &frame_draw.transforms.buffer_device_address().to_le_bytes(),
+                32,
+                &batch_draw.instance_transform_indices_address.to_le_bytes(),
             );
         }
 
-        let mut last_bound_pipeline = vk::Pipeline::null();
-        for draw_params in frame_draw.draw_params.drain(0..) {
-            // Bind the pipeline for the current draw, but only if its
-            // actually different from the most recently used pipeline.
-            let pipeline = draw_params.material.pipeline().raw;
-            if pipeline != last_bound_pipeline {
+        let streamed_geometry =
+            (batch_draw.vertex_buffer_address, batch_draw.index_buffer);
+        let mut bound = BoundState::default();
+        let mut scope = None;
+
+        // Batches are retained until the frame is prepared again so static
+        // geometry outlives the frame's command buffer.
+        let Some(indirect) = &batch_draw.indirect else {
+            for draw_params in &batch_draw.draw_params {
+                if let Some(statistics) = &mut self.statistics {
+                    statistics.next_draw(
+                        frame,
+                        draw_params.material.pipeline().raw,
+                        &bound,
+                        &mut scope,
+                    );
+                }
+                let (index_offset, vertex_offset) = self.bind_draw_state(
+                    ctx,
+                    frame,
+                    draw_params,
+                    streamed_geometry,
+                    &mut bound,
+                );
                 unsafe {
-                    ctx.cmd_bind_pipeline(
+                    ctx.cmd_push_constants(
+                        frame.command_buffer(),
+                        self.pipeline_layout.raw,
+                        vk::ShaderStageFlags::VERTEX,
+                        16,
+                        &draw_params.transform_index.to_le_bytes(),
+                    );
+                    ctx.cmd_draw_indexed(
                         frame.command_buffer(),
-                        vk::PipelineBindPoint::GRAPHICS,
-                        pipeline,
+                        draw_params.index_count, // index count
+                        draw_params.instance_count, // instance count
+                        index_offset,            // first index
+                        vertex_offset as i32,    // vertex offset
+                        draw_params.instance_offset, // first instance
                     );
                 }
-                last_bound_pipeline = pipeline;
             }
+            if let Some(statistics) = &mut self.statistics {
+                statistics.end(frame, scope);
+            }
+            return Ok(());
+        };
+
+        let stride = size_of::<vk::DrawIndexedIndirectCommand>();
+        for (run_index, run) in indirect.runs.iter().enumerate() {
+            let draw_params = &batch_draw.draw_params[run.first_draw];
+            if let Some(statistics) = &mut self.statistics {
+                statistics.next_draw(
+                    frame,
+                    draw_params.material.pipeline().raw,
+                    &bound,
+                    &mut scope,
+                );
+            }
+            self.bind_draw_state(
+                ctx,
+                frame,
+                draw_params,
+                streamed_geometry,
+                &mut bound,
+            );
+            unsafe {
+                ctx.cmd_draw_indexed_indirect_count(
+                    frame.command_buffer(),
+                    indirect.commands_buffer,
+                    indirect.commands_offset
+                        + (run.first_draw * stride) as vk::DeviceSize,
+                    indirect.counts_buffer,
+                    indirect.counts_offset
+                        + (run_index * size_of::<u32>()) as vk::DeviceSize,
+                    run.max_draw_count,
+                    stride as u32,
+                );
+            }
+        }
+        if let Some(statistics) = &mut self.statistics {
+            statistics.end(frame, scope);
+        }
+
+        Ok(())
+    }
+
+    /// Binds the geometry, pipeline, material parameters, and scissor for a
+    /// draw, skipping any state that matches the most recently bound state.
+    ///
+    /// Returns the first index and vertex offset of the draw within the bound
+    /// geometry.
+    fn bind_draw_state(
+        &self,
+        ctx: &VulkanContext,
+        frame: &Frame,
+        draw_params: &DrawParams,
+        streamed_geometry: Geometry,
+        bound: &mut BoundState,
+    ) -> (u32, u32) {
+        let (geometry, index_offset, vertex_offset) =
+            draw_params.geometry(streamed_geometry);
+        if geometry != bound.geometry {
+            let (vertex_buffer_address, index_buffer) = geometry;
             unsafe {
+                ctx.cmd_bind_index_buffer(
+                    frame.command_buffer(),
+                    index_buffer,
+                    0,
+                    vk::IndexType::UINT32,
+                );
                 ctx.cmd_push_constants(
                     frame.command_buffer(),
                     self.pipeline_layout.raw,
                     vk::ShaderStageFlags::VERTEX,
-                    16,
-                    &draw_params.transform_index.to_le_bytes(),
+                    0,
+                    &vertex_buffer_address.to_le_bytes(),
                 );
-                ctx.cmd_set_scissor(
+            }
+            bound.geometry = geometry;
+        }
+
+        // Bind the pipeline for the current draw, but only if its actually
+        // different from the most recently used pipeline.
+        let pipeline = draw_params.material.pipeline().raw;
+        if pipeline != bound.pipeline {
+            unsafe {
+                ctx.cmd_bind_pipeline(
                     frame.command_buffer(),
-                    0,
-                    &[draw_params.scissor],
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipeline,
                 );
-                ctx.cmd_draw_indexed(
+            }
+            bound.pipeline = pipeline;
+        }
+        let parameters = draw_params.material.parameters_address();
+        if Some(parameters) != bound.parameters {
+            unsafe {
+                ctx.cmd_push_constants(
                     frame.command_buffer(),
-                    draw_params.index_count, // index count
-                    1,                       // instance count
-                    draw_params.index_offset, // first index
-                    draw_params.vertex_offset as i32, // vertex offset
-                    0,                       // first instance
+                    self.pipeline_layout.raw,
+                    vk::ShaderStageFlags::VERTEX
+                        | vk::ShaderStageFlags::FRAGMENT,
+                    Material::PARAMETERS_OFFSET,
+                    &parameters.to_le_bytes(),
                 );
             }
+            bound.parameters = Some(parameters);
         }
-
-        Ok(())
+        unsafe {
+            ctx.cmd_set_scissor(
+                frame.command_buffer(),
+                0,
+                &[draw_params.scissor],
+            );
+        }
+        (index_offset, vertex_offset)
     }
 }
@@ -0,0 +1,338 @@
+use {
+    super::Texture,
+    crate::{
+        graphics::{
+            vulkan::{Frame, FramesInFlight, VulkanContext},
+            Barriers, ImageUsage,
+        },
+        unwrap_here,
+    },
+    anyhow::{bail, Result},
+    ash::vk,
+};
+
+/// The attachments used while rendering a single frame.
+struct FrameAttachments {
+    extent: vk::Extent2D,
+    depth: Texture,
+
+    /// The multisampled color image which is resolved into the target.
+    color: Option<Texture>,
+}
+
+/// Owns the depth attachment used when rendering [Material] pipelines and
+/// begins dynamic rendering with it attached.
+///
+/// One set of attachments is kept per frame in flight. Each is recreated
+/// whenever the color attachment's extent changes, so swapchain resizes need
+/// no extra handling. Transitions are recorded with [Barriers], so the
+/// `synchronization2` device feature is required.
+///
+/// Multisampled render targets also own a multisampled color image. Meshes
+/// are rendered into it and resolved into the color attachment passed to
+/// [Self::begin_rendering] when rendering ends.
+///
+/// [Material]: super::Material
+pub struct RenderTarget {
+    samples: vk::SampleCountFlags,
+    color_format: vk::Format,
+    attachments: Vec<Option<FrameAttachments>>,
+    clear_color: [f32; 4],
+    preserve_color: bool,
+}
+
+impl RenderTarget {
+    /// The depth attachment format every [Material](super::Material) pipeline
+    /// is created with.
+    pub const DEPTH_FORMAT: vk::Format = vk::Format::D32_SFLOAT;
+
+    /// Creates a single-sample render target with depth images for each frame
+    /// in flight.
+    ///
+    /// Depth images are created when first used.
+    pub fn new(frames_in_flight: &FramesInFlight) -> Self {
+        Self {
+            samples: vk::SampleCountFlags::TYPE_1,
+            color_format: vk::Format::UNDEFINED,
+            attachments: (0..frames_in_flight.frame_count())
+                .map(|_| None)
+                .collect(),
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            preserve_color: false,
+        }
+    }
+
+    /// Creates a render target which renders with `samples` samples per pixel
+    /// and resolves into color attachments with `color_format`.
+    ///
+    /// Use [StreamingRenderer::new_with_samples] with the same sample count.
+    /// Fails if `samples` isn't exactly one sample count or if the device
+    /// can't render color and depth with it.
+    ///
+    /// [StreamingRenderer::new_with_samples]: super::StreamingRenderer::new_with_samples
+    pub fn new_multisampled(
+        ctx: &VulkanContext,
+        frames_in_flight: &FramesInFlight,
+        color_format: vk::Format,
+        samples: vk::SampleCountFlags,
+    ) -> Result<Self> {
+        check_samples(samples, Self::supported_samples(ctx))?;
+        Ok(Self {
+            samples,
+            color_format,
+            ..Self::new(frames_in_flight)
+        })
+    }
+
+    /// Returns the sample counts the device supports for both color and depth
+    /// attachments.
+    pub fn supported_samples(ctx: &VulkanContext) -> vk::SampleCountFlags {
+        let limits = unsafe {
+            ctx.instance
+                .get_physical_device_properties(ctx.physical_device)
+                .limits
+        };
+        limits.framebuffer_color_sample_counts
+            & limits.framebuffer_depth_sample_counts
+    }
+
+    /// Returns the number of samples per pixel used while rendering.
+    pub fn samples(&self) -> vk::SampleCountFlags {
+        self.samples
+    }
+
+    /// Sets the color used to clear the color attachment when rendering
+    /// begins.
+    pub fn set_clear_color(&mut self, clear_color: [f32; 4]) {
+        self.clear_color = clear_color;
+    }
+
+    /// When true, the color attachment's existing contents are loaded rather
+    /// than cleared when rendering begins, e.g. to draw a UI over a previous
+    /// pass. The depth attachment is always cleared.
+    ///
+    /// Multisampled render targets always overwrite the color attachment with
+    /// the resolve, so [Self::begin_rendering] fails for them when this is
+    /// set.
+    pub fn set_preserve_color(&mut self, preserve_color: bool) {
+        self.preserve_color = preserve_color;
+    }
+
+    /// Returns the frame's depth texture, if rendering has begun for it.
+    pub fn depth_texture(&self, frame: &Frame) -> Option<&Texture> {
+        self.attachments[frame.frame_index()]
+            .as_ref()
+            .map(|attachments| &attachments.depth)
+    }
+
+    /// Begins dynamic rendering to the color attachment with the frame's
+    /// depth attachment.
+    ///
+    /// The color image must already be in COLOR_ATTACHMENT_OPTIMAL layout.
+    /// When multisampled, the color image is written by the resolve at the end
+    /// of rendering and stays in COLOR_ATTACHMENT_OPTIMAL layout. The
+    /// attachments are cleared, unless the color is preserved with
+    /// [Self::set_preserve_color], and the viewport and scissor are set to
+    /// cover the full extent.
+    pub fn begin_rendering(
+        &mut self,
+        ctx: &VulkanContext,
+        frame: &Frame,
+        color_image_view: vk::ImageView,
+        extent: vk::Extent2D,
+    ) -> Result<()> {
+        if self.preserve_color && self.samples != vk::SampleCountFlags::TYPE_1 {
+            bail!("Multisampled render targets cannot preserve color");
+        }
+        let attachments = match &mut self.attachments[frame.frame_index()] {
+            // The previous submission for this frame index has completed, so
+            // old attachments can be replaced immediately.
+            Some(attachments) if attachments.extent == extent => attachments,
+            slot => slot.insert(unwrap_here!(
+                "Create render target attachments",
+                create_attachments(
+                    ctx,
+                    extent,
+                    self.color_format,
+                    self.samples,
+                )
+            )),
+        };
+
+        let mut barriers = Barriers::new().texture_discarding(
+            &attachments.depth,
+            ImageUsage::DepthAttachment,
+        );
+        if let Some(color) = &attachments.color {
+            barriers =
+                barriers.texture_discarding(color, ImageUsage::ColorAttachment);
+        }
+        barriers.record(ctx, frame.command_buffer())?;
+
+        let clear_color = vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: self.clear_color,
+            },
+        };
+        let color_attachments = [match &attachments.color {
+            Some(color) => vk::RenderingAttachmentInfo {
+                image_view: color.view().raw,
+                image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                resolve_mode: vk::ResolveModeFlags::AVERAGE,
+                resolve_image_view: color_image_view,
+                resolve_image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                load_op: vk::AttachmentLoadOp::CLEAR,
+                store_op: vk::AttachmentStoreOp::DONT_CARE,
+                clear_value: clear_color,
+                ..Default::default()
+            },
+            None => vk::RenderingAttachmentInfo {
+                image_view: color_image_view,
+                image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                resolve_mode: vk::ResolveModeFlags::NONE,
+                load_op: if self.preserve_color {
+                    vk::AttachmentLoadOp::LOAD
+                } else {
+                    vk::AttachmentLoadOp::CLEAR
+                },
+                store_op: vk::AttachmentStoreOp::STORE,
+                clear_value: clear_color,
+                ..Default::default()
+            },
+        }];
+        let depth_attachment = vk::RenderingAttachmentInfo {
+            image_view: attachments.depth.view().raw,
+            image_layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+            resolve_mode: vk::ResolveModeFlags::NONE,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::DONT_CARE,
+            clear_value: vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+            ..Default::default()
+        };
+        let render_area = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+        unsafe {
+            ctx.cmd_begin_rendering(
+                frame.command_buffer(),
+                &vk::RenderingInfo {
+                    render_area,
+                    layer_count: 1,
+                    color_attachment_count: color_attachments.len() as u32,
+                    p_color_attachments: color_attachments.as_ptr(),
+                    p_depth_attachment: &depth_attachment,
+                    ..Default::default()
+                },
+            );
+            ctx.cmd_set_viewport(
+                frame.command_buffer(),
+                0,
+                &[vk::Viewport {
+                    x: 0.0,
+                    y: 0.0,
+                    width: extent.width as f32,
+                    height: extent.height as f32,
+                    min_depth: 0.0,
+                    max_depth: 1.0,
+                }],
+            );
+            ctx.cmd_set_scissor(frame.command_buffer(), 0, &[render_area]);
+        }
+        Ok(())
+    }
+
+    /// Ends dynamic rendering started by [Self::begin_rendering].
+    pub fn end_rendering(&self, ctx: &VulkanContext, frame: &Frame) {
+        unsafe {
+            ctx.cmd_end_rendering(frame.command_buffer());
+        }
+    }
+}
+
+fn create_attachments(
+    ctx: &VulkanContext,
+    extent: vk::Extent2D,
+    color_format: vk::Format,
+    samples: vk::SampleCountFlags,
+) -> Result<FrameAttachments> {
+    let attachment = |format, usage| {
+        Texture::builder()
+            .ctx(ctx)
+            .dimensions((extent.width, extent.height))
+            .format(format)
+            .image_usage_flags(usage)
+            .memory_property_flags(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+            .samples(samples)
+            .build()
+    };
+    let depth = unwrap_here!(
+        "Create depth attachment",
+        attachment(
+            RenderTarget::DEPTH_FORMAT,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+        )
+    );
+    let color = if samples == vk::SampleCountFlags::TYPE_1 {
+        None
+    } else {
+        Some(unwrap_here!(
+            "Create multisampled color attachment",
+            attachment(
+                color_format,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT
+                    | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT
+            )
+        ))
+    };
+    Ok(FrameAttachments {
+        extent,
+        depth,
+        color,
+    })
+}
+
+/// Fails unless `samples` is a single sample count which is `supported`.
+fn check_samples(
+    samples: vk::SampleCountFlags,
+    supported: vk::SampleCountFlags,
+) -> Result<()> {
+    if !samples.as_raw().is_power_of_two() {
+        bail!("{:?} is not a single sample count", samples);
+    }
+    if !supported.contains(samples) {
+        bail!(
+            "{:?} samples are not supported, supported sample counts: {:?}",
+            samples,
+            supported
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn samples_should_be_a_single_supported_count() {
+        let supported = vk::SampleCountFlags::TYPE_1
+            | vk::SampleCountFlags::TYPE_2
+            | vk::SampleCountFlags::TYPE_4;
+        assert!(check_samples(vk::SampleCountFlags::TYPE_4, supported).is_ok());
+        assert!(check_samples(vk::SampleCountFlags::TYPE_8, supported).is_err());
+        assert!(
+            check_samples(vk::SampleCountFlags::empty(), supported).is_err()
+        );
+        assert!(check_samples(
+            vk::SampleCountFlags::TYPE_1 | vk::SampleCountFlags::TYPE_2,
+            supported
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,92 @@
+#version 460
+#pragma shader_stage(compute)
+
+// Adds support for buffer references, used for all draw data
+#extension GL_EXT_buffer_reference : enable
+
+layout(local_size_x = 64) in;
+
+struct DrawIndexedIndirectCommand {
+    uint index_count;
+    uint instance_count;
+    uint first_index;
+    int vertex_offset;
+    uint first_instance;
+};
+
+struct CullInput {
+    DrawIndexedIndirectCommand command;
+    uint run_index;
+    uint run_first_command;
+    uint transform_index;
+
+    // w is 1.0 when the draw has bounds, draws without bounds are never culled
+    vec4 bounds_min;
+    vec4 bounds_max;
+};
+
+struct MeshTransform {
+    mat4 transform;
+};
+
+layout(buffer_reference, std430) readonly buffer CullInputBuffer {
+    CullInput data[];
+};
+layout(buffer_reference, std430) writeonly buffer CommandBuffer {
+    DrawIndexedIndirectCommand data[];
+};
+layout(buffer_reference, std430) buffer CountBuffer {
+    uint data[];
+};
+layout(buffer_reference, std430) readonly buffer TransformBuffer {
+    MeshTransform data[];
+};
+layout(push_constant) uniform constants {
+    CullInputBuffer inputs;
+    CommandBuffer commands;
+    CountBuffer counts;
+    TransformBuffer mesh_transforms;
+    uint draw_count;
+} pc_Constants;
+
+// A box is visible unless all of its corners are outside of the same clip
+// plane.
+bool is_visible(mat4 transform, vec3 lo, vec3 hi) {
+    uint outside_all = 0x3F;
+    for (int i = 0; i < 8; i++) {
+        vec3 corner = vec3(
+            (i & 1) != 0 ? hi.x : lo.x,
+            (i & 2) != 0 ? hi.y : lo.y,
+            (i & 4) != 0 ? hi.z : lo.z
+        );
+        vec4 clip = transform * vec4(corner, 1.0);
+        uint outside = 0;
+        outside |= clip.x < -clip.w ? 1 : 0;
+        outside |= clip.x > clip.w ? 2 : 0;
+        outside |= clip.y < -clip.w ? 4 : 0;
+        outside |= clip.y > clip.w ? 8 : 0;
+        outside |= clip.z < 0.0 ? 16 : 0;
+        outside |= clip.z > clip.w ? 32 : 0;
+        outside_all &= outside;
+    }
+    return outside_all == 0;
+}
+
+void main() {
+    uint id = gl_GlobalInvocationID.x;
+    if (id >= pc_Constants.draw_count) {
+        return;
+    }
+
+    CullInput draw = pc_Constants.inputs.data[id];
+    if (draw.bounds_min.w != 0.0) {
+        mat4 transform =
+            pc_Constants.mesh_transforms.data[draw.transform_index].transform;
+        if (!is_visible(transform, draw.bounds_min.xyz, draw.bounds_max.xyz)) {
+            return;
+        }
+    }
+
+    uint slot = atomicAdd(pc_Constants.counts.data[draw.run_index], 1);
+    pc_Constants.commands.data[draw.run_first_command + slot] = draw.command;
+}
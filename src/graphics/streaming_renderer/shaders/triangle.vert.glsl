@@ -21,6 +21,12 @@ struct MeshTransform {
     mat4 transform;
 };
 
+struct Instance {
+    mat4 transform;
+    vec4 color;
+    int texture_index;
+};
+
 // textures bound to set 0
 layout(set = 0, binding = 0) uniform sampler u_Sampler;
 layout(set = 0, binding = 1) uniform texture2D u_Textures[];
@@ -37,10 +43,22 @@ layout(buffer_reference, std430) readonly buffer VertexBuffer {
 layout(buffer_reference, std430) readonly buffer TransformBuffer {
     MeshTransform data[];
 };
+layout(buffer_reference, std430) readonly buffer InstanceBuffer {
+    Instance data[];
+};
+layout(buffer_reference, std430) readonly buffer TransformIndexBuffer {
+    uint32_t data[];
+};
 layout(push_constant) uniform constants {
     VertexBuffer vertices;
     TransformBuffer mesh_transforms;
+
+    // Only set for direct draws, prefer instance_transform_indices which is
+    // valid for both direct and indirect draws.
     uint32_t transform_index;
+
+    InstanceBuffer instances;
+    TransformIndexBuffer instance_transform_indices;
 } pc_Constants;
 
 // Per-Vertex outputs
@@ -50,12 +68,18 @@ layout(location = 2) flat out int out_TextureIndex;
 
 void main() {
     Vertex vert = pc_Constants.vertices.data[gl_VertexIndex];
-    out_VertexColor = vert.color;
-    out_TextureIndex = vert.texture_index;
+    Instance instance = pc_Constants.instances.data[gl_InstanceIndex];
+    out_VertexColor = vert.color * instance.color;
+    out_TextureIndex = instance.texture_index >= 0
+        ? instance.texture_index
+        : vert.texture_index;
     out_UV = vec2(vert.uv_x, vert.uv_y);
 
+    uint32_t transform_index =
+        pc_Constants.instance_transform_indices.data[gl_InstanceIndex];
     mat4 transform =
-        pc_Constants.mesh_transforms.data[pc_Constants.transform_index].transform;
+        pc_Constants.mesh_transforms.data[transform_index].transform
+        * instance.transform;
 
     gl_Position = transform * vec4(vert.pos.x, vert.pos.y, vert.pos.z, 1.0);
 }
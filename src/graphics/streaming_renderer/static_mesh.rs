@@ -0,0 +1,348 @@
+use {
+    super::{Bounds, Material, Mesh, Vertex},
+    crate::{
+        graphics::{
+            vulkan::{
+                raii, CPUBuffer, OwnedBlock, SyncCommands, VulkanContext,
+            },
+            Barriers, BufferUsage,
+        },
+        unwrap_here,
+    },
+    anyhow::Result,
+    ash::vk,
+    nalgebra::Matrix4,
+    std::sync::Arc,
+};
+
+/// Vertex and index buffers in device-local memory.
+///
+/// Geometry is shared by every clone of a [StaticMesh] and is kept alive by
+/// the renderer until frames which draw it have completed.
+#[derive(Debug)]
+pub struct StaticGeometry {
+    _vertex_buffer: raii::Buffer,
+    _vertex_block: OwnedBlock,
+    vertex_buffer_address: vk::DeviceAddress,
+    index_buffer: raii::Buffer,
+    _index_block: OwnedBlock,
+    index_count: u32,
+}
+
+impl StaticGeometry {
+    /// Uploads the vertices and indices to device-local memory.
+    ///
+    /// This blocks until the upload has completed. Vertices can be any type
+    /// which matches the vertex shader of the materials that draw them.
+    pub fn new<VertexT: Copy>(
+        ctx: &VulkanContext,
+        sync_commands: &SyncCommands,
+        vertices: &[VertexT],
+        indices: &[u32],
+    ) -> Result<Self> {
+        let vertex_upload = unwrap_here!(
+            "Stage static mesh vertices",
+            StagedUpload::new(
+                ctx,
+                vertices,
+                vk::BufferUsageFlags::STORAGE_BUFFER
+                    | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            )
+        );
+        let index_upload = unwrap_here!(
+            "Stage static mesh indices",
+            StagedUpload::new(ctx, indices, vk::BufferUsageFlags::INDEX_BUFFER)
+        );
+        unwrap_here!(
+            "Copy static mesh geometry to device-local buffers",
+            sync_commands.submit_and_wait(|cmd| {
+                vertex_upload.record_copy(ctx, cmd);
+                index_upload.record_copy(ctx, cmd);
+                // Make the copies visible to every later draw on the queue.
+                Barriers::new()
+                    .buffer(
+                        vertex_upload.buffer.raw,
+                        0,
+                        vk::WHOLE_SIZE,
+                        BufferUsage::TransferDst,
+                        BufferUsage::VertexRead,
+                    )
+                    .buffer(
+                        index_upload.buffer.raw,
+                        0,
+                        vk::WHOLE_SIZE,
+                        BufferUsage::TransferDst,
+                        BufferUsage::IndexRead,
+                    )
+                    .record(ctx, cmd)
+            })
+        );
+        let (vertex_buffer, vertex_block) = vertex_upload.finish();
+        let (index_buffer, index_block) = index_upload.finish();
+        let vertex_buffer_address = unsafe {
+            ctx.get_buffer_device_address(&vk::BufferDeviceAddressInfo {
+                buffer: vertex_buffer.raw,
+                ..Default::default()
+            })
+        };
+        Ok(Self {
+            _vertex_buffer: vertex_buffer,
+            _vertex_block: vertex_block,
+            vertex_buffer_address,
+            index_buffer,
+            _index_block: index_block,
+            index_count: indices.len() as u32,
+        })
+    }
+
+    /// Returns the device address of the vertex buffer.
+    pub fn vertex_buffer_address(&self) -> vk::DeviceAddress {
+        self.vertex_buffer_address
+    }
+
+    /// Returns the raw index buffer handle.
+    pub fn index_buffer(&self) -> vk::Buffer {
+        self.index_buffer.raw
+    }
+
+    /// Returns the number of indices in the index buffer.
+    pub fn index_count(&self) -> u32 {
+        self.index_count
+    }
+}
+
+/// A retained mesh whose geometry is uploaded once to device-local memory.
+///
+/// Static meshes are drawn with the same [Material] and transform machinery
+/// as streamed meshes and can be mixed freely with them when preparing a
+/// frame. Cloning a static mesh shares its geometry, so the same geometry can
+/// be drawn with different materials, transforms, and scissors.
+#[derive(Clone)]
+pub struct StaticMesh {
+    geometry: Arc<StaticGeometry>,
+    material: Arc<Material>,
+    transform: Matrix4<f32>,
+    scissor: vk::Rect2D,
+    layer: i32,
+    bounds: Option<Bounds>,
+}
+
+impl<VertexT> Mesh<VertexT> for StaticMesh {
+    /// Static meshes stream no vertices, see [Mesh::static_geometry].
+    fn vertices(&self) -> &[VertexT] {
+        &[]
+    }
+
+    /// Static meshes stream no indices, see [Mesh::static_geometry].
+    fn indices(&self) -> &[u32] {
+        &[]
+    }
+
+    fn material(&self) -> &Arc<Material> {
+        &self.material
+    }
+
+    fn transform(&self) -> &Matrix4<f32> {
+        &self.transform
+    }
+
+    fn scissor(&self) -> vk::Rect2D {
+        self.scissor
+    }
+
+    fn static_geometry(&self) -> Option<&Arc<StaticGeometry>> {
+        Some(&self.geometry)
+    }
+
+    fn layer(&self) -> i32 {
+        self.layer
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
+}
+
+impl StaticMesh {
+    /// Uploads the vertices and indices to device-local memory.
+    ///
+    /// This blocks until the upload has completed. Bounds are computed from
+    /// the vertices.
+    pub fn new(
+        ctx: &VulkanContext,
+        sync_commands: &SyncCommands,
+        vertices: &[Vertex],
+        indices: &[u32],
+        material: Arc<Material>,
+    ) -> Result<Self> {
+        let geometry = unwrap_here!(
+            "Upload static mesh geometry",
+            StaticGeometry::new(ctx, sync_commands, vertices, indices)
+        );
+        let mut static_mesh = Self::from_geometry(Arc::new(geometry), material);
+        static_mesh.set_bounds(Bounds::from_vertices(vertices));
+        Ok(static_mesh)
+    }
+
+    /// Creates a mesh which draws existing geometry, e.g. geometry uploaded
+    /// with a custom vertex type.
+    ///
+    /// The mesh has no bounds until they are set with [Self::set_bounds].
+    pub fn from_geometry(
+        geometry: Arc<StaticGeometry>,
+        material: Arc<Material>,
+    ) -> Self {
+        Self {
+            geometry,
+            material,
+            transform: Matrix4::identity(),
+            scissor: vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: vk::Extent2D {
+                    width: 1,
+                    height: 1,
+                },
+            },
+            layer: 0,
+            bounds: None,
+        }
+    }
+
+    /// Uploads another mesh's current geometry, e.g. a [TrianglesMesh] built
+    /// once at startup.
+    ///
+    /// The material, transform, scissor, layer, and bounds are copied from
+    /// the mesh.
+    ///
+    /// [TrianglesMesh]: super::TrianglesMesh
+    pub fn from_mesh<VertexT: Copy>(
+        ctx: &VulkanContext,
+        sync_commands: &SyncCommands,
+        mesh: &dyn Mesh<VertexT>,
+    ) -> Result<Self> {
+        let geometry = unwrap_here!(
+            "Upload static mesh geometry",
+            StaticGeometry::new(
+                ctx,
+                sync_commands,
+                mesh.vertices(),
+                mesh.indices()
+            )
+        );
+        let mut static_mesh =
+            Self::from_geometry(Arc::new(geometry), mesh.material().clone());
+        static_mesh.set_transform(*mesh.transform());
+        static_mesh.set_scissor(mesh.scissor());
+        static_mesh.set_layer(mesh.layer());
+        static_mesh.set_bounds(mesh.bounds());
+        Ok(static_mesh)
+    }
+
+    /// Returns the uploaded geometry.
+    pub fn geometry(&self) -> &Arc<StaticGeometry> {
+        &self.geometry
+    }
+
+    pub fn set_material(&mut self, material: Arc<Material>) {
+        self.material = material;
+    }
+
+    pub fn set_scissor(&mut self, rect: vk::Rect2D) {
+        self.scissor = rect;
+    }
+
+    /// Sets the layer used when sorting draws, see [Mesh::layer].
+    pub fn set_layer(&mut self, layer: i32) {
+        self.layer = layer;
+    }
+
+    /// Sets the bounds used for GPU culling, see [Mesh::bounds].
+    pub fn set_bounds(&mut self, bounds: Option<Bounds>) {
+        self.bounds = bounds;
+    }
+
+    /// Set the matrix transformation matrix.
+    pub fn set_transform(&mut self, transform: Matrix4<f32>) {
+        self.transform = transform;
+    }
+}
+
+/// A device-local buffer and the temporary staging buffer that fills it.
+struct StagedUpload<DataT: Copy> {
+    staging: CPUBuffer<DataT>,
+    buffer: raii::Buffer,
+    block: OwnedBlock,
+    size: u64,
+}
+
+impl<DataT: Copy> StagedUpload<DataT> {
+    /// Writes the data into a staging buffer and allocates the device-local
+    /// buffer it's copied to.
+    fn new(
+        ctx: &VulkanContext,
+        data: &[DataT],
+        usage: vk::BufferUsageFlags,
+    ) -> Result<Self> {
+        // Vulkan buffers cannot be empty
+        let count = data.len().max(1);
+        let size = (count * size_of::<DataT>()) as u64;
+
+        let mut staging = unwrap_here!(
+            "Allocate staging buffer",
+            CPUBuffer::<DataT>::allocate(
+                ctx,
+                count,
+                vk::BufferUsageFlags::TRANSFER_SRC
+            )
+        );
+        unwrap_here!("Write staging buffer", unsafe {
+            staging.write_data(0, data)
+        });
+
+        let (block, buffer) = unwrap_here!(
+            "Allocate device-local buffer",
+            OwnedBlock::allocate_buffer(
+                ctx.allocator.clone(),
+                &vk::BufferCreateInfo {
+                    size,
+                    usage: usage | vk::BufferUsageFlags::TRANSFER_DST,
+                    sharing_mode: vk::SharingMode::EXCLUSIVE,
+                    queue_family_index_count: 1,
+                    p_queue_family_indices: &ctx.graphics_queue_family_index,
+                    ..Default::default()
+                },
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )
+        );
+
+        Ok(Self {
+            staging,
+            buffer,
+            block,
+            size,
+        })
+    }
+
+    /// Records the copy from the staging buffer to the device-local buffer.
+    fn record_copy(&self, ctx: &VulkanContext, cmd: vk::CommandBuffer) {
+        unsafe {
+            ctx.cmd_copy_buffer(
+                cmd,
+                self.staging.buffer(),
+                self.buffer.raw,
+                &[vk::BufferCopy {
+                    src_offset: 0,
+                    dst_offset: 0,
+                    size: self.size,
+                }],
+            );
+        }
+    }
+
+    /// Drops the staging buffer and returns the device-local buffer.
+    ///
+    /// The copy must have completed.
+    fn finish(self) -> (raii::Buffer, OwnedBlock) {
+        (self.buffer, self.block)
+    }
+}
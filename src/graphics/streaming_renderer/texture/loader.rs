@@ -1,7 +1,10 @@
 use {
     super::{super::utility::round_to_power_of_two, Texture},
-    crate::graphics::vulkan::{CPUBuffer, SyncCommands, VulkanContext},
-    anyhow::{Context, Result},
+    crate::graphics::{
+        vulkan::{CPUBuffer, SyncCommands, VulkanContext},
+        Barriers, ImageUsage,
+    },
+    anyhow::{bail, Context, Result},
     ash::vk::{self},
     image::{imageops::FilterType, DynamicImage, RgbaImage},
     std::{path::PathBuf, sync::Arc},
@@ -16,7 +19,13 @@ pub struct TextureLoader {
 
 impl TextureLoader {
     /// Creates a new texture loader instance.
+    ///
+    /// Uploads are synchronized with [Barriers], so the device must be
+    /// created with the `synchronization2` feature.
     pub fn new(ctx: Arc<VulkanContext>) -> Result<Self> {
+        if !ctx.synchronization2_enabled() {
+            bail!("TextureLoader requires the synchronization2 device feature");
+        }
         Ok(Self {
             sync_commands: SyncCommands::new(ctx.clone()).context(
                 "Unable to create SyncCommands for the TextureLoader!",
@@ -241,32 +250,10 @@ impl TextureLoader {
         mipmaps: &[RgbaImage],
     ) -> Result<()> {
         let ctx = &self.ctx;
-        self.sync_commands.submit_and_wait(|command_buffer| unsafe {
-            ctx.cmd_pipeline_barrier(
-                command_buffer,
-                vk::PipelineStageFlags::TOP_OF_PIPE,
-                vk::PipelineStageFlags::TRANSFER,
-                vk::DependencyFlags::empty(),
-                &[],
-                &[],
-                &[vk::ImageMemoryBarrier {
-                    src_access_mask: vk::AccessFlags::empty(),
-                    dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
-                    old_layout: vk::ImageLayout::UNDEFINED,
-                    new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                    src_queue_family_index: ctx.graphics_queue_family_index,
-                    dst_queue_family_index: ctx.graphics_queue_family_index,
-                    image: texture.image().raw,
-                    subresource_range: vk::ImageSubresourceRange {
-                        aspect_mask: vk::ImageAspectFlags::COLOR,
-                        base_mip_level: 0,
-                        level_count: mipmaps.len() as u32,
-                        base_array_layer: 0,
-                        layer_count: 1,
-                    },
-                    ..Default::default()
-                }],
-            );
+        self.sync_commands.submit_and_wait(|command_buffer| {
+            Barriers::new()
+                .texture_discarding(texture, ImageUsage::TransferDst)
+                .record(ctx, command_buffer)?;
             let buffer_image_copies: Vec<vk::BufferImageCopy> = {
                 let mut offset: u64 = 0;
                 mipmaps
@@ -295,39 +282,21 @@ impl TextureLoader {
                     })
                     .collect()
             };
-            ctx.cmd_copy_buffer_to_image(
-                command_buffer,
-                self.transfer_buffer.buffer(),
-                texture.image().raw,
-                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                &buffer_image_copies,
-            );
-            ctx.cmd_pipeline_barrier(
-                command_buffer,
-                vk::PipelineStageFlags::TRANSFER,
-                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
-                vk::DependencyFlags::empty(),
-                &[],
-                &[],
-                &[vk::ImageMemoryBarrier {
-                    src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
-                    dst_access_mask: vk::AccessFlags::empty(),
-                    old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                    new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                    src_queue_family_index: ctx.graphics_queue_family_index,
-                    dst_queue_family_index: ctx.graphics_queue_family_index,
-                    image: texture.image().raw,
-                    subresource_range: vk::ImageSubresourceRange {
-                        aspect_mask: vk::ImageAspectFlags::COLOR,
-                        base_mip_level: 0,
-                        level_count: mipmaps.len() as u32,
-                        base_array_layer: 0,
-                        layer_count: 1,
-                    },
-                    ..Default::default()
-                }],
-            );
-            Ok(())
+            unsafe {
+                ctx.cmd_copy_buffer_to_image(
+                    command_buffer,
+                    self.transfer_buffer.buffer(),
+                    texture.image().raw,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &buffer_image_copies,
+                );
+            }
+            // Recording the final transition through Barriers keeps the
+            // texture's tracked access in sync, so later barriers preserve the
+            // uploaded pixels.
+            Barriers::new()
+                .texture(texture, ImageUsage::Sampled)
+                .record(ctx, command_buffer)
         }) // sync_commands end
     }
 }
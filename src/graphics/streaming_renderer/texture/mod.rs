@@ -2,9 +2,14 @@ mod atlas;
 mod loader;
 
 use {
-    crate::graphics::vulkan::{raii, OwnedBlock, VulkanContext},
+    crate::graphics::{
+        aspect_mask_for_format,
+        vulkan::{raii, OwnedBlock, VulkanContext},
+        ImageAccess,
+    },
     anyhow::{Context, Result},
     ash::vk,
+    std::sync::Mutex,
 };
 
 pub use self::{atlas::TextureAtlas, loader::TextureLoader};
@@ -15,11 +20,22 @@ pub use self::{atlas::TextureAtlas, loader::TextureLoader};
 ///
 /// Textures own their own Vulkan resources and will destroy them when dropped.
 /// The application is responsible for synchronizing access to Texture
-/// resources with the GPU and ensuring nothing is dropped early.
+/// resources with the GPU and ensuring nothing is dropped early. Textures that
+/// are replaced while frames are in flight can be passed to
+/// [FramesInFlight::defer_drop](crate::graphics::vulkan::FramesInFlight::defer_drop).
+///
+/// Textures remember the layout and access from their most recent barrier so
+/// transitions only need to name the next use. See
+/// [Barriers::texture](crate::graphics::Barriers::texture).
 pub struct Texture {
     mip_levels: u32,
+    samples: vk::SampleCountFlags,
     width: u32,
     height: u32,
+    format: vk::Format,
+    usage: vk::ImageUsageFlags,
+    aspect_mask: vk::ImageAspectFlags,
+    access: Mutex<ImageAccess>,
     image_view: raii::ImageView,
     image: raii::Image,
     block: OwnedBlock,
@@ -35,6 +51,9 @@ impl Texture {
         image_usage_flags: vk::ImageUsageFlags,
         memory_property_flags: vk::MemoryPropertyFlags,
         #[builder(default = 1)] mip_levels: u32,
+        /// Multisampled images must have a single mip level.
+        #[builder(default = vk::SampleCountFlags::TYPE_1)]
+        samples: vk::SampleCountFlags,
     ) -> Result<Self> {
         let (width, height) = dimensions;
 
@@ -51,7 +70,7 @@ impl Texture {
                 },
                 mip_levels,
                 array_layers: 1,
-                samples: vk::SampleCountFlags::TYPE_1,
+                samples,
                 tiling: vk::ImageTiling::OPTIMAL,
                 usage: image_usage_flags,
                 sharing_mode: vk::SharingMode::EXCLUSIVE,
@@ -64,8 +83,7 @@ impl Texture {
         )
         .context("Unable to create texture image!")?;
 
-        let is_depth = image_usage_flags
-            .contains(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT);
+        let aspect_mask = aspect_mask_for_format(format);
         let image_view = raii::ImageView::new(
             "Texture Image View",
             ctx.device.clone(),
@@ -75,10 +93,13 @@ impl Texture {
                 format,
                 components: vk::ComponentMapping::default(),
                 subresource_range: vk::ImageSubresourceRange {
-                    aspect_mask: if is_depth {
+                    // views can only include the depth or the stencil aspect
+                    aspect_mask: if aspect_mask
+                        .contains(vk::ImageAspectFlags::DEPTH)
+                    {
                         vk::ImageAspectFlags::DEPTH
                     } else {
-                        vk::ImageAspectFlags::COLOR
+                        aspect_mask
                     },
                     base_mip_level: 0,
                     level_count: mip_levels,
@@ -92,8 +113,13 @@ impl Texture {
 
         Ok(Self {
             mip_levels,
+            samples,
             width,
             height,
+            format,
+            usage: image_usage_flags,
+            aspect_mask,
+            access: Mutex::new(ImageAccess::UNDEFINED),
             image_view,
             image,
             block,
@@ -130,6 +156,59 @@ impl Texture {
         }
     }
 
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    /// The usage flags the texture's image was created with.
+    pub fn usage(&self) -> vk::ImageUsageFlags {
+        self.usage
+    }
+
+    pub fn mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
+    /// The number of samples per texel.
+    pub fn samples(&self) -> vk::SampleCountFlags {
+        self.samples
+    }
+
+    /// The image aspects derived from the texture's format.
+    pub fn aspect_mask(&self) -> vk::ImageAspectFlags {
+        self.aspect_mask
+    }
+
+    /// A subresource range covering every mip level of the texture.
+    pub fn subresource_range(&self) -> vk::ImageSubresourceRange {
+        vk::ImageSubresourceRange {
+            aspect_mask: self.aspect_mask,
+            base_mip_level: 0,
+            level_count: self.mip_levels,
+            base_array_layer: 0,
+            layer_count: 1,
+        }
+    }
+
+    /// Returns the layout and access from the texture's most recent barrier.
+    pub fn access(&self) -> ImageAccess {
+        *self.access.lock().unwrap()
+    }
+
+    /// Replaces the tracked layout and access, returning the previous value.
+    ///
+    /// This does not record a barrier. It is useful when the texture's layout
+    /// is changed by something other than a barrier, like a render pass.
+    pub fn set_access(&self, access: ImageAccess) -> ImageAccess {
+        std::mem::replace(&mut *self.access.lock().unwrap(), access)
+    }
+
+    /// Records a pipeline barrier with explicit layouts, access masks, and
+    /// stage masks.
+    ///
+    /// The texture's tracked access is updated to match the destination of the
+    /// barrier. Prefer [Barriers](crate::graphics::Barriers) which derives the
+    /// source from the tracked state.
     #[builder]
     pub fn pipeline_barrier(
         &self,
@@ -142,10 +221,15 @@ impl Texture {
         src_stage_mask: vk::PipelineStageFlags,
         dst_stage_mask: vk::PipelineStageFlags,
     ) {
-        let is_depth_texture = dst_access_mask
-            .contains(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ)
-            || dst_access_mask
-                .contains(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE);
+        self.set_access(ImageAccess {
+            stage_mask: vk::PipelineStageFlags2::from_raw(
+                dst_stage_mask.as_raw() as u64,
+            ),
+            access_mask: vk::AccessFlags2::from_raw(
+                dst_access_mask.as_raw() as u64
+            ),
+            layout: new_layout,
+        });
 
         let image_memory_barrier = vk::ImageMemoryBarrier {
             old_layout,
@@ -153,17 +237,7 @@ impl Texture {
             src_access_mask,
             dst_access_mask,
             image: self.image.raw,
-            subresource_range: vk::ImageSubresourceRange {
-                aspect_mask: if is_depth_texture {
-                    vk::ImageAspectFlags::DEPTH
-                } else {
-                    vk::ImageAspectFlags::COLOR
-                },
-                base_mip_level: 0,
-                level_count: self.mip_levels,
-                base_array_layer: 0,
-                layer_count: 1,
-            },
+            subresource_range: self.subresource_range(),
             ..Default::default()
         };
         unsafe {
@@ -101,4 +101,33 @@ where
 
         Ok(())
     }
+
+    /// Reads data from the GPU memory starting at the given index.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the caller must synchronize access to the region being read. E.g. any
+    ///   GPU writes must be complete and made available to the host.
+    pub unsafe fn read_data(
+        &self,
+        start_index: usize,
+        data: &mut [DataT],
+    ) -> Result<()> {
+        if start_index + data.len() > self.count {
+            bail!(
+                "Out of bounds read attempted! {}/{}",
+                start_index + data.len(),
+                self.count
+            );
+        }
+
+        std::ptr::copy_nonoverlapping(
+            (self.block.mapped_ptr() as *const DataT).add(start_index),
+            data.as_mut_ptr(),
+            data.len(),
+        );
+
+        Ok(())
+    }
 }
@@ -1,4 +1,8 @@
 mod cpu_buffer;
+mod per_frame_buffer;
 mod uniform_buffer;
 
-pub use self::{cpu_buffer::CPUBuffer, uniform_buffer::UniformBuffer};
+pub use self::{
+    cpu_buffer::CPUBuffer, per_frame_buffer::PerFrameBuffer,
+    uniform_buffer::UniformBuffer,
+};
@@ -0,0 +1,275 @@
+use {
+    crate::{
+        graphics::vulkan::{
+            raii, Frame, FramesInFlight, OwnedBlock, VulkanContext,
+        },
+        unwrap_here,
+    },
+    anyhow::{bail, Result},
+    ash::vk,
+    std::marker::PhantomData,
+};
+
+/// A CPU accessible buffer with a separate region for each frame-in-flight.
+///
+/// Each frame's region holds `capacity` elements of `DataT` and begins at an
+/// offset which satisfies the device's alignment requirements for the
+/// buffer's usage. This means each region can be bound to its own uniform or
+/// storage buffer descriptor, or addressed directly with a buffer device
+/// address.
+#[derive(Debug)]
+pub struct PerFrameBuffer<DataT: Sized + Copy> {
+    buffer: raii::Buffer,
+    block: OwnedBlock,
+    usage: vk::BufferUsageFlags,
+    aligned_frame_size: u64,
+    frame_count: usize,
+    capacity: usize,
+    buffer_device_address: vk::DeviceAddress,
+    _phantom_data: PhantomData<DataT>,
+}
+
+impl<DataT> PerFrameBuffer<DataT>
+where
+    DataT: Sized + Copy,
+{
+    /// Allocates a buffer with `frame_count` regions, each with space for
+    /// `capacity` copies of `DataT`.
+    ///
+    /// The alignment of each region is derived from `usage`:
+    /// - `UNIFORM_BUFFER` respects `min_uniform_buffer_offset_alignment`
+    /// - `STORAGE_BUFFER` respects `min_storage_buffer_offset_alignment`
+    /// - all regions are at least aligned to `align_of::<DataT>()`
+    pub fn allocate(
+        cxt: &VulkanContext,
+        frame_count: usize,
+        capacity: usize,
+        usage: vk::BufferUsageFlags,
+    ) -> Result<Self> {
+        let limits = {
+            let mut physical_device_properties =
+                vk::PhysicalDeviceProperties2::default();
+            unsafe {
+                cxt.instance.get_physical_device_properties2(
+                    cxt.physical_device,
+                    &mut physical_device_properties,
+                );
+            }
+            physical_device_properties.properties.limits
+        };
+
+        let alignment = {
+            let mut alignment = align_of::<DataT>() as u64;
+            if usage.contains(vk::BufferUsageFlags::UNIFORM_BUFFER) {
+                alignment =
+                    alignment.max(limits.min_uniform_buffer_offset_alignment);
+            }
+            if usage.contains(vk::BufferUsageFlags::STORAGE_BUFFER) {
+                alignment =
+                    alignment.max(limits.min_storage_buffer_offset_alignment);
+            }
+            alignment
+        };
+
+        // Vulkan buffers cannot be empty, so zero-sized data (like `()`) still
+        // reserves one aligned unit per frame.
+        let aligned_frame_size = aligned_size(
+            ((capacity * size_of::<DataT>()) as u64).max(1),
+            alignment,
+        );
+        let buffer_size_in_bytes = aligned_frame_size * frame_count as u64;
+
+        let (block, buffer) = unwrap_here!(
+            "Allocate host visible and coherent memory",
+            OwnedBlock::allocate_buffer(
+                cxt.allocator.clone(),
+                &vk::BufferCreateInfo {
+                    size: buffer_size_in_bytes,
+                    usage,
+                    sharing_mode: vk::SharingMode::EXCLUSIVE,
+                    queue_family_index_count: 1,
+                    p_queue_family_indices: &cxt.graphics_queue_family_index,
+                    ..Default::default()
+                },
+                vk::MemoryPropertyFlags::HOST_VISIBLE
+                    | vk::MemoryPropertyFlags::HOST_COHERENT,
+            )
+        );
+
+        let buffer_device_address = if usage
+            .contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS)
+        {
+            unsafe {
+                cxt.get_buffer_device_address(&vk::BufferDeviceAddressInfo {
+                    buffer: buffer.raw,
+                    ..Default::default()
+                })
+            }
+        } else {
+            0
+        };
+
+        Ok(Self {
+            buffer,
+            block,
+            usage,
+            aligned_frame_size,
+            frame_count,
+            capacity,
+            buffer_device_address,
+            _phantom_data: PhantomData,
+        })
+    }
+
+    /// Allocates a buffer with one region for each frame in flight.
+    pub fn allocate_per_frame(
+        cxt: &VulkanContext,
+        frames_in_flight: &FramesInFlight,
+        capacity: usize,
+        usage: vk::BufferUsageFlags,
+    ) -> Result<Self> {
+        Self::allocate(cxt, frames_in_flight.frame_count(), capacity, usage)
+    }
+
+    /// Returns a non-owning copy of the Vulkan buffer handle.
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer.raw
+    }
+
+    /// Returns the usage flags the buffer was created with.
+    pub fn usage(&self) -> vk::BufferUsageFlags {
+        self.usage
+    }
+
+    /// The number of frame regions in the buffer.
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// The maximum number of elements that can be written to each frame's
+    /// region.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The size, in bytes, of the data held by each frame's region.
+    ///
+    /// This is the value to use as the range when binding a frame's region to
+    /// a descriptor. It does not include the padding needed for alignment.
+    pub fn frame_size_in_bytes(&self) -> u64 {
+        (self.capacity * size_of::<DataT>()) as u64
+    }
+
+    /// Returns the byte-offset into the buffer for the corresponding Frame's
+    /// data.
+    pub fn offset_for_index(&self, frame_index: usize) -> u64 {
+        frame_index as u64 * self.aligned_frame_size
+    }
+
+    /// Returns the device address for the start of the corresponding Frame's
+    /// data.
+    ///
+    /// Only valid if the buffer was created with the
+    /// `vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS` flag.
+    pub fn device_address_for_index(
+        &self,
+        frame_index: usize,
+    ) -> vk::DeviceAddress {
+        if self.buffer_device_address == 0 {
+            return 0;
+        }
+        self.buffer_device_address + self.offset_for_index(frame_index)
+    }
+
+    /// Returns the device address for the current Frame's data.
+    ///
+    /// Only valid if the buffer was created with the
+    /// `vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS` flag.
+    pub fn device_address_for_frame(&self, frame: &Frame) -> vk::DeviceAddress {
+        self.device_address_for_index(frame.frame_index())
+    }
+
+    /// Updates GPU memory with the provided data for the current frame.
+    ///
+    /// Data is written to the start of the frame's region.
+    pub fn update_frame_data(
+        &mut self,
+        frame: &Frame,
+        data: &[DataT],
+    ) -> Result<()> {
+        // SAFE: because borrowing the Frame means that no pending graphics
+        // commands can still reference the targeted region of the
+        // buffer.
+        unsafe { self.write_indexed(frame.frame_index(), 0, data) }
+    }
+
+    /// Writes data into the frame's region, starting at element
+    /// `start_index`.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the caller must synchronize access to the region being written.
+    pub unsafe fn write_indexed(
+        &mut self,
+        frame_index: usize,
+        start_index: usize,
+        data: &[DataT],
+    ) -> Result<()> {
+        if frame_index >= self.frame_count {
+            bail!(
+                "Attempt to write to frame {}/{}",
+                frame_index,
+                self.frame_count
+            );
+        }
+        if start_index + data.len() > self.capacity {
+            bail!(
+                "Out of bounds write attempted! {}/{}",
+                start_index + data.len(),
+                self.capacity
+            );
+        }
+
+        let offset = self.offset_for_index(frame_index) as isize;
+        std::ptr::copy_nonoverlapping(
+            data.as_ptr(),
+            (self.block.mapped_ptr().byte_offset(offset) as *mut DataT)
+                .add(start_index),
+            data.len(),
+        );
+
+        Ok(())
+    }
+}
+
+/// Rounds `size` up to the nearest multiple of `alignment`.
+fn aligned_size(size: u64, alignment: u64) -> u64 {
+    let alignment = alignment.max(1);
+    size.div_ceil(alignment) * alignment
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn aligned_size_should_not_pad_aligned_values() {
+        assert_eq!(aligned_size(256, 256), 256);
+        assert_eq!(aligned_size(512, 256), 512);
+        assert_eq!(aligned_size(64, 16), 64);
+    }
+
+    #[test]
+    pub fn aligned_size_should_round_up_to_the_next_multiple() {
+        assert_eq!(aligned_size(1, 256), 256);
+        assert_eq!(aligned_size(257, 256), 512);
+        assert_eq!(aligned_size(20, 16), 32);
+    }
+
+    #[test]
+    pub fn aligned_size_should_treat_zero_alignment_as_unaligned() {
+        assert_eq!(aligned_size(13, 0), 13);
+        assert_eq!(aligned_size(13, 1), 13);
+    }
+}
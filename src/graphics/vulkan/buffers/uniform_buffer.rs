@@ -1,24 +1,19 @@
 use {
-    crate::{
-        graphics::vulkan::{
-            raii, Frame, FramesInFlight, OwnedBlock, VulkanContext,
-        },
-        unwrap_here,
+    crate::graphics::vulkan::{
+        Frame, FramesInFlight, PerFrameBuffer, VulkanContext,
     },
-    anyhow::{bail, Result},
+    anyhow::Result,
     ash::vk,
-    std::marker::PhantomData,
 };
 
 /// A CPU accessible buffer with some convenience functions for uploading
 /// per-frame data.
+///
+/// This is a [PerFrameBuffer] holding exactly one `DataT` per frame, with
+/// UNIFORM_BUFFER usage.
 #[derive(Debug)]
 pub struct UniformBuffer<DataT: Sized + Copy> {
-    buffer: raii::Buffer,
-    block: OwnedBlock,
-    aligned_unit_size: usize,
-    count: usize,
-    _phantom_data: PhantomData<DataT>,
+    buffer: PerFrameBuffer<DataT>,
 }
 
 impl<DataT> UniformBuffer<DataT>
@@ -28,49 +23,13 @@ where
     /// Allocates a buffer with enough space for count copies of `DataT` aligned
     /// such that each copy can be bound to a separate descriptor set.
     pub fn allocate(cxt: &VulkanContext, count: usize) -> Result<Self> {
-        // compute the aligned size for each element in the buffer
-        let properties = {
-            let mut physical_device_properties =
-                vk::PhysicalDeviceProperties2::default();
-            unsafe {
-                cxt.instance.get_physical_device_properties2(
-                    cxt.physical_device,
-                    &mut physical_device_properties,
-                );
-            }
-            physical_device_properties.properties
-        };
-        let aligned_unit_size: u64 = {
-            let count = size_of::<DataT>() as u64
-                / properties.limits.min_uniform_buffer_offset_alignment;
-            (count + 1) * properties.limits.min_uniform_buffer_offset_alignment
-        };
-
-        let buffer_size_in_bytes = aligned_unit_size * count as u64;
-
-        let (block, buffer) = unwrap_here!(
-            "Allocate host visible and coherent memory",
-            OwnedBlock::allocate_buffer(
-                cxt.allocator.clone(),
-                &vk::BufferCreateInfo {
-                    size: buffer_size_in_bytes,
-                    usage: vk::BufferUsageFlags::UNIFORM_BUFFER,
-                    sharing_mode: vk::SharingMode::EXCLUSIVE,
-                    queue_family_index_count: 1,
-                    p_queue_family_indices: &cxt.graphics_queue_family_index,
-                    ..Default::default()
-                },
-                vk::MemoryPropertyFlags::HOST_VISIBLE
-                    | vk::MemoryPropertyFlags::HOST_COHERENT,
-            )
-        );
-
         Ok(Self {
-            buffer,
-            block,
-            count,
-            aligned_unit_size: aligned_unit_size as usize,
-            _phantom_data: PhantomData,
+            buffer: PerFrameBuffer::allocate(
+                cxt,
+                count,
+                1,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+            )?,
         })
     }
 
@@ -85,7 +44,7 @@ where
 
     /// Returns a non-owning copy of the Vulkan buffer handle.
     pub fn buffer(&self) -> vk::Buffer {
-        self.buffer.raw
+        self.buffer.buffer()
     }
 
     /// Updates GPU memory with the provided data for the current frame.
@@ -94,16 +53,13 @@ where
         frame: &Frame,
         data: DataT,
     ) -> Result<()> {
-        // SAFE: because borrowing the Frame means that no pending graphics
-        // commands can still reference the targeted region of the
-        // buffer.
-        unsafe { self.write_indexed(frame.frame_index(), data) }
+        self.buffer.update_frame_data(frame, &[data])
     }
 
     /// Returns the byte-offset into the buffer for the corresponding Frame's
     /// data.
     pub fn offset_for_index(&self, frame_index: usize) -> u64 {
-        (frame_index * self.aligned_unit_size) as u64
+        self.buffer.offset_for_index(frame_index)
     }
 
     /// Writes data into the GPU memory at the given index.
@@ -117,17 +73,6 @@ where
         index: usize,
         data: DataT,
     ) -> Result<()> {
-        if index >= self.count {
-            bail!("Attempt to write to index {}/{}", index, self.count);
-        }
-
-        let offset = self.offset_for_index(index) as isize;
-        std::ptr::copy_nonoverlapping(
-            &data,
-            self.block.mapped_ptr().byte_offset(offset) as *mut DataT,
-            1,
-        );
-
-        Ok(())
+        self.buffer.write_indexed(index, 0, &[data])
     }
 }
@@ -9,6 +9,7 @@ use {
 };
 
 const VK_EXT_DEBUG_UTILS: &CStr = c"VK_EXT_debug_utils";
+const VK_EXT_SWAPCHAIN_COLORSPACE: &CStr = c"VK_EXT_swapchain_colorspace";
 
 /// The logical Vulkan instance.
 ///
@@ -24,23 +25,49 @@ pub struct Instance {
 
 impl Instance {
     /// Create a new Vulkan instance for the given GLFW window.
+    ///
+    /// VK_EXT_swapchain_colorspace is enabled when available so the swapchain
+    /// can use extended color spaces such as HDR10 or scRGB.
     pub fn for_window(
         app_name: impl AsRef<str>,
         window: &Window,
     ) -> Result<Self> {
-        let extensions = ash_window::enumerate_required_extensions(
+        let mut extensions = ash_window::enumerate_required_extensions(
             window
                 .display_handle()
                 .with_context(|| "Unable to fetch display handle!")?
                 .as_raw(),
-        )?;
-        Self::new(app_name, extensions)
+        )?
+        .to_vec();
+        let entry = load_entry()?;
+        let available_extensions =
+            unwrap_here!("Enumerate instance extensions", unsafe {
+                entry.enumerate_instance_extension_properties(None)
+            });
+        let has_swapchain_colorspace =
+            available_extensions.iter().any(|properties| {
+                properties.extension_name_as_c_str()
+                    == Ok(VK_EXT_SWAPCHAIN_COLORSPACE)
+            });
+        if has_swapchain_colorspace {
+            extensions.push(VK_EXT_SWAPCHAIN_COLORSPACE.as_ptr());
+        }
+        Self::with_entry(entry, app_name, &extensions)
     }
 
     /// Create a new Vulkan instance.
     pub fn new(
         app_name: impl AsRef<str>,
         extensions: &[*const i8],
+    ) -> Result<Self> {
+        Self::with_entry(load_entry()?, app_name, extensions)
+    }
+
+    /// Create a new Vulkan instance using an already-loaded library entry.
+    fn with_entry(
+        entry: ash::Entry,
+        app_name: impl AsRef<str>,
+        extensions: &[*const i8],
     ) -> Result<Self> {
         let ptrs = {
             let mut ptrs = extensions.to_vec();
@@ -69,7 +96,7 @@ impl Instance {
 
         let ash = unwrap_here!(
             "Create Vulkan instance",
-            raii::Instance::new(&create_info)
+            raii::Instance::new(entry, &create_info)
         );
 
         let debug_utils = unwrap_here!(
@@ -88,6 +115,13 @@ impl Instance {
     }
 }
 
+/// Loads the Vulkan library entry.
+fn load_entry() -> Result<ash::Entry> {
+    Ok(unwrap_here!("Create the Vulkan loader", unsafe {
+        ash::Entry::load()
+    }))
+}
+
 impl std::fmt::Debug for Instance {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Instance")
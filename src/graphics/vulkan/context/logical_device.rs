@@ -7,9 +7,12 @@ use {
 
 /// Create the Vulkan device with all required features and queues for this
 /// application.
+///
+/// The graphics queue must also support presenting to `surface_khr` when it's
+/// given. The swapchain extension is only enabled with a surface.
 pub fn create_logical_device(
     instance: &Instance,
-    surface_khr: &raii::Surface,
+    surface_khr: Option<&raii::Surface>,
     physical_device: vk::PhysicalDevice,
     required_device_features: RequiredDeviceFeatures,
 ) -> Result<(Arc<raii::Device>, u32)> {
@@ -36,16 +39,17 @@ pub fn create_logical_device(
         .iter()
         .enumerate()
         .find(|(index, properties)| {
-            let supports_present = unsafe {
-                surface_khr
-                    .ext
-                    .get_physical_device_surface_support(
-                        physical_device,
-                        *index as u32,
-                        surface_khr.raw,
-                    )
-                    .unwrap_or(false)
-            };
+            let supports_present =
+                surface_khr.is_none_or(|surface_khr| unsafe {
+                    surface_khr
+                        .ext
+                        .get_physical_device_surface_support(
+                            physical_device,
+                            *index as u32,
+                            surface_khr.raw,
+                        )
+                        .unwrap_or(false)
+                });
             supports_present
                 && properties.queue_flags.contains(QueueFlags::GRAPHICS)
         })
@@ -58,7 +62,10 @@ pub fn create_logical_device(
         p_queue_priorities: queue_priorities.as_ptr(),
         ..Default::default()
     }];
-    let extensions = [ash::khr::swapchain::NAME.as_ptr()];
+    let extensions = match surface_khr {
+        Some(_) => vec![ash::khr::swapchain::NAME.as_ptr()],
+        None => vec![],
+    };
 
     let logical_device = {
         let mut maintenence4_features =
@@ -74,6 +81,11 @@ pub fn create_logical_device(
             vk::PhysicalDeviceVulkan12Features {
                 ..required_device_features.physical_device_vulkan12_features
             };
+        let mut physical_device_synchronization2_features =
+            vk::PhysicalDeviceSynchronization2Features {
+                ..required_device_features
+                    .physical_device_synchronization2_features
+            };
 
         // pack the desired features
         let mut features = vk::PhysicalDeviceFeatures2 {
@@ -84,7 +96,8 @@ pub fn create_logical_device(
         }
         .push_next(&mut physical_device_vulkan12_features)
         .push_next(&mut physical_device_dynamic_rendering_features)
-        .push_next(&mut maintenence4_features);
+        .push_next(&mut maintenence4_features)
+        .push_next(&mut physical_device_synchronization2_features);
 
         // create the device using the requested features
         let create_info = vk::DeviceCreateInfo {
@@ -7,7 +7,7 @@ use {
         graphics::vulkan::{raii, Allocator},
         unwrap_here,
     },
-    anyhow::Result,
+    anyhow::{bail, Result},
     ash::vk::{self},
     std::sync::Arc,
     winit::window::Window,
@@ -30,6 +30,8 @@ pub struct RequiredDeviceFeatures {
         vk::PhysicalDeviceVulkan12Features<'static>,
     pub physical_device_dynamic_rendering_features:
         vk::PhysicalDeviceDynamicRenderingFeatures<'static>,
+    pub physical_device_synchronization2_features:
+        vk::PhysicalDeviceSynchronization2Features<'static>,
 }
 
 /// The Vulkan context is the logical handle for all Vulkan operations within
@@ -39,18 +41,27 @@ pub struct RequiredDeviceFeatures {
 /// required queues and a device memory allocator.
 pub struct VulkanContext {
     pub instance: Instance,
-    pub surface_khr: Arc<raii::Surface>,
+
+    /// The window surface the device presents to. This is None for headless
+    /// contexts, see [Self::headless].
+    pub surface_khr: Option<Arc<raii::Surface>>,
     pub physical_device: vk::PhysicalDevice,
     pub device: Arc<raii::Device>,
 
     /// The queue family index for the graphics + present queue.
     pub graphics_queue_family_index: u32,
 
-    /// The graphics queue supports GRAPHICS and presentation operations.
+    /// The graphics queue supports GRAPHICS and, unless the context is
+    /// headless, presentation operations.
     pub graphics_queue: vk::Queue,
 
     /// The device memory allocator.
     pub allocator: Arc<Allocator>,
+
+    draw_indirect_count: bool,
+    fill_mode_non_solid: bool,
+    pipeline_statistics_query: bool,
+    synchronization2: bool,
 }
 
 impl VulkanContext {
@@ -70,20 +81,65 @@ impl VulkanContext {
             raii::Surface::for_window(instance.ash.clone(), window)
         );
 
+        Self::with_instance(
+            instance,
+            Some(surface_khr),
+            required_device_features,
+        )
+    }
+
+    /// Creates a new Vulkan Context without a window or surface, e.g. to
+    /// render offscreen.
+    ///
+    /// The instance is created without surface extensions and the device
+    /// isn't required to support presentation, so headless contexts can only
+    /// be used with offscreen swapchains. See [super::Swapchain::offscreen].
+    pub fn headless(
+        required_device_features: RequiredDeviceFeatures,
+    ) -> Result<Arc<Self>> {
+        let instance = unwrap_here!(
+            "Create headless Vulkan instance",
+            Instance::new("demo-vk", &[])
+        );
+        Self::with_instance(instance, None, required_device_features)
+    }
+
+    /// Picks a device for the instance and, if given, the surface.
+    fn with_instance(
+        instance: Instance,
+        surface_khr: Option<Arc<raii::Surface>>,
+        required_device_features: RequiredDeviceFeatures,
+    ) -> Result<Arc<Self>> {
         let physical_device = unwrap_here!(
             "Pick a suitable device for the application",
             physical_device::pick_suitable_device(
                 &instance,
-                &surface_khr,
+                surface_khr.as_deref(),
                 &required_device_features,
             )
         );
 
+        let draw_indirect_count = required_device_features
+            .physical_device_vulkan12_features
+            .draw_indirect_count
+            == vk::TRUE;
+        let fill_mode_non_solid = required_device_features
+            .physical_device_features
+            .fill_mode_non_solid
+            == vk::TRUE;
+        let pipeline_statistics_query = required_device_features
+            .physical_device_features
+            .pipeline_statistics_query
+            == vk::TRUE;
+        let synchronization2 = required_device_features
+            .physical_device_synchronization2_features
+            .synchronization2
+            == vk::TRUE;
         let (device, graphics_queue_family_index) = unwrap_here!(
             "Create a logical device for the chosen physical device",
             logical_device::create_logical_device(
                 &instance,
-                &surface_khr,
+                surface_khr.as_deref(),
                 physical_device,
                 required_device_features,
             )
@@ -105,8 +161,67 @@ impl VulkanContext {
             graphics_queue_family_index,
             graphics_queue,
             allocator: Arc::new(allocator),
+            draw_indirect_count,
+            fill_mode_non_solid,
+            pipeline_statistics_query,
+            synchronization2,
         }))
     }
+
+    /// Returns true when the device was created with the Vulkan 1.2
+    /// `draw_indirect_count` feature enabled.
+    pub fn draw_indirect_count_enabled(&self) -> bool {
+        self.draw_indirect_count
+    }
+
+    /// Returns true when the device was created with the
+    /// `fill_mode_non_solid` feature enabled.
+    pub fn fill_mode_non_solid_enabled(&self) -> bool {
+        self.fill_mode_non_solid
+    }
+
+    /// Returns true when the device was created with the
+    /// `pipeline_statistics_query` feature enabled.
+    pub fn pipeline_statistics_query_enabled(&self) -> bool {
+        self.pipeline_statistics_query
+    }
+
+    /// Returns true when the device was created with the Vulkan 1.3
+    /// `synchronization2` feature enabled.
+    pub fn synchronization2_enabled(&self) -> bool {
+        self.synchronization2
+    }
+
+    /// Creates a surface for another window which shares this context's
+    /// device, e.g. to render to several windows at once.
+    ///
+    /// The window must belong to the same event loop as the window used to
+    /// create the context. Fails if the graphics queue can't present to the
+    /// new surface, or if the context is headless.
+    pub fn create_surface(
+        &self,
+        window: &Window,
+    ) -> Result<Arc<raii::Surface>> {
+        if self.surface_khr.is_none() {
+            bail!("Headless Vulkan contexts can't create window surfaces");
+        }
+        let surface = unwrap_here!(
+            "Create Vulkan surface for an additional window",
+            raii::Surface::for_window(self.instance.ash.clone(), window)
+        );
+        let supported =
+            unwrap_here!("Check surface presentation support", unsafe {
+                surface.ext.get_physical_device_surface_support(
+                    self.physical_device,
+                    self.graphics_queue_family_index,
+                    surface.raw,
+                )
+            });
+        if !supported {
+            bail!("The graphics queue cannot present to the window's surface");
+        }
+        Ok(surface)
+    }
 }
 
 impl std::ops::Deref for VulkanContext {
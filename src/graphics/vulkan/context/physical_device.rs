@@ -9,9 +9,12 @@ use {
 
 /// Select a physical device based on the application's requried features and
 /// properties.
+///
+/// Devices must be able to present to `surface_khr` when it's given. Without a
+/// surface, devices are only checked for features and graphics queues.
 pub fn pick_suitable_device(
     instance: &Instance,
-    surface_khr: &raii::Surface,
+    surface_khr: Option<&raii::Surface>,
     required_device_features: &RequiredDeviceFeatures,
 ) -> Result<vk::PhysicalDevice> {
     let physical_devices = unwrap_here!("Enumerate physical devices", unsafe {
@@ -45,11 +48,18 @@ pub fn pick_suitable_device(
             required_device_features,
         );
         let has_queues = has_required_queues(instance, physical_device);
-        let has_extensions = has_required_extensions(instance, physical_device);
-        let has_surface_formats = unwrap_here!(
-            "Check physical device surface formats",
-            has_required_surface_formats(surface_khr, physical_device)
-        );
+        let (has_extensions, has_surface_formats) = match surface_khr {
+            Some(surface_khr) => (
+                has_required_extensions(instance, physical_device),
+                unwrap_here!(
+                    "Check physical device surface formats",
+                    has_required_surface_formats(surface_khr, physical_device)
+                ),
+            ),
+            // presentation extensions and formats aren't needed without a
+            // surface
+            None => (true, true),
+        };
 
         log::trace!(
             indoc::indoc! {"
@@ -165,11 +175,14 @@ fn has_required_features(
         vk::PhysicalDeviceDynamicRenderingFeatures::default();
     let mut actual_vulkan12_features =
         vk::PhysicalDeviceVulkan12Features::default();
+    let mut actual_synchronization2_features =
+        vk::PhysicalDeviceSynchronization2Features::default();
     let actual_features = unsafe {
         let mut features = vk::PhysicalDeviceFeatures2::default()
             .push_next(&mut actual_vulkan12_features)
             .push_next(&mut actual_dynamic_rendering_features)
-            .push_next(&mut actual_maintenenc4_features);
+            .push_next(&mut actual_maintenenc4_features)
+            .push_next(&mut actual_synchronization2_features);
         instance.get_physical_device_features2(physical_device, &mut features);
         features.features
     };
@@ -202,6 +215,13 @@ fn has_required_features(
         dynamic_rendering
     );
 
+    // check for synchronization2 support
+    check!(
+        required_device_features.physical_device_synchronization2_features,
+        actual_synchronization2_features,
+        synchronization2
+    );
+
     macro_rules! check_feature {
         ($name:ident) => {
             check!(
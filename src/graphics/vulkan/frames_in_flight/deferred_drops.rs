@@ -0,0 +1,71 @@
+use std::{any::Any, collections::VecDeque};
+
+/// A queue of resources which are dropped once a frame value completes.
+#[derive(Default)]
+pub(super) struct DeferredDrops {
+    queue: VecDeque<(u64, Box<dyn Any + Send>)>,
+}
+
+impl std::fmt::Debug for DeferredDrops {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeferredDrops")
+            .field("len", &self.queue.len())
+            .finish()
+    }
+}
+
+impl DeferredDrops {
+    /// Queues the resource to be dropped once `frame_value` completes.
+    pub fn push(&mut self, frame_value: u64, resource: Box<dyn Any + Send>) {
+        self.queue.push_back((frame_value, resource));
+    }
+
+    /// Drops every resource queued with a frame value at or below
+    /// `completed_frame_value`.
+    pub fn drop_completed(&mut self, completed_frame_value: u64) {
+        self.queue
+            .retain(|(value, _)| *value > completed_frame_value);
+    }
+
+    /// Drops every queued resource.
+    pub fn clear(&mut self) {
+        self.queue.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, std::sync::Arc};
+
+    #[test]
+    pub fn resources_should_drop_after_their_frame_completes() {
+        let first = Arc::new(());
+        let second = Arc::new(());
+        let mut drops = DeferredDrops::default();
+        drops.push(3, Box::new(first.clone()));
+        drops.push(5, Box::new(second.clone()));
+
+        drops.drop_completed(2);
+        assert_eq!(Arc::strong_count(&first), 2);
+
+        drops.drop_completed(4);
+        assert_eq!(Arc::strong_count(&first), 1);
+        assert_eq!(Arc::strong_count(&second), 2);
+
+        drops.drop_completed(5);
+        assert_eq!(Arc::strong_count(&second), 1);
+    }
+
+    #[test]
+    pub fn later_frame_values_should_not_delay_earlier_drops() {
+        let later = Arc::new(());
+        let earlier = Arc::new(());
+        let mut drops = DeferredDrops::default();
+        drops.push(8, Box::new(later.clone()));
+        drops.push(6, Box::new(earlier.clone()));
+
+        drops.drop_completed(6);
+        assert_eq!(Arc::strong_count(&earlier), 1);
+        assert_eq!(Arc::strong_count(&later), 2);
+    }
+}
@@ -1,14 +1,17 @@
+mod deferred_drops;
+
 use {
+    self::deferred_drops::DeferredDrops,
     crate::{
         graphics::vulkan::{
-            raii, AcquireImageStatus, PresentImageStatus, Swapchain,
+            raii, AcquireImageStatus, GpuTicket, PresentImageStatus, Swapchain,
             VulkanContext,
         },
         unwrap_here,
     },
-    anyhow::{Context, Result},
+    anyhow::{bail, Context, Result},
     ash::vk::{self, Handle},
-    std::{ffi::CString, sync::Arc},
+    std::{any::Any, ffi::CString, sync::Arc},
 };
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -32,6 +35,7 @@ pub struct Frame {
     frame_index: usize,
     swapchain_image: vk::Image,
     swapchain_image_view: vk::ImageView,
+    frame_value: u64,
 }
 
 impl Frame {
@@ -54,6 +58,15 @@ impl Frame {
     pub fn swapchain_image_view(&self) -> vk::ImageView {
         self.swapchain_image_view
     }
+
+    /// The monotonically increasing value which identifies this frame.
+    ///
+    /// Once [FramesInFlight::completed_frame_value] is greater than or equal
+    /// to this value, all of the frame's graphics commands have finished
+    /// executing.
+    pub fn frame_value(&self) -> u64 {
+        self.frame_value
+    }
 }
 
 /// Used to track the status of each [FrameSync] instance.
@@ -74,11 +87,35 @@ enum FrameSyncStatus {
 struct FrameSync {
     status: FrameSyncStatus,
     swapchain_image_acquired: raii::Semaphore,
-    graphics_commands_complete: raii::Fence,
+
+    /// Signaled when the frame's graphics commands complete. Only present when
+    /// the FramesInFlight is not using a timeline semaphore.
+    graphics_commands_complete: Option<raii::Fence>,
+
+    /// The frame value of the most recent frame started with this FrameSync.
+    frame_value: u64,
+
+    /// Tickets the frame's commands wait on before the given stage. Tickets
+    /// are held until the frame completes so their semaphores stay alive.
+    ticket_waits: Vec<(GpuTicket, vk::PipelineStageFlags)>,
+
     command_pool: raii::CommandPool,
     command_buffer: vk::CommandBuffer,
 }
 
+impl FrameSync {
+    /// Returns the frame's graphics commands fence.
+    ///
+    /// Fails when the FramesInFlight tracks completion with a timeline
+    /// semaphore.
+    fn fence(&self) -> Result<vk::Fence> {
+        self.graphics_commands_complete
+            .as_ref()
+            .map(|fence| fence.raw)
+            .context("Frame fences are unused with a timeline semaphore")
+    }
+}
+
 /// The fundamental synchronization mechanism for an application with N "frames
 /// in flight" where frame K's graphics command buffer can be recorded while
 /// frames K+1, K+2, ... K+N are all in-progress rendering on the GPU.
@@ -94,24 +131,85 @@ struct FrameSync {
 /// use [Frame::frame_index] to select frame-specific resources. This is certain
 /// to be safe because the only time the application has a [Frame] instance is
 /// when that frame's previous commands have finished executing.
+///
+/// Every started frame is assigned a monotonically increasing frame value (see
+/// [Frame::frame_value]). Other systems can use
+/// [Self::completed_frame_value] and [Self::wait_for_frame_value] to check on
+/// "frame N is complete" without needing access to the frame's fence. When
+/// created with [Self::new_with_timeline_semaphore], frame completion is
+/// tracked with a single timeline semaphore instead of per-frame fences.
+///
+/// Resources which may still be referenced by in-flight frames can be passed
+/// to [Self::defer_drop]. They are dropped once every frame that was in
+/// flight at the time has retired, so replacing a texture or pipeline does not
+/// require waiting for all frames to complete.
 #[derive(Debug)]
 pub struct FramesInFlight {
     // Used to synchronize the calls to QueueSubmit and Present per swapchain
     // image.
     swapchain_image_present_semaphores: Vec<raii::Semaphore>,
 
+    // Signaled with each frame's value when its graphics commands complete.
+    timeline_semaphore: Option<raii::Semaphore>,
+
     frames: Vec<FrameSync>,
     frame_index: usize,
+    started_frame_value: u64,
+    submitted_frame_value: u64,
+    deferred_drops: DeferredDrops,
     cxt: Arc<VulkanContext>,
 }
 
 impl FramesInFlight {
     /// Creates a new instance with `frame_count` frames.
+    ///
+    /// Frame completion is tracked with one fence per frame.
     pub fn new(
         ctx: Arc<VulkanContext>,
         swapchain_image_count: usize,
         frame_count: usize,
     ) -> Result<Self> {
+        Self::create(ctx, swapchain_image_count, frame_count, false)
+    }
+
+    /// Creates a new instance with `frame_count` frames.
+    ///
+    /// Frame completion is tracked with a single timeline semaphore which is
+    /// signaled with each frame's value. The device must be created with the
+    /// Vulkan 1.2 `timeline_semaphore` feature enabled.
+    pub fn new_with_timeline_semaphore(
+        ctx: Arc<VulkanContext>,
+        swapchain_image_count: usize,
+        frame_count: usize,
+    ) -> Result<Self> {
+        Self::create(ctx, swapchain_image_count, frame_count, true)
+    }
+
+    fn create(
+        ctx: Arc<VulkanContext>,
+        swapchain_image_count: usize,
+        frame_count: usize,
+        use_timeline_semaphore: bool,
+    ) -> Result<Self> {
+        let timeline_semaphore = if use_timeline_semaphore {
+            let mut semaphore_type_create_info = vk::SemaphoreTypeCreateInfo {
+                semaphore_type: vk::SemaphoreType::TIMELINE,
+                initial_value: 0,
+                ..Default::default()
+            };
+            Some(unwrap_here!(
+                "Create frames in flight timeline semaphore",
+                raii::Semaphore::new(
+                    "frames in flight timeline",
+                    ctx.device.clone(),
+                    &vk::SemaphoreCreateInfo::default()
+                        .push_next(&mut semaphore_type_create_info),
+                )
+            ))
+        } else {
+            None
+        };
+
         // Create one semaphore per swapchain image
         let mut swapchain_image_present_semaphores =
             Vec::with_capacity(swapchain_image_count);
@@ -178,28 +276,38 @@ impl FramesInFlight {
                         &vk::SemaphoreCreateInfo::default(),
                     )
                 ),
-                graphics_commands_complete: unwrap_here!(
-                    format!("Create graphics cmd fence for frame {index}"),
-                    raii::Fence::new(
-                        format!(
-                            "graphics commands complete - frame [{}]",
-                            index
-                        ),
-                        ctx.device.clone(),
-                        &vk::FenceCreateInfo {
-                            flags: vk::FenceCreateFlags::SIGNALED,
-                            ..Default::default()
-                        },
-                    )
-                ),
+                graphics_commands_complete: if use_timeline_semaphore {
+                    None
+                } else {
+                    Some(unwrap_here!(
+                        format!("Create graphics cmd fence for frame {index}"),
+                        raii::Fence::new(
+                            format!(
+                                "graphics commands complete - frame [{}]",
+                                index
+                            ),
+                            ctx.device.clone(),
+                            &vk::FenceCreateInfo {
+                                flags: vk::FenceCreateFlags::SIGNALED,
+                                ..Default::default()
+                            },
+                        )
+                    ))
+                },
+                frame_value: 0,
+                ticket_waits: vec![],
                 command_pool,
                 command_buffer,
             });
         }
         Ok(Self {
             swapchain_image_present_semaphores,
+            timeline_semaphore,
             frames,
             frame_index: 0,
+            started_frame_value: 0,
+            submitted_frame_value: 0,
+            deferred_drops: DeferredDrops::default(),
             cxt: ctx,
         })
     }
@@ -208,20 +316,30 @@ impl FramesInFlight {
     ///
     /// This should only be required after the swapchain is rebuilt.
     ///
-    /// Rebuilding semaphores cannot be done until all images have finished,
-    /// which means waiting for a full pipeline stall.
+    /// When using per-frame fences the old semaphores can't be destroyed
+    /// until every frame has finished, which means a full pipeline stall.
+    /// When using a timeline semaphore this does not block. Submitted frames
+    /// can still be waiting on the old acquire semaphores, presentation can
+    /// still be waiting on the old present semaphores, and an acquire which
+    /// returned SUBOPTIMAL leaves a pending signal on its semaphore. None of
+    /// these are covered by frame values, so the old semaphores are kept alive
+    /// until every frame in flight has been started and retired again.
     pub fn rebuild_swapchain_semaphores(
         &mut self,
         ctx: &VulkanContext,
         swapchain_image_count: usize,
     ) -> Result<()> {
-        self.wait_for_all_frames_to_complete()?;
+        if self.timeline_semaphore.is_none() {
+            self.wait_for_all_frames_to_complete()?;
+            // Needed to ensure that all resources are finished being used
+            // before continuing
+            unsafe { ctx.device_wait_idle()? };
+        }
+        self.drop_retired_resources()?;
 
-        // Needed to ensure that all resources are finished being used before
-        // continuing
-        unsafe { ctx.device_wait_idle()? };
+        let mut retired_semaphores =
+            std::mem::take(&mut self.swapchain_image_present_semaphores);
 
-        self.swapchain_image_present_semaphores.clear();
         // Create one semaphore per swapchain image
         for i in 0..swapchain_image_count {
             self.swapchain_image_present_semaphores.push(unwrap_here!(
@@ -235,7 +353,7 @@ impl FramesInFlight {
         }
 
         for (i, frame_sync) in self.frames.iter_mut().enumerate() {
-            frame_sync.swapchain_image_acquired = unwrap_here!(
+            let acquired = unwrap_here!(
                 format!("Rebuild frame {i} swapchain image acquired semaphore"),
                 raii::Semaphore::new(
                     format!("Swapchain Image Present [{}]", i),
@@ -243,6 +361,20 @@ impl FramesInFlight {
                     &vk::SemaphoreCreateInfo::default(),
                 )
             );
+            retired_semaphores.push(std::mem::replace(
+                &mut frame_sync.swapchain_image_acquired,
+                acquired,
+            ));
+        }
+
+        if self.timeline_semaphore.is_some() {
+            // Presentation and acquisition are queued around each frame's
+            // submission, so they have finished once the frames started
+            // after them have retired.
+            self.deferred_drops.push(
+                self.started_frame_value + self.frames.len() as u64,
+                Box::new(retired_semaphores),
+            );
         }
 
         Ok(())
@@ -253,23 +385,136 @@ impl FramesInFlight {
         self.frames.len()
     }
 
+    /// Returns true when frame completion is tracked with a timeline
+    /// semaphore.
+    pub fn uses_timeline_semaphore(&self) -> bool {
+        self.timeline_semaphore.is_some()
+    }
+
+    /// Returns the timeline semaphore signaled with each frame's value, if
+    /// there is one.
+    pub fn timeline_semaphore(&self) -> Option<vk::Semaphore> {
+        self.timeline_semaphore
+            .as_ref()
+            .map(|semaphore| semaphore.raw)
+    }
+
+    /// The frame value of the most recently started frame.
+    pub fn frame_value(&self) -> u64 {
+        self.started_frame_value
+    }
+
+    /// The frame value of the most recently submitted frame.
+    pub fn submitted_frame_value(&self) -> u64 {
+        self.submitted_frame_value
+    }
+
+    /// Returns the largest frame value such that the frame, and every frame
+    /// before it, has finished executing on the GPU.
+    pub fn completed_frame_value(&self) -> Result<u64> {
+        if let Some(timeline_semaphore) = &self.timeline_semaphore {
+            return unsafe {
+                self.cxt
+                    .get_semaphore_counter_value(timeline_semaphore.raw)
+                    .context("get frames in flight timeline value")
+            };
+        }
+
+        let mut pending_frames = Vec::with_capacity(self.frames.len());
+        for frame_sync in &self.frames {
+            if frame_sync.status != FrameSyncStatus::Pending {
+                continue;
+            }
+            let fence = frame_sync.fence()?;
+            let signaled = unsafe {
+                self.cxt
+                    .get_fence_status(fence)
+                    .context("get frame fence status")?
+            };
+            pending_frames.push((frame_sync.frame_value, signaled));
+        }
+        Ok(completed_frame_value_from_fences(
+            self.submitted_frame_value,
+            &pending_frames,
+        ))
+    }
+
+    /// Blocks until the frame with the given value, and every frame before it,
+    /// has finished executing on the GPU.
+    ///
+    /// It is an error to wait on a frame which has not been submitted yet
+    /// because the wait could never complete.
+    pub fn wait_for_frame_value(&self, frame_value: u64) -> Result<()> {
+        if frame_value > self.submitted_frame_value {
+            bail!(
+                "Cannot wait for frame {} because the last submitted frame is \
+                 {}",
+                frame_value,
+                self.submitted_frame_value
+            );
+        }
+
+        if let Some(timeline_semaphore) = &self.timeline_semaphore {
+            return unsafe {
+                self.cxt
+                    .wait_semaphores(
+                        &vk::SemaphoreWaitInfo::default()
+                            .semaphores(&[timeline_semaphore.raw])
+                            .values(&[frame_value]),
+                        u64::MAX,
+                    )
+                    .with_context(|| format!("wait for frame {frame_value}"))
+            };
+        }
+
+        let mut fences = vec![];
+        for frame_sync in &self.frames {
+            if frame_sync.status == FrameSyncStatus::Pending
+                && frame_sync.frame_value <= frame_value
+            {
+                fences.push(frame_sync.fence()?);
+            }
+        }
+        if fences.is_empty() {
+            return Ok(());
+        }
+        unsafe {
+            self.cxt
+                .wait_for_fences(&fences, true, u64::MAX)
+                .with_context(|| format!("wait for frame {frame_value}"))
+        }
+    }
+
+    /// Drops the resource once every frame that is currently in flight,
+    /// including a frame that is still being assembled, has retired.
+    ///
+    /// Accepts any owned, sendable value: raii wrappers, an [OwnedBlock], an
+    /// `Arc<Texture>`, or a tuple of several resources. Queued resources are
+    /// checked at the start of each frame.
+    ///
+    /// [OwnedBlock]: crate::graphics::vulkan::OwnedBlock
+    pub fn defer_drop(&mut self, resource: impl Any + Send) {
+        self.deferred_drops
+            .push(self.started_frame_value, Box::new(resource));
+    }
+
+    /// Drops deferred resources whose frames have retired.
+    ///
+    /// This is called automatically by [Self::start_frame].
+    pub fn drop_retired_resources(&mut self) -> Result<()> {
+        let completed = self.completed_frame_value()?;
+        self.deferred_drops.drop_completed(completed);
+        Ok(())
+    }
+
     /// Blocks until all submitted commands for all frames have completed.
     ///
     /// NOTE: Only waits on pending frames. If there's a frame mid-assembly,
     ///       there's nothing to wait on. (and what's more, attempting to wait
     ///       would never succeed until the frame is submitted)
     pub fn wait_for_all_frames_to_complete(&self) -> Result<()> {
-        let fences = self
-            .frames
-            .iter()
-            .filter(|frame_sync| frame_sync.status == FrameSyncStatus::Pending)
-            .map(|frame_sync| frame_sync.graphics_commands_complete.raw)
-            .collect::<Vec<vk::Fence>>();
-        unsafe {
-            self.cxt
-                .wait_for_fences(&fences, true, u64::MAX)
-                .context("wait for all pending frames to complete")
-        }
+        self.wait_for_frame_value(self.submitted_frame_value)
+            .context("wait for all pending frames to complete")
     }
 
     /// Starts the next frame in flight.
@@ -293,13 +538,31 @@ impl FramesInFlight {
 
         // Wait for the last frame's submission to complete, if its still
         // running.
-        unwrap_here!("Wait for the previous submission to complete", unsafe {
-            self.cxt.wait_for_fences(
-                &[frame_sync.graphics_commands_complete.raw],
-                true,
-                u64::MAX,
-            )
-        });
+        if let Some(timeline_semaphore) = &self.timeline_semaphore {
+            unwrap_here!(
+                "Wait for the previous submission to complete",
+                unsafe {
+                    self.cxt.wait_semaphores(
+                        &vk::SemaphoreWaitInfo::default()
+                            .semaphores(&[timeline_semaphore.raw])
+                            .values(&[frame_sync.frame_value]),
+                        u64::MAX,
+                    )
+                }
+            );
+        } else {
+            let fence = frame_sync.fence()?;
+            unwrap_here!(
+                "Wait for the previous submission to complete",
+                unsafe { self.cxt.wait_for_fences(&[fence], true, u64::MAX) }
+            );
+        }
+
+        unwrap_here!(
+            "Drop deferred resources from retired frames",
+            self.drop_retired_resources()
+        );
+        let frame_sync = &mut self.frames[self.frame_index];
 
         // Acquire the next Swapchain image
         let status = unwrap_here!(
@@ -319,12 +582,16 @@ impl FramesInFlight {
         // to be reconstructed.
 
         // Swapchain image is available, so reset the commands fence.
-        unwrap_here!("Reset the frame's fence", unsafe {
-            self.cxt
-                .reset_fences(&[frame_sync.graphics_commands_complete.raw])
-        });
+        if let Some(fence) = &frame_sync.graphics_commands_complete {
+            unwrap_here!("Reset the frame's fence", unsafe {
+                self.cxt.reset_fences(&[fence.raw])
+            });
+        }
         // mark the frame as pending so nobody gets stuck waiting for it
         frame_sync.status = FrameSyncStatus::Assembling;
+        frame_sync.ticket_waits.clear();
+        self.started_frame_value += 1;
+        frame_sync.frame_value = self.started_frame_value;
 
         // Start the Frame's command buffer.
         unwrap_here!("Reset the frame's command pool", unsafe {
@@ -351,9 +618,27 @@ impl FramesInFlight {
             swapchain_image_view: swapchain.image_views()
                 [swapchain_image_index as usize]
                 .raw,
+            frame_value: frame_sync.frame_value,
         }))
     }
 
+    /// Makes the [Frame]'s command buffer wait for the ticket's submission to
+    /// complete before executing `wait_stage`.
+    ///
+    /// This lets a frame consume the results of an async submission, like a
+    /// texture upload, without blocking the CPU. The device must have the
+    /// `timeline_semaphore` feature enabled.
+    pub fn add_ticket_wait(
+        &mut self,
+        frame: &Frame,
+        ticket: &GpuTicket,
+        wait_stage: vk::PipelineStageFlags,
+    ) {
+        self.frames[frame.frame_index()]
+            .ticket_waits
+            .push((ticket.clone(), wait_stage));
+    }
+
     /// Queues the [Frame]'s command buffer and swapchain presentation.
     pub fn present_frame(
         &mut self,
@@ -365,27 +650,53 @@ impl FramesInFlight {
             self.cxt.end_command_buffer(frame_sync.command_buffer)
         });
 
-        let wait_stage = vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT;
+        let present_semaphore = self.swapchain_image_present_semaphores
+            [frame.swapchain_image_index as usize]
+            .raw;
+
+        // Binary semaphores ignore their entry in the values arrays.
+        let mut wait_semaphores = vec![frame_sync.swapchain_image_acquired.raw];
+        let mut wait_stages =
+            vec![vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+        let mut wait_values = vec![0];
+        for (ticket, wait_stage) in &frame_sync.ticket_waits {
+            wait_semaphores.push(ticket.semaphore());
+            wait_stages.push(*wait_stage);
+            wait_values.push(ticket.value());
+        }
+        let mut signal_semaphores = vec![present_semaphore];
+        let mut signal_values = vec![0];
+        if let Some(timeline_semaphore) = &self.timeline_semaphore {
+            signal_semaphores.push(timeline_semaphore.raw);
+            signal_values.push(frame.frame_value);
+        }
+        let mut timeline_submit_info =
+            vk::TimelineSemaphoreSubmitInfo::default()
+                .wait_semaphore_values(&wait_values)
+                .signal_semaphore_values(&signal_values);
+        let mut submit_info = vk::SubmitInfo::default()
+            .wait_semaphores(&wait_semaphores)
+            .wait_dst_stage_mask(&wait_stages)
+            .command_buffers(std::slice::from_ref(&frame_sync.command_buffer))
+            .signal_semaphores(&signal_semaphores);
+        if self.timeline_semaphore.is_some()
+            || !frame_sync.ticket_waits.is_empty()
+        {
+            submit_info = submit_info.push_next(&mut timeline_submit_info);
+        }
+        let fence = match &frame_sync.graphics_commands_complete {
+            Some(fence) => fence.raw,
+            None => vk::Fence::null(),
+        };
         unwrap_here!("Submit the frame's primary command buffer", unsafe {
             self.cxt.queue_submit(
                 self.cxt.graphics_queue,
-                &[vk::SubmitInfo {
-                    wait_semaphore_count: 1,
-                    p_wait_semaphores: &frame_sync.swapchain_image_acquired.raw,
-                    p_wait_dst_stage_mask: &wait_stage,
-                    command_buffer_count: 1,
-                    p_command_buffers: &frame_sync.command_buffer,
-                    signal_semaphore_count: 1,
-                    p_signal_semaphores: &self
-                        .swapchain_image_present_semaphores
-                        [frame.swapchain_image_index as usize]
-                        .raw,
-                    ..Default::default()
-                }],
-                frame_sync.graphics_commands_complete.raw,
+                &[submit_info],
+                fence,
             )
         });
         frame_sync.status = FrameSyncStatus::Pending;
+        self.submitted_frame_value = frame.frame_value;
 
         swapchain.present_image(
             self.swapchain_image_present_semaphores
@@ -402,5 +713,45 @@ impl Drop for FramesInFlight {
         unsafe {
             self.cxt.device_wait_idle().unwrap();
         }
+        self.deferred_drops.clear();
+    }
+}
+
+/// Returns the largest frame value that is known to be complete when frame
+/// completion is tracked with per-frame fences.
+///
+/// `pending_frames` holds the frame value and fence status of each frame that
+/// was submitted and has not been restarted since.
+fn completed_frame_value_from_fences(
+    submitted_frame_value: u64,
+    pending_frames: &[(u64, bool)],
+) -> u64 {
+    pending_frames
+        .iter()
+        .filter(|(_, signaled)| !signaled)
+        .map(|(frame_value, _)| frame_value.saturating_sub(1))
+        .fold(submitted_frame_value, u64::min)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn completed_frame_value_should_advance_as_fences_signal() {
+        // frames 4, 5, and 6 are in flight
+        let mut pending = [(4, false), (5, false), (6, false)];
+        assert_eq!(completed_frame_value_from_fences(6, &pending), 3);
+
+        // a later frame finishing first doesn't complete the earlier frames
+        pending[1].1 = true;
+        assert_eq!(completed_frame_value_from_fences(6, &pending), 3);
+
+        pending[0].1 = true;
+        assert_eq!(completed_frame_value_from_fences(6, &pending), 5);
+
+        pending[2].1 = true;
+        assert_eq!(completed_frame_value_from_fences(6, &pending), 6);
+        assert_eq!(completed_frame_value_from_fences(0, &[]), 0);
     }
 }
@@ -13,6 +13,7 @@ mod allocator;
 mod buffers;
 mod context;
 mod frames_in_flight;
+mod queries;
 pub mod raii;
 mod spirv;
 mod swapchain;
@@ -20,10 +21,16 @@ mod sync_commands;
 
 pub use self::{
     allocator::{block::Block, owned_block::OwnedBlock, Allocator},
-    buffers::{CPUBuffer, UniformBuffer},
+    buffers::{CPUBuffer, PerFrameBuffer, UniformBuffer},
     context::{Instance, RequiredDeviceFeatures, VulkanContext},
     frames_in_flight::{Frame, FrameStatus, FramesInFlight},
+    queries::{
+        GpuScope, GpuTimer, OcclusionQueries, PipelineStatistics,
+        ShaderInvocations,
+    },
     spirv::{spirv_module, spirv_words},
-    swapchain::{AcquireImageStatus, PresentImageStatus, Swapchain},
-    sync_commands::SyncCommands,
+    swapchain::{
+        AcquireImageStatus, PresentImageStatus, Swapchain, SwapchainSettings,
+    },
+    sync_commands::{GpuTicket, SyncCommands},
 };
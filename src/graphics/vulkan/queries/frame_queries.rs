@@ -0,0 +1,155 @@
+use {
+    crate::graphics::vulkan::{raii, Frame, FramesInFlight, VulkanContext},
+    anyhow::Result,
+    ash::vk,
+};
+
+/// The named scopes recorded into a single frame.
+#[derive(Debug, Default)]
+struct FrameScopes {
+    names: Vec<String>,
+    ended: Vec<bool>,
+}
+
+/// A query pool split into a separate range of queries for each frame in
+/// flight.
+///
+/// Each named scope owns `queries_per_scope` consecutive queries within its
+/// frame's range.
+#[derive(Debug)]
+pub(super) struct FrameQueries {
+    name: String,
+    query_pool: raii::QueryPool,
+    queries_per_scope: u32,
+    max_scopes_per_frame: u32,
+    frames: Vec<FrameScopes>,
+}
+
+impl FrameQueries {
+    pub fn new(
+        name: &str,
+        cxt: &VulkanContext,
+        frames_in_flight: &FramesInFlight,
+        query_type: vk::QueryType,
+        pipeline_statistics: vk::QueryPipelineStatisticFlags,
+        queries_per_scope: u32,
+        max_scopes_per_frame: u32,
+    ) -> Result<Self> {
+        let frame_count = frames_in_flight.frame_count();
+        let query_pool = raii::QueryPool::new(
+            name,
+            cxt.device.clone(),
+            &vk::QueryPoolCreateInfo {
+                query_type,
+                query_count: frame_count as u32
+                    * max_scopes_per_frame
+                    * queries_per_scope,
+                pipeline_statistics,
+                ..Default::default()
+            },
+        )?;
+        Ok(Self {
+            name: name.to_owned(),
+            query_pool,
+            queries_per_scope,
+            max_scopes_per_frame,
+            frames: (0..frame_count).map(|_| FrameScopes::default()).collect(),
+        })
+    }
+
+    /// The raw query pool handle.
+    pub fn raw(&self) -> vk::QueryPool {
+        self.query_pool.raw
+    }
+
+    /// Reads the results for every scope which was ended the last time this
+    /// frame index was used, then records a reset for the frame's queries.
+    ///
+    /// `ResultT` holds the result of a single query, including its
+    /// availability value, and `N` must match the number of queries per scope.
+    /// Scopes with any unavailable query are skipped.
+    pub fn read_and_reset<ResultT: Copy + Default, const N: usize>(
+        &mut self,
+        cxt: &VulkanContext,
+        frame: &Frame,
+        is_available: impl Fn(&ResultT) -> bool,
+    ) -> Result<Vec<(String, [ResultT; N])>> {
+        debug_assert_eq!(N as u32, self.queries_per_scope);
+        let first_query = self.first_query(frame);
+        let scopes = &mut self.frames[frame.frame_index()];
+
+        let mut results = Vec::with_capacity(scopes.names.len());
+        for (index, name) in scopes.names.drain(..).enumerate() {
+            if !scopes.ended[index] {
+                continue;
+            }
+            let mut result = [ResultT::default(); N];
+            let status = unsafe {
+                cxt.get_query_pool_results(
+                    self.query_pool.raw,
+                    first_query + index as u32 * self.queries_per_scope,
+                    &mut result,
+                    vk::QueryResultFlags::TYPE_64
+                        | vk::QueryResultFlags::WITH_AVAILABILITY,
+                )
+            };
+            match status {
+                // availability is still written for each query
+                Ok(()) | Err(vk::Result::NOT_READY) => {}
+                Err(err) => return Err(err.into()),
+            }
+            if result.iter().all(&is_available) {
+                results.push((name, result));
+            }
+        }
+        scopes.ended.clear();
+
+        unsafe {
+            cxt.cmd_reset_query_pool(
+                frame.command_buffer(),
+                self.query_pool.raw,
+                first_query,
+                self.max_scopes_per_frame * self.queries_per_scope,
+            );
+        }
+
+        Ok(results)
+    }
+
+    /// Reserves the queries for a new named scope.
+    ///
+    /// Returns the scope's index within the frame and the index of its first
+    /// query, or None if the frame has no more scopes available.
+    pub fn begin_scope(
+        &mut self,
+        frame: &Frame,
+        name: impl Into<String>,
+    ) -> Option<(u32, u32)> {
+        let first_query = self.first_query(frame);
+        let scopes = &mut self.frames[frame.frame_index()];
+        let index = scopes.names.len() as u32;
+        if index >= self.max_scopes_per_frame {
+            log::warn!(
+                "{} ran out of scopes for the frame, dropping {}",
+                self.name,
+                name.into()
+            );
+            return None;
+        }
+        scopes.names.push(name.into());
+        scopes.ended.push(false);
+        Some((index, first_query + index * self.queries_per_scope))
+    }
+
+    /// Marks the scope as ended and returns the index of its first query.
+    pub fn end_scope(&mut self, frame: &Frame, index: u32) -> u32 {
+        self.frames[frame.frame_index()].ended[index as usize] = true;
+        self.first_query(frame) + index * self.queries_per_scope
+    }
+
+    fn first_query(&self, frame: &Frame) -> u32 {
+        frame.frame_index() as u32
+            * self.max_scopes_per_frame
+            * self.queries_per_scope
+    }
+}
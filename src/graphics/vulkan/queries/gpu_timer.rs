@@ -0,0 +1,153 @@
+use {
+    super::{frame_queries::FrameQueries, GpuScope},
+    crate::{
+        graphics::vulkan::{Frame, FramesInFlight, VulkanContext},
+        unwrap_here,
+    },
+    anyhow::{Context, Result},
+    ash::vk,
+    std::sync::Arc,
+};
+
+/// Records named pairs of timestamps into each frame's command buffer.
+///
+/// Each frame in flight owns a separate range of queries in a shared query
+/// pool. The timestamps for a frame are read back the next time the same frame
+/// index is started, at which point its previous commands are known to have
+/// completed.
+#[derive(Debug)]
+pub struct GpuTimer {
+    queries: Option<FrameQueries>,
+    timestamp_period_ns: f64,
+    timestamp_mask: u64,
+    cxt: Arc<VulkanContext>,
+}
+
+impl GpuTimer {
+    /// Creates a new timer which can record up to `max_scopes_per_frame`
+    /// scopes in each frame.
+    ///
+    /// If the graphics queue does not support timestamps then the timer is
+    /// created but never records anything.
+    pub fn new(
+        cxt: Arc<VulkanContext>,
+        frames_in_flight: &FramesInFlight,
+        max_scopes_per_frame: u32,
+    ) -> Result<Self> {
+        let timestamp_period_ns = unsafe {
+            cxt.instance
+                .get_physical_device_properties(cxt.physical_device)
+                .limits
+                .timestamp_period as f64
+        };
+        let timestamp_valid_bits = unsafe {
+            cxt.instance
+                .get_physical_device_queue_family_properties(
+                    cxt.physical_device,
+                )
+                .get(cxt.graphics_queue_family_index as usize)
+                .context("Graphics queue family properties are missing")?
+                .timestamp_valid_bits
+        };
+
+        let queries = if timestamp_valid_bits == 0 {
+            log::warn!("The graphics queue does not support timestamps");
+            None
+        } else {
+            Some(unwrap_here!(
+                "Create GPU timer query pool",
+                FrameQueries::new(
+                    "GpuTimer",
+                    &cxt,
+                    frames_in_flight,
+                    vk::QueryType::TIMESTAMP,
+                    vk::QueryPipelineStatisticFlags::empty(),
+                    2,
+                    max_scopes_per_frame,
+                )
+            ))
+        };
+
+        Ok(Self {
+            queries,
+            timestamp_period_ns,
+            timestamp_mask: if timestamp_valid_bits >= 64 {
+                u64::MAX
+            } else {
+                (1 << timestamp_valid_bits) - 1
+            },
+            cxt,
+        })
+    }
+
+    /// Reads back the timings from the last time this frame index was used
+    /// and resets the frame's queries.
+    ///
+    /// This must be called once per frame, before any scopes are recorded and
+    /// outside of any render pass.
+    ///
+    /// Returns the (name, milliseconds) for each scope that was ended.
+    pub fn begin_frame(&mut self, frame: &Frame) -> Result<Vec<(String, f32)>> {
+        let Some(queries) = &mut self.queries else {
+            return Ok(vec![]);
+        };
+        // each timestamp is followed by its availability
+        let results = unwrap_here!(
+            "Read GPU timer query results",
+            queries.read_and_reset::<[u64; 2], 2>(
+                &self.cxt,
+                frame,
+                |[_, available]| *available != 0,
+            )
+        );
+        Ok(results
+            .into_iter()
+            .map(|(name, [[begin, _], [end, _]])| {
+                let ticks = end.wrapping_sub(begin) & self.timestamp_mask;
+                let ms =
+                    (ticks as f64 * self.timestamp_period_ns) / 1_000_000.0;
+                (name, ms as f32)
+            })
+            .collect())
+    }
+
+    /// Writes a timestamp marking the start of a named scope.
+    pub fn begin(
+        &mut self,
+        frame: &Frame,
+        name: impl Into<String>,
+    ) -> GpuScope {
+        let Some(queries) = &mut self.queries else {
+            return GpuScope(None);
+        };
+        let Some((index, query)) = queries.begin_scope(frame, name) else {
+            return GpuScope(None);
+        };
+        unsafe {
+            self.cxt.cmd_write_timestamp(
+                frame.command_buffer(),
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                queries.raw(),
+                query,
+            );
+        }
+        GpuScope(Some(index))
+    }
+
+    /// Writes a timestamp marking the end of the scope.
+    pub fn end(&mut self, frame: &Frame, scope: GpuScope) {
+        let (Some(queries), GpuScope(Some(index))) = (&mut self.queries, scope)
+        else {
+            return;
+        };
+        let query = queries.end_scope(frame, index);
+        unsafe {
+            self.cxt.cmd_write_timestamp(
+                frame.command_buffer(),
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                queries.raw(),
+                query + 1,
+            );
+        }
+    }
+}
@@ -0,0 +1,22 @@
+//! Utilities for recording and reading back Vulkan queries.
+//!
+//! Each query type records named scopes into a frame's command buffer and
+//! reads the results back once the same frame index is started again.
+
+mod frame_queries;
+mod gpu_timer;
+mod occlusion;
+mod pipeline_statistics;
+
+pub use self::{
+    gpu_timer::GpuTimer,
+    occlusion::OcclusionQueries,
+    pipeline_statistics::{PipelineStatistics, ShaderInvocations},
+};
+
+/// A handle for a scope started by one of the query types.
+///
+/// Scopes which could not be started, e.g. because the frame ran out of
+/// queries, are ignored when ended.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GpuScope(Option<u32>);
@@ -0,0 +1,118 @@
+use {
+    super::{frame_queries::FrameQueries, GpuScope},
+    crate::{
+        graphics::vulkan::{Frame, FramesInFlight, VulkanContext},
+        unwrap_here,
+    },
+    anyhow::Result,
+    ash::vk,
+    std::sync::Arc,
+};
+
+/// Records occlusion queries around named ranges of draw commands.
+///
+/// Each scope reports the number of samples which passed the depth and
+/// stencil tests. Results are read back the next time the same frame index is
+/// started, so they lag behind the current frame by the number of frames in
+/// flight.
+#[derive(Debug)]
+pub struct OcclusionQueries {
+    queries: FrameQueries,
+    control_flags: vk::QueryControlFlags,
+    cxt: Arc<VulkanContext>,
+}
+
+impl OcclusionQueries {
+    /// Creates a new set of queries which can record up to
+    /// `max_scopes_per_frame` scopes in each frame.
+    ///
+    /// When `precise` is false, implementations may report any non-zero value
+    /// for visible scopes. Precise counts require the device's
+    /// `occlusion_query_precise` feature.
+    pub fn new(
+        cxt: Arc<VulkanContext>,
+        frames_in_flight: &FramesInFlight,
+        max_scopes_per_frame: u32,
+        precise: bool,
+    ) -> Result<Self> {
+        let queries = unwrap_here!(
+            "Create occlusion query pool",
+            FrameQueries::new(
+                "OcclusionQueries",
+                &cxt,
+                frames_in_flight,
+                vk::QueryType::OCCLUSION,
+                vk::QueryPipelineStatisticFlags::empty(),
+                1,
+                max_scopes_per_frame,
+            )
+        );
+        Ok(Self {
+            queries,
+            control_flags: if precise {
+                vk::QueryControlFlags::PRECISE
+            } else {
+                vk::QueryControlFlags::empty()
+            },
+            cxt,
+        })
+    }
+
+    /// Reads back the sample counts from the last time this frame index was
+    /// used and resets the frame's queries.
+    ///
+    /// This must be called once per frame, before any scopes are recorded and
+    /// outside of any render pass.
+    pub fn begin_frame(&mut self, frame: &Frame) -> Result<Vec<(String, u64)>> {
+        // the sample count is followed by the availability
+        let results = unwrap_here!(
+            "Read occlusion query results",
+            self.queries.read_and_reset::<[u64; 2], 1>(
+                &self.cxt,
+                frame,
+                |[_, available]| *available != 0,
+            )
+        );
+        Ok(results
+            .into_iter()
+            .map(|(name, [[samples, _]])| (name, samples))
+            .collect())
+    }
+
+    /// Begins counting samples for a named scope.
+    ///
+    /// The scope must begin and end within the same render pass instance.
+    pub fn begin(
+        &mut self,
+        frame: &Frame,
+        name: impl Into<String>,
+    ) -> GpuScope {
+        let Some((index, query)) = self.queries.begin_scope(frame, name) else {
+            return GpuScope(None);
+        };
+        unsafe {
+            self.cxt.cmd_begin_query(
+                frame.command_buffer(),
+                self.queries.raw(),
+                query,
+                self.control_flags,
+            );
+        }
+        GpuScope(Some(index))
+    }
+
+    /// Stops counting samples for the scope.
+    pub fn end(&mut self, frame: &Frame, scope: GpuScope) {
+        let GpuScope(Some(index)) = scope else {
+            return;
+        };
+        let query = self.queries.end_scope(frame, index);
+        unsafe {
+            self.cxt.cmd_end_query(
+                frame.command_buffer(),
+                self.queries.raw(),
+                query,
+            );
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use {
+    super::{frame_queries::FrameQueries, GpuScope},
+    crate::{
+        graphics::vulkan::{Frame, FramesInFlight, VulkanContext},
+        unwrap_here,
+    },
+    anyhow::{bail, Result},
+    ash::vk,
+    std::sync::Arc,
+};
+
+/// Shader invocation counts for a scope recorded by [PipelineStatistics].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ShaderInvocations {
+    pub vertex: u64,
+    pub fragment: u64,
+    pub compute: u64,
+}
+
+impl ShaderInvocations {
+    /// Decodes a single query result.
+    ///
+    /// Statistics are written in [PipelineStatistics::STATISTICS] bit order
+    /// and followed by the query's availability.
+    fn from_query_result([vertex, fragment, compute, _]: [u64; 4]) -> Self {
+        Self {
+            vertex,
+            fragment,
+            compute,
+        }
+    }
+}
+
+impl std::ops::AddAssign for ShaderInvocations {
+    fn add_assign(&mut self, rhs: Self) {
+        self.vertex += rhs.vertex;
+        self.fragment += rhs.fragment;
+        self.compute += rhs.compute;
+    }
+}
+
+/// Records pipeline statistics queries around named ranges of commands.
+///
+/// Like the [super::GpuTimer], results are read back the next time the same
+/// frame index is started.
+///
+/// The device must enable the `pipeline_statistics_query` feature.
+#[derive(Debug)]
+pub struct PipelineStatistics {
+    queries: FrameQueries,
+    cxt: Arc<VulkanContext>,
+}
+
+impl PipelineStatistics {
+    /// The statistics recorded by each query. Results are written in bit
+    /// order.
+    const STATISTICS: vk::QueryPipelineStatisticFlags =
+        vk::QueryPipelineStatisticFlags::from_raw(
+            vk::QueryPipelineStatisticFlags::VERTEX_SHADER_INVOCATIONS.as_raw()
+                | vk::QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS
+                    .as_raw()
+                | vk::QueryPipelineStatisticFlags::COMPUTE_SHADER_INVOCATIONS
+                    .as_raw(),
+        );
+
+    /// Creates a new set of queries which can record up to
+    /// `max_scopes_per_frame` scopes in each frame.
+    ///
+    /// Fails if the device was created without the
+    /// `pipeline_statistics_query` feature.
+    pub fn new(
+        cxt: Arc<VulkanContext>,
+        frames_in_flight: &FramesInFlight,
+        max_scopes_per_frame: u32,
+    ) -> Result<Self> {
+        if !cxt.pipeline_statistics_query_enabled() {
+            bail!(
+                "PipelineStatistics requires the pipeline_statistics_query \
+                device feature"
+            );
+        }
+        let queries = unwrap_here!(
+            "Create pipeline statistics query pool",
+            FrameQueries::new(
+                "PipelineStatistics",
+                &cxt,
+                frames_in_flight,
+                vk::QueryType::PIPELINE_STATISTICS,
+                Self::STATISTICS,
+                1,
+                max_scopes_per_frame,
+            )
+        );
+        Ok(Self { queries, cxt })
+    }
+
+    /// Reads back the statistics from the last time this frame index was used
+    /// and resets the frame's queries.
+    ///
+    /// This must be called once per frame, before any scopes are recorded and
+    /// outside of any render pass.
+    pub fn begin_frame(
+        &mut self,
+        frame: &Frame,
+    ) -> Result<Vec<(String, ShaderInvocations)>> {
+        // the three statistics are followed by the availability
+        let results = unwrap_here!(
+            "Read pipeline statistics query results",
+            self.queries.read_and_reset::<[u64; 4], 1>(
+                &self.cxt,
+                frame,
+                |[.., available]| *available != 0,
+            )
+        );
+        Ok(results
+            .into_iter()
+            .map(|(name, [result])| {
+                (name, ShaderInvocations::from_query_result(result))
+            })
+            .collect())
+    }
+
+    /// Begins collecting statistics for a named scope.
+    ///
+    /// The scope must begin and end within the same render pass instance if
+    /// it is started inside one.
+    pub fn begin(
+        &mut self,
+        frame: &Frame,
+        name: impl Into<String>,
+    ) -> GpuScope {
+        let Some((index, query)) = self.queries.begin_scope(frame, name) else {
+            return GpuScope(None);
+        };
+        unsafe {
+            self.cxt.cmd_begin_query(
+                frame.command_buffer(),
+                self.queries.raw(),
+                query,
+                vk::QueryControlFlags::empty(),
+            );
+        }
+        GpuScope(Some(index))
+    }
+
+    /// Stops collecting statistics for the scope.
+    pub fn end(&mut self, frame: &Frame, scope: GpuScope) {
+        let GpuScope(Some(index)) = scope else {
+            return;
+        };
+        let query = self.queries.end_scope(frame, index);
+        unsafe {
+            self.cxt.cmd_end_query(
+                frame.command_buffer(),
+                self.queries.raw(),
+                query,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn query_results_should_decode_in_statistic_bit_order() {
+        // vertex < fragment < compute in vk::QueryPipelineStatisticFlags
+        assert!(
+            vk::QueryPipelineStatisticFlags::VERTEX_SHADER_INVOCATIONS.as_raw()
+                < vk::QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS
+                    .as_raw()
+        );
+        assert!(
+            vk::QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS
+                .as_raw()
+                < vk::QueryPipelineStatisticFlags::COMPUTE_SHADER_INVOCATIONS
+                    .as_raw()
+        );
+        assert_eq!(PipelineStatistics::STATISTICS.as_raw().count_ones(), 3);
+
+        assert_eq!(
+            ShaderInvocations::from_query_result([3, 5, 7, 1]),
+            ShaderInvocations {
+                vertex: 3,
+                fragment: 5,
+                compute: 7,
+            }
+        );
+    }
+
+    #[test]
+    pub fn invocations_should_sum_each_stage() {
+        let mut total = ShaderInvocations {
+            vertex: 1,
+            fragment: 2,
+            compute: 3,
+        };
+        total += ShaderInvocations {
+            vertex: 10,
+            fragment: 20,
+            compute: 30,
+        };
+        assert_eq!(
+            total,
+            ShaderInvocations {
+                vertex: 11,
+                fragment: 22,
+                compute: 33,
+            }
+        );
+    }
+}
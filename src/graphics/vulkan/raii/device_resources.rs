@@ -237,6 +237,15 @@ resource!(
     destroy_pipeline_layout
 );
 
+resource!(
+    QueryPool,
+    vk::QueryPool,
+    vk::QueryPool::TYPE,
+    vk::QueryPoolCreateInfo,
+    create_query_pool,
+    destroy_query_pool
+);
+
 // Pipeline is a special case because there are separate create infos for each
 // kind of pipeline.
 resource_impl!(Pipeline, vk::Pipeline, vk::Pipeline::TYPE, destroy_pipeline);
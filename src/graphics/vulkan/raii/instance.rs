@@ -8,10 +8,10 @@ pub struct Instance {
 }
 
 impl Instance {
-    pub fn new(create_info: &vk::InstanceCreateInfo) -> Result<Arc<Self>> {
-        let entry = unwrap_here!("Create the Vulkan loader", unsafe {
-            ash::Entry::load()
-        });
+    pub fn new(
+        entry: ash::Entry,
+        create_info: &vk::InstanceCreateInfo,
+    ) -> Result<Arc<Self>> {
         let raw = unwrap_here!("Create the Vulkan library instance", unsafe {
             entry.create_instance(create_info, None)
         });
@@ -5,6 +5,10 @@
 //! Wrappers do not track dependencies. The application is responsible for
 //! dropping Vulkan objects in the correct order and synchronizing to prevent
 //! GPU inconsistencies.
+//!
+//! Objects which may still be in use by in-flight frames can be handed to
+//! [FramesInFlight::defer_drop](crate::graphics::vulkan::FramesInFlight::defer_drop)
+//! which drops them once those frames have retired.
 
 mod device;
 mod device_extensions;
@@ -18,7 +22,7 @@ pub use self::{
     device_resources::{
         Buffer, CommandPool, DescriptorPool, DescriptorSetLayout, DeviceMemory,
         Fence, Framebuffer, Image, ImageView, Pipeline, PipelineLayout,
-        RenderPass, Sampler, Semaphore, ShaderModule,
+        QueryPool, RenderPass, Sampler, Semaphore, ShaderModule,
     },
     instance::Instance,
     instance_extensions::{DebugUtils, Surface},
@@ -1,6 +1,8 @@
+mod offscreen;
 mod settings;
 
 use {
+    self::offscreen::OffscreenImages,
     crate::{
         graphics::vulkan::{raii, VulkanContext},
         unwrap_here,
@@ -10,6 +12,19 @@ use {
     std::sync::Arc,
 };
 
+pub use self::settings::SwapchainSettings;
+
+/// Shorthand for the surface format constants.
+const fn surface_format(
+    format: vk::Format,
+    color_space: vk::ColorSpaceKHR,
+) -> vk::SurfaceFormatKHR {
+    vk::SurfaceFormatKHR {
+        format,
+        color_space,
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
 pub enum AcquireImageStatus {
     ImageAcquired(u32),
@@ -22,66 +37,207 @@ pub enum PresentImageStatus {
     SwapchainNeedsRebuild,
 }
 
+/// Where the swapchain's images come from.
+enum SwapchainImages {
+    Surface {
+        swapchain: Arc<raii::Swapchain>,
+        // kept alive until the swapchain is destroyed
+        _surface: Arc<raii::Surface>,
+    },
+    Offscreen(OffscreenImages),
+}
+
 /// The Vulkan swapchain and associated resources.
+///
+/// Swapchains are either presented to the window surface or, when created
+/// with [Self::offscreen], backed by plain images which are never presented.
 pub struct Swapchain {
-    swapchain: Arc<raii::Swapchain>,
+    swapchain: SwapchainImages,
     extent: vk::Extent2D,
     format: vk::SurfaceFormatKHR,
+    present_mode: vk::PresentModeKHR,
+    image_usage: vk::ImageUsageFlags,
     images: Vec<vk::Image>,
     image_views: Vec<raii::ImageView>,
     cxt: Arc<VulkanContext>,
 }
 
 impl Swapchain {
-    /// Creates a new Vulkan swapchain.
+    /// Present modes which wait for vertical blank before presenting.
+    pub const VSYNC_PRESENT_MODES: &'static [vk::PresentModeKHR] =
+        &[vk::PresentModeKHR::FIFO];
+
+    /// Present modes which present as soon as possible, preferring modes that
+    /// avoid tearing.
+    pub const NO_VSYNC_PRESENT_MODES: &'static [vk::PresentModeKHR] = &[
+        vk::PresentModeKHR::MAILBOX,
+        vk::PresentModeKHR::IMMEDIATE,
+        vk::PresentModeKHR::FIFO,
+    ];
+
+    /// 8-bit sRGB formats. Shaders write linear color and the hardware
+    /// applies the sRGB transfer function.
+    pub const SRGB_SURFACE_FORMATS: &'static [vk::SurfaceFormatKHR] = &[
+        surface_format(
+            vk::Format::B8G8R8A8_SRGB,
+            vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        ),
+        surface_format(
+            vk::Format::R8G8B8A8_SRGB,
+            vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        ),
+    ];
+
+    /// 8-bit UNORM formats in the sRGB color space. Shaders are responsible
+    /// for applying gamma manually.
+    pub const UNORM_SURFACE_FORMATS: &'static [vk::SurfaceFormatKHR] = &[
+        surface_format(
+            vk::Format::B8G8R8A8_UNORM,
+            vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        ),
+        surface_format(
+            vk::Format::R8G8B8A8_UNORM,
+            vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        ),
+    ];
+
+    /// 10-bit UNORM formats in the sRGB color space. Shaders are responsible
+    /// for applying gamma manually.
+    pub const TEN_BIT_SURFACE_FORMATS: &'static [vk::SurfaceFormatKHR] = &[
+        surface_format(
+            vk::Format::A2B10G10R10_UNORM_PACK32,
+            vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        ),
+        surface_format(
+            vk::Format::A2R10G10B10_UNORM_PACK32,
+            vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        ),
+    ];
+
+    /// 10-bit formats using the HDR10 (BT.2020 primaries with the ST.2084 PQ
+    /// transfer function) color space. Shaders must output PQ encoded color.
+    pub const HDR10_SURFACE_FORMATS: &'static [vk::SurfaceFormatKHR] = &[
+        surface_format(
+            vk::Format::A2B10G10R10_UNORM_PACK32,
+            vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+        ),
+        surface_format(
+            vk::Format::A2R10G10B10_UNORM_PACK32,
+            vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+        ),
+    ];
+
+    /// 16-bit float formats using the scRGB (linear, extended range sRGB)
+    /// color space. Values above 1.0 are brighter than SDR white.
+    pub const SCRGB_SURFACE_FORMATS: &'static [vk::SurfaceFormatKHR] =
+        &[surface_format(
+            vk::Format::R16G16B16A16_SFLOAT,
+            vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+        )];
+
+    /// Creates a new Vulkan swapchain for the context's window surface.
+    ///
+    /// Fails if the context is headless.
+    ///
+    /// See [SwapchainSettings] for how preferences are chosen. HDR color
+    /// spaces are only reported by the surface when the
+    /// VK_EXT_swapchain_colorspace instance extension is available.
     pub fn new(
         cxt: Arc<VulkanContext>,
         framebuffer_size: (u32, u32),
         previous_swapchain: Option<vk::SwapchainKHR>,
+        settings: &SwapchainSettings,
     ) -> Result<Self> {
-        let (swapchain, extent, format) = unwrap_here!(
+        let surface = cxt.surface_khr.clone().context(
+            "Headless Vulkan contexts only support offscreen swapchains",
+        )?;
+        Self::for_surface(
+            cxt,
+            surface,
+            framebuffer_size,
+            previous_swapchain,
+            settings,
+        )
+    }
+
+    /// Creates a new Vulkan swapchain for any surface, e.g. an additional
+    /// window's surface from [VulkanContext::create_surface].
+    ///
+    /// `previous_swapchain` must have been created for the same surface.
+    pub fn for_surface(
+        cxt: Arc<VulkanContext>,
+        surface: Arc<raii::Surface>,
+        framebuffer_size: (u32, u32),
+        previous_swapchain: Option<vk::SwapchainKHR>,
+        settings: &SwapchainSettings,
+    ) -> Result<Self> {
+        let (swapchain, extent, format, present_mode) = unwrap_here!(
             "Create swapchain",
             settings::create_swapchain(
                 &cxt,
+                &surface,
                 framebuffer_size,
                 previous_swapchain,
+                settings,
             )
         );
 
         let images = unwrap_here!("Get swapchain images", unsafe {
             swapchain.ext.get_swapchain_images(swapchain.raw)
         });
-        let mut image_views = vec![];
-        for (index, image) in images.iter().enumerate() {
-            let create_info = vk::ImageViewCreateInfo {
-                image: *image,
-                view_type: vk::ImageViewType::TYPE_2D,
-                format: format.format,
-                components: vk::ComponentMapping::default(),
-                subresource_range: vk::ImageSubresourceRange {
-                    aspect_mask: vk::ImageAspectFlags::COLOR,
-                    base_mip_level: 0,
-                    level_count: 1,
-                    base_array_layer: 0,
-                    layer_count: 1,
-                },
-                ..Default::default()
-            };
-            let image_view = unwrap_here!(
-                format!("Create swapchain image view [{}]", index),
-                raii::ImageView::new(
-                    format!("Swapchain Image [{}]", index),
-                    cxt.device.clone(),
-                    &create_info,
-                )
-            );
-            image_views.push(image_view);
-        }
+        let image_views = unwrap_here!(
+            "Create swapchain image views",
+            create_image_views(&cxt, &images, format.format)
+        );
+
+        Ok(Self {
+            swapchain: SwapchainImages::Surface {
+                swapchain,
+                _surface: surface,
+            },
+            extent,
+            format,
+            present_mode,
+            image_usage: settings.image_usage,
+            images,
+            image_views,
+            cxt,
+        })
+    }
 
+    /// Creates a swapchain backed by device-local images rather than the
+    /// window surface.
+    ///
+    /// Offscreen swapchains behave like a surface swapchain that never needs
+    /// to be rebuilt. Images are acquired round-robin and "presenting" only
+    /// waits for rendering to finish, so the results can be copied out for
+    /// capture or recording. The first preferred surface format which
+    /// supports the requested image usage is used and the present mode
+    /// preferences are ignored.
+    pub fn offscreen(
+        cxt: Arc<VulkanContext>,
+        framebuffer_size: (u32, u32),
+        settings: &SwapchainSettings,
+    ) -> Result<Self> {
+        let extent = vk::Extent2D {
+            width: framebuffer_size.0,
+            height: framebuffer_size.1,
+        };
+        let (offscreen, format) = unwrap_here!(
+            "Create offscreen swapchain images",
+            OffscreenImages::new(&cxt, extent, settings)
+        );
+        let images = offscreen.raw_images();
+        let image_views = unwrap_here!(
+            "Create offscreen swapchain image views",
+            create_image_views(&cxt, &images, format.format)
+        );
         Ok(Self {
-            swapchain,
+            swapchain: SwapchainImages::Offscreen(offscreen),
             extent,
             format,
+            present_mode: vk::PresentModeKHR::IMMEDIATE,
+            image_usage: settings.image_usage,
             images,
             image_views,
             cxt,
@@ -89,8 +245,19 @@ impl Swapchain {
     }
 
     /// Returns the non-owning Vulkan swapchain handle.
+    ///
+    /// This is null for offscreen swapchains.
     pub fn raw(&self) -> vk::SwapchainKHR {
-        self.swapchain.raw
+        match &self.swapchain {
+            SwapchainImages::Surface { swapchain, .. } => swapchain.raw,
+            SwapchainImages::Offscreen(_) => vk::SwapchainKHR::null(),
+        }
+    }
+
+    /// Returns true when the Swapchain's images are never presented to the
+    /// window surface.
+    pub fn is_offscreen(&self) -> bool {
+        matches!(self.swapchain, SwapchainImages::Offscreen(_))
     }
 
     /// Returns the Swapchain's current extent.
@@ -122,6 +289,29 @@ impl Swapchain {
         self.format.format
     }
 
+    /// Returns the Swapchain's color space.
+    ///
+    /// Shaders can use this to decide how to encode their output, e.g. when
+    /// the format is UNORM or the color space is HDR.
+    pub fn color_space(&self) -> vk::ColorSpaceKHR {
+        self.format.color_space
+    }
+
+    /// Returns the Swapchain's image format and color space.
+    pub fn surface_format(&self) -> vk::SurfaceFormatKHR {
+        self.format
+    }
+
+    /// Returns the present mode selected when the Swapchain was created.
+    pub fn present_mode(&self) -> vk::PresentModeKHR {
+        self.present_mode
+    }
+
+    /// Returns the usage flags the Swapchain's images were created with.
+    pub fn image_usage(&self) -> vk::ImageUsageFlags {
+        self.image_usage
+    }
+
     /// Returns the Swapchain's image handles.
     pub fn images(&self) -> &[vk::Image] {
         &self.images
@@ -143,9 +333,17 @@ impl Swapchain {
         &self,
         image_ready_semaphore: vk::Semaphore,
     ) -> Result<AcquireImageStatus> {
+        let swapchain = match &self.swapchain {
+            SwapchainImages::Surface { swapchain, .. } => swapchain,
+            SwapchainImages::Offscreen(offscreen) => {
+                let index =
+                    offscreen.acquire(&self.cxt, image_ready_semaphore)?;
+                return Ok(AcquireImageStatus::ImageAcquired(index));
+            }
+        };
         let result = unsafe {
-            self.swapchain.ext.acquire_next_image(
-                self.swapchain.raw,
+            swapchain.ext.acquire_next_image(
+                swapchain.raw,
                 u64::MAX,
                 image_ready_semaphore,
                 vk::Fence::null(),
@@ -177,16 +375,23 @@ impl Swapchain {
         wait_semaphore: vk::Semaphore,
         image_index: u32,
     ) -> Result<PresentImageStatus> {
+        let swapchain = match &self.swapchain {
+            SwapchainImages::Surface { swapchain, .. } => swapchain,
+            SwapchainImages::Offscreen(offscreen) => {
+                offscreen.present(&self.cxt, wait_semaphore)?;
+                return Ok(PresentImageStatus::Queued);
+            }
+        };
         let present_info = vk::PresentInfoKHR {
             wait_semaphore_count: 1,
             p_wait_semaphores: &wait_semaphore,
             swapchain_count: 1,
-            p_swapchains: &self.swapchain.raw,
+            p_swapchains: &swapchain.raw,
             p_image_indices: &image_index,
             ..Default::default()
         };
         let result = unsafe {
-            self.swapchain
+            swapchain
                 .ext
                 .queue_present(self.cxt.graphics_queue, &present_info)
         };
@@ -203,8 +408,16 @@ impl Swapchain {
 
 impl std::fmt::Debug for Swapchain {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Swapchain")
-            .field("swapchain", &self.swapchain)
+        let mut debug = f.debug_struct("Swapchain");
+        match &self.swapchain {
+            SwapchainImages::Surface { swapchain, .. } => {
+                debug.field("swapchain", swapchain)
+            }
+            SwapchainImages::Offscreen(offscreen) => {
+                debug.field("offscreen", offscreen)
+            }
+        };
+        debug
             .field("extent", &self.extent)
             .field("format", &self.format)
             .field("images", &self.images)
@@ -212,3 +425,38 @@ impl std::fmt::Debug for Swapchain {
             .finish()
     }
 }
+
+/// Creates one color view for each swapchain image.
+fn create_image_views(
+    cxt: &VulkanContext,
+    images: &[vk::Image],
+    format: vk::Format,
+) -> Result<Vec<raii::ImageView>> {
+    let mut image_views = vec![];
+    for (index, image) in images.iter().enumerate() {
+        let create_info = vk::ImageViewCreateInfo {
+            image: *image,
+            view_type: vk::ImageViewType::TYPE_2D,
+            format,
+            components: vk::ComponentMapping::default(),
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            ..Default::default()
+        };
+        let image_view = unwrap_here!(
+            format!("Create swapchain image view [{}]", index),
+            raii::ImageView::new(
+                format!("Swapchain Image [{}]", index),
+                cxt.device.clone(),
+                &create_info,
+            )
+        );
+        image_views.push(image_view);
+    }
+    Ok(image_views)
+}
@@ -0,0 +1,165 @@
+use {
+    super::SwapchainSettings,
+    crate::{
+        graphics::vulkan::{raii, OwnedBlock, VulkanContext},
+        unwrap_here,
+    },
+    anyhow::{bail, Context, Result},
+    ash::vk,
+    std::sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The default number of images in an offscreen swapchain.
+const DEFAULT_IMAGE_COUNT: u32 = 3;
+
+/// Device-local images which stand in for a surface's swapchain images.
+///
+/// Images are handed out round-robin. Acquiring and presenting submit empty
+/// batches which signal and wait on the frame's semaphores, so callers
+/// synchronize exactly as they would with a real swapchain.
+#[derive(Debug)]
+pub(super) struct OffscreenImages {
+    images: Vec<(raii::Image, OwnedBlock)>,
+    next_index: AtomicUsize,
+}
+
+impl OffscreenImages {
+    /// Allocates images using the first preferred format which supports the
+    /// requested usage.
+    pub fn new(
+        cxt: &VulkanContext,
+        extent: vk::Extent2D,
+        settings: &SwapchainSettings,
+    ) -> Result<(Self, vk::SurfaceFormatKHR)> {
+        let format = unwrap_here!(
+            "Select offscreen image format",
+            select_format(cxt, settings)
+        );
+        let image_count =
+            settings.image_count.unwrap_or(DEFAULT_IMAGE_COUNT).max(1);
+
+        let mut images = vec![];
+        for _ in 0..image_count {
+            let (block, image) = unwrap_here!(
+                "Allocate offscreen swapchain image",
+                OwnedBlock::allocate_image(
+                    cxt.allocator.clone(),
+                    &vk::ImageCreateInfo {
+                        image_type: vk::ImageType::TYPE_2D,
+                        format: format.format,
+                        extent: vk::Extent3D {
+                            width: extent.width,
+                            height: extent.height,
+                            depth: 1,
+                        },
+                        mip_levels: 1,
+                        array_layers: 1,
+                        samples: vk::SampleCountFlags::TYPE_1,
+                        tiling: vk::ImageTiling::OPTIMAL,
+                        usage: settings.image_usage,
+                        sharing_mode: vk::SharingMode::EXCLUSIVE,
+                        queue_family_index_count: 1,
+                        p_queue_family_indices: &cxt
+                            .graphics_queue_family_index,
+                        initial_layout: vk::ImageLayout::UNDEFINED,
+                        ..Default::default()
+                    },
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                )
+            );
+            images.push((image, block));
+        }
+
+        Ok((
+            Self {
+                images,
+                next_index: AtomicUsize::new(0),
+            },
+            format,
+        ))
+    }
+
+    /// Returns the raw image handles.
+    pub fn raw_images(&self) -> Vec<vk::Image> {
+        self.images.iter().map(|(image, _)| image.raw).collect()
+    }
+
+    /// Returns the next image index and signals `image_ready_semaphore`
+    /// once all previously submitted work has completed.
+    pub fn acquire(
+        &self,
+        cxt: &VulkanContext,
+        image_ready_semaphore: vk::Semaphore,
+    ) -> Result<u32> {
+        let index =
+            self.next_index.fetch_add(1, Ordering::Relaxed) % self.images.len();
+        if image_ready_semaphore != vk::Semaphore::null() {
+            let submit_info = vk::SubmitInfo {
+                signal_semaphore_count: 1,
+                p_signal_semaphores: &image_ready_semaphore,
+                ..Default::default()
+            };
+            unsafe {
+                cxt.queue_submit(
+                    cxt.graphics_queue,
+                    &[submit_info],
+                    vk::Fence::null(),
+                )
+                .context("Unable to signal offscreen image acquired")?;
+            }
+        }
+        Ok(index as u32)
+    }
+
+    /// Consumes the semaphore which would have been waited on by
+    /// presentation.
+    pub fn present(
+        &self,
+        cxt: &VulkanContext,
+        wait_semaphore: vk::Semaphore,
+    ) -> Result<()> {
+        let wait_stage = vk::PipelineStageFlags::ALL_COMMANDS;
+        let submit_info = vk::SubmitInfo {
+            wait_semaphore_count: 1,
+            p_wait_semaphores: &wait_semaphore,
+            p_wait_dst_stage_mask: &wait_stage,
+            ..Default::default()
+        };
+        unsafe {
+            cxt.queue_submit(
+                cxt.graphics_queue,
+                &[submit_info],
+                vk::Fence::null(),
+            )
+            .context("Unable to wait for offscreen image rendering")
+        }
+    }
+}
+
+/// Selects the first preferred surface format whose format supports the
+/// requested image usage.
+fn select_format(
+    cxt: &VulkanContext,
+    settings: &SwapchainSettings,
+) -> Result<vk::SurfaceFormatKHR> {
+    for surface_format in &settings.surface_formats {
+        let supported = unsafe {
+            cxt.instance.get_physical_device_image_format_properties(
+                cxt.physical_device,
+                surface_format.format,
+                vk::ImageType::TYPE_2D,
+                vk::ImageTiling::OPTIMAL,
+                settings.image_usage,
+                vk::ImageCreateFlags::empty(),
+            )
+        };
+        if supported.is_ok() {
+            return Ok(*surface_format);
+        }
+    }
+    bail!(
+        "None of the preferred formats {:?} support {:?}",
+        settings.surface_formats,
+        settings.image_usage
+    );
+}
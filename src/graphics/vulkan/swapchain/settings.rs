@@ -1,51 +1,128 @@
 use {
+    super::Swapchain,
     crate::{
         graphics::vulkan::{raii, VulkanContext},
         unwrap_here,
     },
-    anyhow::{Context, Result},
+    anyhow::{bail, Context, Result},
     ash::vk,
+    bon::Builder,
     std::sync::Arc,
 };
 
+/// Options used when creating a [Swapchain].
+///
+/// Preference lists are checked in order and the first option supported by
+/// the surface is used.
+#[derive(Debug, Clone, PartialEq, Eq, Builder)]
+pub struct SwapchainSettings {
+    /// Preferred present modes. FIFO is used if none are supported.
+    #[builder(into, default = Swapchain::NO_VSYNC_PRESENT_MODES.to_vec())]
+    pub present_modes: Vec<vk::PresentModeKHR>,
+
+    /// Preferred image formats and color spaces. The surface's first format
+    /// is used if none are supported.
+    #[builder(into, default = Swapchain::SRGB_SURFACE_FORMATS.to_vec())]
+    pub surface_formats: Vec<vk::SurfaceFormatKHR>,
+
+    /// How the swapchain images will be used, e.g. STORAGE for compute
+    /// shaders or TRANSFER_SRC for screenshots.
+    ///
+    /// Surface formats whose features don't support the usage are skipped,
+    /// e.g. sRGB formats usually can't be used as STORAGE images. Creating the
+    /// swapchain fails if the surface or none of its formats support the
+    /// usage.
+    #[builder(
+        default = vk::ImageUsageFlags::COLOR_ATTACHMENT
+            | vk::ImageUsageFlags::TRANSFER_DST
+    )]
+    pub image_usage: vk::ImageUsageFlags,
+
+    /// Preferred composite alpha modes. The first mode supported by the
+    /// surface is used if none are supported.
+    ///
+    /// Transparent windows also require the window itself to be created with
+    /// transparency.
+    #[builder(into, default = vec![vk::CompositeAlphaFlagsKHR::OPAQUE])]
+    pub composite_alpha: Vec<vk::CompositeAlphaFlagsKHR>,
+
+    /// The minimum number of swapchain images. This is clamped to the range
+    /// supported by the surface and defaults to the surface's minimum + 2.
+    pub image_count: Option<u32>,
+}
+
+impl Default for SwapchainSettings {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
 pub fn create_swapchain(
     cxt: &VulkanContext,
+    surface: &raii::Surface,
     framebuffer_size: (u32, u32),
     previous_swapchain: Option<vk::SwapchainKHR>,
-) -> Result<(Arc<raii::Swapchain>, vk::Extent2D, vk::SurfaceFormatKHR)> {
+    settings: &SwapchainSettings,
+) -> Result<(
+    Arc<raii::Swapchain>,
+    vk::Extent2D,
+    vk::SurfaceFormatKHR,
+    vk::PresentModeKHR,
+)> {
     let capabilities =
         unwrap_here!("Get device surface capabilities", unsafe {
-            cxt.surface_khr
-                .ext
-                .get_physical_device_surface_capabilities(
-                    cxt.physical_device,
-                    cxt.surface_khr.raw,
-                )
+            surface.ext.get_physical_device_surface_capabilities(
+                cxt.physical_device,
+                surface.raw,
+            )
         });
     log::trace!("Device capabilities:\n{:#?}", capabilities);
 
-    let format =
-        unwrap_here!("Select surface image format", select_image_format(cxt));
+    let format = unwrap_here!(
+        "Select surface image format",
+        select_image_format(
+            cxt,
+            surface,
+            &settings.surface_formats,
+            settings.image_usage
+        )
+    );
     let extent = select_image_extent(&capabilities, framebuffer_size);
+    let present_mode = unwrap_here!(
+        "Select surface present mode",
+        select_present_mode(cxt, surface, &settings.present_modes)
+    );
+    if !capabilities
+        .supported_usage_flags
+        .contains(settings.image_usage)
+    {
+        bail!(
+            "Swapchain image usage {:?} is not supported, supported usage: {:?}",
+            settings.image_usage,
+            capabilities.supported_usage_flags
+        );
+    }
     let queue_families = [cxt.graphics_queue_family_index];
     let create_info = vk::SwapchainCreateInfoKHR {
-        surface: cxt.surface_khr.raw,
-        min_image_count: select_image_count(&capabilities),
+        surface: surface.raw,
+        min_image_count: select_image_count(
+            &capabilities,
+            settings.image_count,
+        ),
         image_format: format.format,
         image_color_space: format.color_space,
         image_extent: extent,
         image_array_layers: 1,
-        image_usage: vk::ImageUsageFlags::COLOR_ATTACHMENT
-            | vk::ImageUsageFlags::TRANSFER_DST,
+        image_usage: settings.image_usage,
         image_sharing_mode: vk::SharingMode::EXCLUSIVE,
         queue_family_index_count: 1,
         p_queue_family_indices: queue_families.as_ptr(),
         pre_transform: capabilities.current_transform,
-        composite_alpha: vk::CompositeAlphaFlagsKHR::OPAQUE,
-        present_mode: unwrap_here!(
-            "Select surface present mode",
-            select_present_mode(cxt)
+        composite_alpha: select_composite_alpha(
+            &capabilities,
+            &settings.composite_alpha,
         ),
+        present_mode,
         clipped: vk::TRUE,
         old_swapchain: previous_swapchain.unwrap_or(vk::SwapchainKHR::null()),
         ..Default::default()
@@ -61,49 +138,138 @@ pub fn create_swapchain(
               - swapchain: {:#?}
               - extent: {:?}
               - format: {:?}
+              - present mode: {:?}
             "
         ),
         swapchain,
         extent,
-        format
+        format,
+        present_mode
     );
-    Ok((swapchain, extent, format))
+    Ok((swapchain, extent, format, present_mode))
 }
 
-/// Pick the desired image format for the swapchain.
-fn select_image_format(cxt: &VulkanContext) -> Result<vk::SurfaceFormatKHR> {
+/// Picks the first supported surface format from the preferences.
+///
+/// Only formats whose optimal tiling features support `image_usage` are
+/// considered. The first such format reported by the surface is used when
+/// none of the preferred formats are available.
+fn select_image_format(
+    cxt: &VulkanContext,
+    surface: &raii::Surface,
+    preferences: &[vk::SurfaceFormatKHR],
+    image_usage: vk::ImageUsageFlags,
+) -> Result<vk::SurfaceFormatKHR> {
     let surface_formats =
         unwrap_here!("List avialable surface formats", unsafe {
-            cxt.surface_khr.ext.get_physical_device_surface_formats(
+            surface.ext.get_physical_device_surface_formats(
                 cxt.physical_device,
-                cxt.surface_khr.raw,
+                surface.raw,
             )
         });
     log::trace!("Formats supported by device\n{:#?}", surface_formats);
 
-    let preferred = surface_formats.iter().find(|surface_format| {
-        let has_color_space =
-            surface_format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR;
-        let has_format = surface_format.format == vk::Format::B8G8R8A8_SRGB;
-        has_color_space && has_format
+    let required_features = format_features_for_usage(image_usage);
+    let usable_formats: Vec<vk::SurfaceFormatKHR> = surface_formats
+        .iter()
+        .copied()
+        .filter(|surface_format| {
+            let features = unsafe {
+                cxt.instance
+                    .get_physical_device_format_properties(
+                        cxt.physical_device,
+                        surface_format.format,
+                    )
+                    .optimal_tiling_features
+            };
+            features.contains(required_features)
+        })
+        .collect();
+
+    let preferred = preferences.iter().find(|preference| {
+        usable_formats.iter().any(|surface_format| {
+            surface_format.format == preference.format
+                && surface_format.color_space == preference.color_space
+        })
     });
 
-    let format = preferred.or(surface_formats.first()).context(
-        "Unable to find a suitable surface format for the swapchain!",
-    )?;
+    let format = preferred.or(usable_formats.first()).with_context(|| {
+        format!(
+            "None of the surface formats {:?} support image usage {:?}",
+            surface_formats, image_usage
+        )
+    })?;
 
     Ok(*format)
 }
 
-fn select_image_count(capabilities: &vk::SurfaceCapabilitiesKHR) -> u32 {
-    let count = capabilities.min_image_count + 2;
+/// Returns the format features an image format needs for the usage.
+fn format_features_for_usage(
+    usage: vk::ImageUsageFlags,
+) -> vk::FormatFeatureFlags {
+    [
+        (
+            vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::FormatFeatureFlags::TRANSFER_SRC,
+        ),
+        (
+            vk::ImageUsageFlags::TRANSFER_DST,
+            vk::FormatFeatureFlags::TRANSFER_DST,
+        ),
+        (
+            vk::ImageUsageFlags::SAMPLED,
+            vk::FormatFeatureFlags::SAMPLED_IMAGE,
+        ),
+        (
+            vk::ImageUsageFlags::STORAGE,
+            vk::FormatFeatureFlags::STORAGE_IMAGE,
+        ),
+        (
+            vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            vk::FormatFeatureFlags::COLOR_ATTACHMENT,
+        ),
+        (
+            vk::ImageUsageFlags::INPUT_ATTACHMENT,
+            vk::FormatFeatureFlags::COLOR_ATTACHMENT,
+        ),
+    ]
+    .into_iter()
+    .filter(|(image_usage, _)| usage.contains(*image_usage))
+    .fold(vk::FormatFeatureFlags::empty(), |features, (_, feature)| {
+        features | feature
+    })
+}
+
+fn select_image_count(
+    capabilities: &vk::SurfaceCapabilitiesKHR,
+    image_count: Option<u32>,
+) -> u32 {
+    let count = image_count.unwrap_or(capabilities.min_image_count + 2);
     if capabilities.max_image_count > 0 {
         count.clamp(capabilities.min_image_count, capabilities.max_image_count)
     } else {
-        count
+        count.max(capabilities.min_image_count)
     }
 }
 
+/// Picks the first supported composite alpha mode from the preferences.
+fn select_composite_alpha(
+    capabilities: &vk::SurfaceCapabilitiesKHR,
+    preferences: &[vk::CompositeAlphaFlagsKHR],
+) -> vk::CompositeAlphaFlagsKHR {
+    let supported = capabilities.supported_composite_alpha;
+    preferences
+        .iter()
+        .copied()
+        .find(|&mode| supported.contains(mode))
+        .unwrap_or_else(|| {
+            // The spec guarantees at least one bit is set. Use the lowest.
+            vk::CompositeAlphaFlagsKHR::from_raw(
+                supported.as_raw() & supported.as_raw().wrapping_neg(),
+            )
+        })
+}
+
 fn select_image_extent(
     capabilities: &vk::SurfaceCapabilitiesKHR,
     framebuffer_size: (u32, u32),
@@ -130,21 +296,85 @@ fn select_image_extent(
     }
 }
 
-fn select_present_mode(cxt: &VulkanContext) -> Result<vk::PresentModeKHR> {
+/// Picks the first supported present mode from the preferences.
+///
+/// FIFO is always supported so it's used when none of the preferred modes are
+/// available.
+fn select_present_mode(
+    cxt: &VulkanContext,
+    surface: &raii::Surface,
+    preferences: &[vk::PresentModeKHR],
+) -> Result<vk::PresentModeKHR> {
     let present_modes = unsafe {
-        cxt.surface_khr
-            .ext
-            .get_physical_device_surface_present_modes(
-                cxt.physical_device,
-                cxt.surface_khr.raw,
-            )?
+        surface.ext.get_physical_device_surface_present_modes(
+            cxt.physical_device,
+            surface.raw,
+        )?
     };
     log::trace!("Present modes for device:\n{:#?}", present_modes);
-    if present_modes.contains(&vk::PresentModeKHR::MAILBOX) {
-        Ok(vk::PresentModeKHR::MAILBOX)
-    } else if present_modes.contains(&vk::PresentModeKHR::IMMEDIATE) {
-        Ok(vk::PresentModeKHR::IMMEDIATE)
-    } else {
-        Ok(vk::PresentModeKHR::FIFO)
+    Ok(preferences
+        .iter()
+        .find(|mode| present_modes.contains(mode))
+        .copied()
+        .unwrap_or(vk::PresentModeKHR::FIFO))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn unsupported_composite_alpha_should_fall_back_to_a_supported_mode() {
+        let capabilities = vk::SurfaceCapabilitiesKHR {
+            supported_composite_alpha: vk::CompositeAlphaFlagsKHR::INHERIT
+                | vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            select_composite_alpha(
+                &capabilities,
+                &[
+                    vk::CompositeAlphaFlagsKHR::OPAQUE,
+                    vk::CompositeAlphaFlagsKHR::INHERIT
+                ]
+            ),
+            vk::CompositeAlphaFlagsKHR::INHERIT
+        );
+        assert_eq!(
+            select_composite_alpha(
+                &capabilities,
+                &[vk::CompositeAlphaFlagsKHR::OPAQUE]
+            ),
+            vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED
+        );
+    }
+
+    #[test]
+    pub fn image_count_should_be_clamped_to_surface_limits() {
+        let capabilities = vk::SurfaceCapabilitiesKHR {
+            min_image_count: 2,
+            max_image_count: 3,
+            ..Default::default()
+        };
+
+        assert_eq!(select_image_count(&capabilities, None), 3);
+        assert_eq!(select_image_count(&capabilities, Some(1)), 2);
+        assert_eq!(select_image_count(&capabilities, Some(2)), 2);
+    }
+
+    #[test]
+    pub fn image_usage_should_require_matching_format_features() {
+        assert_eq!(
+            format_features_for_usage(
+                vk::ImageUsageFlags::STORAGE
+                    | vk::ImageUsageFlags::TRANSFER_SRC
+            ),
+            vk::FormatFeatureFlags::STORAGE_IMAGE
+                | vk::FormatFeatureFlags::TRANSFER_SRC
+        );
+        assert!(
+            format_features_for_usage(vk::ImageUsageFlags::empty()).is_empty()
+        );
     }
 }
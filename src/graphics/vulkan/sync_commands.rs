@@ -3,26 +3,331 @@ use {
         graphics::vulkan::{raii, VulkanContext},
         unwrap_here,
     },
-    anyhow::Result,
+    anyhow::{Context, Result},
     ash::vk,
-    std::sync::Arc,
+    std::sync::{Arc, Mutex},
 };
 
-/// A utility for synchronously submitting commands to the GPU.
+/// The maximum number of submissions which can be pending at once. Submitting
+/// more will block until the oldest submission completes.
+///
+/// Slots owned by a blocked [SyncCommands::submit_and_wait] call don't count
+/// towards the limit.
+const MAX_PENDING_SUBMISSIONS: usize = 4;
+
+/// A handle to a submission made with [SyncCommands::submit_async].
+///
+/// The ticket is complete once its value is reached by the SyncCommands
+/// timeline semaphore. Tickets can be polled, waited on, or passed to
+/// [FramesInFlight::add_ticket_wait](crate::graphics::vulkan::FramesInFlight::add_ticket_wait)
+/// so a frame's commands wait for the submission on the GPU.
+#[derive(Debug, Clone)]
+pub struct GpuTicket {
+    semaphore: Arc<raii::Semaphore>,
+    value: u64,
+}
+
+impl GpuTicket {
+    /// The timeline semaphore signaled when the submission completes.
+    pub fn semaphore(&self) -> vk::Semaphore {
+        self.semaphore.raw
+    }
+
+    /// The timeline value signaled when the submission completes.
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    /// Returns true when the submission has finished executing.
+    pub fn is_complete(&self) -> Result<bool> {
+        let current = unsafe {
+            self.semaphore
+                .device
+                .get_semaphore_counter_value(self.semaphore.raw)
+                .context("Get SyncCommands timeline value")?
+        };
+        Ok(current >= self.value)
+    }
+
+    /// Blocks until the submission has finished executing.
+    pub fn wait(&self) -> Result<()> {
+        unsafe {
+            self.semaphore
+                .device
+                .wait_semaphores(
+                    &vk::SemaphoreWaitInfo::default()
+                        .semaphores(&[self.semaphore.raw])
+                        .values(&[self.value]),
+                    u64::MAX,
+                )
+                .context("Wait for SyncCommands ticket")
+        }
+    }
+}
+
+/// A command buffer and the fence signaled when its commands complete.
 #[derive(Debug)]
-pub struct SyncCommands {
+struct CommandSlot {
     command_pool: raii::CommandPool,
     command_buffer: vk::CommandBuffer,
     fence: raii::Fence,
+    pending: bool,
+
+    /// Set while [SyncCommands::submit_and_wait] waits on the fence without
+    /// holding the state lock. The slot cannot be reused until it is cleared.
+    waiting: bool,
+}
+
+#[derive(Debug, Default)]
+struct SyncCommandsState {
+    slots: Vec<CommandSlot>,
+    timeline_semaphore: Option<Arc<raii::Semaphore>>,
+    timeline_value: u64,
+}
+
+/// A utility for submitting one-off commands to the GPU.
+///
+/// Commands can be submitted synchronously with [Self::submit_and_wait] or
+/// asynchronously with [Self::submit_async]. A small pool of command buffers
+/// and fences allows several asynchronous submissions to be in-flight at
+/// once.
+#[derive(Debug)]
+pub struct SyncCommands {
+    state: Mutex<SyncCommandsState>,
     cxt: Arc<VulkanContext>,
 }
 
 impl SyncCommands {
     pub fn new(cxt: Arc<VulkanContext>) -> Result<Self> {
+        let mut state = SyncCommandsState::default();
+        state.slots.push(unwrap_here!(
+            "Create initial command slot",
+            Self::create_slot(&cxt, 0)
+        ));
+        Ok(Self {
+            state: Mutex::new(state),
+            cxt,
+        })
+    }
+
+    /// Records commands with `build_commands`, submits them, and blocks until
+    /// they have finished executing.
+    pub fn submit_and_wait(
+        &self,
+        build_commands: impl FnOnce(vk::CommandBuffer) -> Result<()>,
+    ) -> Result<()> {
+        let (index, fence) = {
+            let mut state = self.state.lock().unwrap();
+            let index = unwrap_here!(
+                "Acquire a free command slot",
+                self.acquire_slot(&mut state)
+            );
+            let slot = &mut state.slots[index];
+            unwrap_here!(
+                "Record commands",
+                self.record(
+                    slot.command_pool.raw,
+                    slot.command_buffer,
+                    build_commands
+                )
+            );
+
+            unwrap_here!("Submit commands and signal fence", unsafe {
+                self.cxt.queue_submit(
+                    self.cxt.graphics_queue,
+                    &[vk::SubmitInfo::default()
+                        .command_buffers(&[slot.command_buffer])],
+                    slot.fence.raw,
+                )
+            });
+            slot.pending = true;
+            slot.waiting = true;
+            (index, slot.fence.raw)
+        };
+
+        // Other submissions can proceed while this one executes. The slot is
+        // marked as waiting so its fence isn't reset until the wait is done.
+        let result =
+            unsafe { self.cxt.wait_for_fences(&[fence], true, u64::MAX) };
+
+        let mut state = self.state.lock().unwrap();
+        let slot = &mut state.slots[index];
+        slot.waiting = false;
+        unwrap_here!("Wait for submission fence", result);
+        slot.pending = false;
+
+        Ok(())
+    }
+
+    /// Records commands with `build_commands` and submits them without
+    /// waiting for them to complete.
+    ///
+    /// The returned [GpuTicket] is signaled when the commands finish. Async
+    /// submissions require the Vulkan 1.2 `timeline_semaphore` device feature.
+    ///
+    /// Submissions are executed in order, so waiting on a ticket also means
+    /// every earlier submission has completed.
+    pub fn submit_async(
+        &self,
+        build_commands: impl FnOnce(vk::CommandBuffer) -> Result<()>,
+    ) -> Result<GpuTicket> {
+        let mut state = self.state.lock().unwrap();
+        let semaphore = if let Some(semaphore) = &state.timeline_semaphore {
+            semaphore.clone()
+        } else {
+            let mut semaphore_type_create_info = vk::SemaphoreTypeCreateInfo {
+                semaphore_type: vk::SemaphoreType::TIMELINE,
+                initial_value: 0,
+                ..Default::default()
+            };
+            let semaphore = Arc::new(unwrap_here!(
+                "Create SyncCommands timeline semaphore",
+                raii::Semaphore::new(
+                    "SyncCommands timeline",
+                    self.cxt.device.clone(),
+                    &vk::SemaphoreCreateInfo::default()
+                        .push_next(&mut semaphore_type_create_info),
+                )
+            ));
+            state.timeline_semaphore = Some(semaphore.clone());
+            semaphore
+        };
+
+        let index = unwrap_here!(
+            "Acquire a free command slot",
+            self.acquire_slot(&mut state)
+        );
+        state.timeline_value += 1;
+        let value = state.timeline_value;
+
+        let slot = &mut state.slots[index];
+        unwrap_here!(
+            "Record commands",
+            self.record(
+                slot.command_pool.raw,
+                slot.command_buffer,
+                build_commands
+            )
+        );
+
+        let signal_values = [value];
+        let mut timeline_submit_info =
+            vk::TimelineSemaphoreSubmitInfo::default()
+                .signal_semaphore_values(&signal_values);
+        unwrap_here!("Submit commands and signal timeline", unsafe {
+            self.cxt.queue_submit(
+                self.cxt.graphics_queue,
+                &[vk::SubmitInfo::default()
+                    .command_buffers(&[slot.command_buffer])
+                    .signal_semaphores(&[semaphore.raw])
+                    .push_next(&mut timeline_submit_info)],
+                slot.fence.raw,
+            )
+        });
+        slot.pending = true;
+
+        Ok(GpuTicket { semaphore, value })
+    }
+
+    /// Blocks until every pending submission has completed.
+    pub fn wait_idle(&self) -> Result<()> {
+        let state = self.state.lock().unwrap();
+        let fences = state
+            .slots
+            .iter()
+            .filter(|slot| slot.pending)
+            .map(|slot| slot.fence.raw)
+            .collect::<Vec<vk::Fence>>();
+        if fences.is_empty() {
+            return Ok(());
+        }
+        unsafe {
+            self.cxt
+                .wait_for_fences(&fences, true, u64::MAX)
+                .context("Wait for all pending SyncCommands submissions")
+        }
+    }
+
+    /// Returns the index of a slot which is ready to record new commands.
+    ///
+    /// Completed slots are reused, new slots are created until there are
+    /// [MAX_PENDING_SUBMISSIONS] that aren't waiting, and then this blocks
+    /// until a pending slot completes.
+    fn acquire_slot(&self, state: &mut SyncCommandsState) -> Result<usize> {
+        loop {
+            for (index, slot) in state.slots.iter_mut().enumerate() {
+                if slot.waiting {
+                    continue;
+                }
+                if slot.pending {
+                    let complete =
+                        unsafe { self.cxt.get_fence_status(slot.fence.raw)? };
+                    if !complete {
+                        continue;
+                    }
+                    slot.pending = false;
+                }
+                unsafe { self.cxt.reset_fences(&[slot.fence.raw])? };
+                return Ok(index);
+            }
+
+            let fences = state
+                .slots
+                .iter()
+                .filter(|slot| !slot.waiting)
+                .map(|slot| slot.fence.raw)
+                .collect::<Vec<vk::Fence>>();
+            if fences.len() < MAX_PENDING_SUBMISSIONS {
+                let index = state.slots.len();
+                state.slots.push(Self::create_slot(&self.cxt, index)?);
+                return Ok(index);
+            }
+
+            unsafe { self.cxt.wait_for_fences(&fences, false, u64::MAX)? };
+        }
+    }
+
+    /// Resets the command pool and records the command buffer.
+    fn record(
+        &self,
+        command_pool: vk::CommandPool,
+        command_buffer: vk::CommandBuffer,
+        build_commands: impl FnOnce(vk::CommandBuffer) -> Result<()>,
+    ) -> Result<()> {
+        unwrap_here!("Reset command pool", unsafe {
+            self.cxt.reset_command_pool(
+                command_pool,
+                vk::CommandPoolResetFlags::empty(),
+            )
+        });
+
+        unwrap_here!("Begin command buffer one time submit", unsafe {
+            self.cxt.begin_command_buffer(
+                command_buffer,
+                &vk::CommandBufferBeginInfo {
+                    flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+                    ..Default::default()
+                },
+            )
+        });
+
+        unwrap_here!(
+            "Add commands to the buffer",
+            build_commands(command_buffer)
+        );
+
+        unwrap_here!("End command buffer", unsafe {
+            self.cxt.end_command_buffer(command_buffer)
+        });
+
+        Ok(())
+    }
+
+    fn create_slot(cxt: &VulkanContext, index: usize) -> Result<CommandSlot> {
         let command_pool = unwrap_here!(
             "Create command pool",
             raii::CommandPool::new(
-                "SyncCommands",
+                format!("SyncCommands [{}]", index),
                 cxt.device.clone(),
                 &vk::CommandPoolCreateInfo {
                     flags: vk::CommandPoolCreateFlags::TRANSIENT,
@@ -46,74 +351,24 @@ impl SyncCommands {
         let fence = unwrap_here!(
             "Create command fence",
             raii::Fence::new(
-                "SyncCommands",
+                format!("SyncCommands [{}]", index),
                 cxt.device.clone(),
                 &vk::FenceCreateInfo::default(),
             )
         );
-        Ok(Self {
+        Ok(CommandSlot {
             command_pool,
             command_buffer,
             fence,
-            cxt,
+            pending: false,
+            waiting: false,
         })
     }
+}
 
-    pub fn submit_and_wait(
-        &self,
-        build_commands: impl FnOnce(vk::CommandBuffer) -> Result<()>,
-    ) -> Result<()> {
-        unwrap_here!("Reset command pool", unsafe {
-            self.cxt.reset_command_pool(
-                self.command_pool.raw,
-                vk::CommandPoolResetFlags::empty(),
-            )
-        });
-
-        unwrap_here!("Begin command buffer one time submit", unsafe {
-            self.cxt.begin_command_buffer(
-                self.command_buffer,
-                &vk::CommandBufferBeginInfo {
-                    flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
-                    ..Default::default()
-                },
-            )
-        });
-
-        unwrap_here!(
-            "Add commands to the buffer",
-            build_commands(self.command_buffer)
-        );
-
-        unwrap_here!("End command buffer", unsafe {
-            self.cxt.end_command_buffer(self.command_buffer)
-        });
-
-        unwrap_here!("Submit commands and signal fence", unsafe {
-            self.cxt.queue_submit(
-                self.cxt.graphics_queue,
-                &[vk::SubmitInfo {
-                    wait_semaphore_count: 0,
-                    p_wait_semaphores: std::ptr::null(),
-                    p_wait_dst_stage_mask: std::ptr::null(),
-                    command_buffer_count: 1,
-                    p_command_buffers: &self.command_buffer,
-                    signal_semaphore_count: 0,
-                    p_signal_semaphores: std::ptr::null(),
-                    ..Default::default()
-                }],
-                self.fence.raw,
-            )
-        });
-
-        unwrap_here!("Wait for submission fence", unsafe {
-            self.cxt.wait_for_fences(&[self.fence.raw], true, u64::MAX)
-        });
-
-        unwrap_here!("Reset fence after commands complete", unsafe {
-            self.cxt.reset_fences(&[self.fence.raw])
-        });
-
-        Ok(())
+impl Drop for SyncCommands {
+    fn drop(&mut self) {
+        // command pools cannot be destroyed while their commands are pending
+        self.wait_idle().unwrap();
     }
 }
@@ -33,7 +33,7 @@ impl Demo for DeviceBufferAddressTest {
     }
 
     fn new(
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         gfx: &mut Graphics,
         _args: &Self::Args,
     ) -> Result<Self> {
@@ -62,7 +62,7 @@ impl Demo for DeviceBufferAddressTest {
 
     fn update(
         &mut self,
-        #[allow(unused_variables)] window: &mut Window,
+        #[allow(unused_variables)] window: Option<&mut Window>,
         #[allow(unused_variables)] gfx: &mut Graphics,
     ) -> Result<AppState> {
         Ok(AppState::Exit)
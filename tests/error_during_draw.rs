@@ -22,7 +22,7 @@ impl Demo for ErrorDuringDraw {
     type Args = Args;
 
     fn new(
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         _gfx: &mut Graphics,
         _args: &Self::Args,
     ) -> Result<Self> {
@@ -31,7 +31,7 @@ impl Demo for ErrorDuringDraw {
 
     fn draw(
         &mut self,
-        _window: &mut Window,
+        _window: Option<&mut Window>,
         _gfx: &mut Graphics,
         _frame: &Frame,
     ) -> Result<AppState> {
@@ -0,0 +1,117 @@
+//! This test runs a demo with `--offscreen`, which must not need a window,
+//! surface, or display connection, and checks that the last frame's pixels can
+//! be read back once the demo has rendered a few frames.
+
+use {
+    anyhow::{bail, Result},
+    ash::vk,
+    clap::Parser,
+    demo_vk::{
+        app::AppState,
+        demo::{demo_main_with_args, Demo, Graphics},
+        graphics::{vulkan::Frame, Barriers, ImageUsage},
+    },
+    winit::window::Window,
+};
+
+/// The number of frames to render before reading back the last one.
+const FRAME_COUNT: u32 = 3;
+
+/// Red, so swizzled BGRA pixels would not match.
+const CLEAR_COLOR: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+#[derive(Debug, Parser)]
+struct Args {}
+
+struct OffscreenDemo {
+    frames_drawn: u32,
+}
+
+impl Demo for OffscreenDemo {
+    type Args = Args;
+    const INITIAL_WINDOW_SIZE: (i32, i32) = (64, 32);
+
+    fn new(
+        window: Option<&mut Window>,
+        gfx: &mut Graphics,
+        _args: &Self::Args,
+    ) -> Result<Self> {
+        if window.is_some() || !gfx.is_offscreen() {
+            bail!("Expected the demo to run offscreen without a window");
+        }
+        if gfx.vulkan.surface_khr.is_some() {
+            bail!("Expected a headless Vulkan context");
+        }
+        Ok(Self { frames_drawn: 0 })
+    }
+
+    fn update(
+        &mut self,
+        _window: Option<&mut Window>,
+        gfx: &mut Graphics,
+    ) -> Result<AppState> {
+        if self.frames_drawn < FRAME_COUNT {
+            return Ok(AppState::Continue);
+        }
+
+        let pixels = gfx.last_frame_pixels()?;
+        assert_eq!(pixels.dimensions(), (64, 32));
+        for pixel in pixels.pixels() {
+            assert_eq!(pixel.0, [255, 0, 0, 255]);
+        }
+        Ok(AppState::Exit)
+    }
+
+    fn draw(
+        &mut self,
+        _window: Option<&mut Window>,
+        gfx: &mut Graphics,
+        frame: &Frame,
+    ) -> Result<AppState> {
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        Barriers::new()
+            .image(
+                frame.swapchain_image(),
+                vk::ImageAspectFlags::COLOR,
+                ImageUsage::Undefined,
+                ImageUsage::TransferDst,
+            )
+            .record(&gfx.vulkan, frame.command_buffer())?;
+        unsafe {
+            gfx.vulkan.cmd_clear_color_image(
+                frame.command_buffer(),
+                frame.swapchain_image(),
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &vk::ClearColorValue {
+                    float32: CLEAR_COLOR,
+                },
+                &[subresource_range],
+            );
+        }
+        Barriers::new()
+            .image(
+                frame.swapchain_image(),
+                vk::ImageAspectFlags::COLOR,
+                ImageUsage::TransferDst,
+                ImageUsage::Present,
+            )
+            .record(&gfx.vulkan, frame.command_buffer())?;
+
+        self.frames_drawn += 1;
+        Ok(AppState::Continue)
+    }
+}
+
+fn main() {
+    let result = demo_main_with_args::<OffscreenDemo, _, _>([
+        "offscreen_demo",
+        "--offscreen",
+    ]);
+    assert!(result.is_ok());
+}
@@ -0,0 +1,55 @@
+//! This test verifies that textures loaded from image files are tracked in the
+//! sampled layout once the upload finishes. Later barriers derive their source
+//! from the tracked state, so a texture left tracked as UNDEFINED would have
+//! its uploaded pixels discarded.
+
+use {
+    anyhow::Result,
+    clap::Parser,
+    demo_vk::{
+        app::AppState,
+        demo::{demo_main, Demo, Graphics},
+        graphics::{streaming_renderer::TextureLoader, ImageUsage},
+    },
+    winit::window::Window,
+};
+
+#[derive(Debug, Parser)]
+struct Args {}
+
+struct TextureLoaderAccessTest;
+
+impl Demo for TextureLoaderAccessTest {
+    type Args = Args;
+
+    fn new(
+        _window: Option<&mut Window>,
+        gfx: &mut Graphics,
+        _args: &Self::Args,
+    ) -> Result<Self> {
+        let path = std::env::temp_dir().join("texture_loader_access.png");
+        image::RgbaImage::from_pixel(16, 16, image::Rgba([255, 0, 255, 255]))
+            .save(&path)?;
+
+        let mut loader = TextureLoader::new(gfx.vulkan.clone())?;
+        for generate_mipmaps in [false, true] {
+            let texture = loader.load_from_file(&path, generate_mipmaps)?;
+            assert_eq!(texture.access(), ImageUsage::Sampled.access());
+        }
+
+        Ok(Self {})
+    }
+
+    fn update(
+        &mut self,
+        #[allow(unused_variables)] window: Option<&mut Window>,
+        #[allow(unused_variables)] gfx: &mut Graphics,
+    ) -> Result<AppState> {
+        Ok(AppState::Exit)
+    }
+}
+
+fn main() {
+    let result = demo_main::<TextureLoaderAccessTest>();
+    assert!(result.is_ok());
+}